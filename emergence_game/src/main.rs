@@ -1,22 +1,35 @@
+use bevy::asset::AssetPlugin;
 use bevy::prelude::*;
 use bevy::window::{PresentMode, WindowPlugin};
+use emergence_lib::signals::config_file::SignalConfigFilePlugin;
 use emergence_lib::simulation::generation::GenerationConfig;
 
 fn main() {
     App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
-            window: WindowDescriptor {
-                title: "Emergence".to_string(),
-                // choose `AutoNoVsync` as it is more widely supported than `Immediate`
-                present_mode: PresentMode::AutoNoVsync,
-                ..Default::default()
-            },
-            ..Default::default()
-        }))
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    window: WindowDescriptor {
+                        title: "Emergence".to_string(),
+                        // choose `AutoNoVsync` as it is more widely supported than `Immediate`
+                        present_mode: PresentMode::AutoNoVsync,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                // Reload config files like signals.ron when they are edited
+                .set(AssetPlugin {
+                    watch_for_changes: true,
+                    ..Default::default()
+                }),
+        )
         .add_plugin(emergence_lib::simulation::SimulationPlugin {
             gen_config: GenerationConfig::default(),
         })
         .add_plugin(emergence_lib::InteractionPlugin)
         .add_plugin(emergence_lib::graphics::GraphicsPlugin)
+        .add_plugin(SignalConfigFilePlugin {
+            path: "signals/default.signals.ron".to_string(),
+        })
         .run();
 }
@@ -0,0 +1,297 @@
+//! Compares the dense, parallel signal solver against the per-tile hash map solver it replaced.
+//!
+//! Run with `cargo bench -p emergence_lib --bench signal_diffusion`.
+//!
+//! Both solvers simulate the same signals on a large map, and the mean time per tick is reported
+//! against the budget of a single frame at 60 FPS.
+
+use emergence_lib::signals::configs::SignalConfigs;
+use emergence_lib::signals::diffusion::tick_signals;
+use emergence_lib::signals::emitters::{Emitter, StockEmitter};
+use emergence_lib::signals::field::SignalField;
+use emergence_lib::simulation::map::resources::MapResource;
+use emergence_lib::simulation::map::{MapGeometry, MapPositions, MapShape};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+/// The radius of the benchmarked map
+const MAP_RADIUS: u32 = 150;
+
+/// The number of emitters whose signals are simulated
+const N_EMITTERS: usize = 12;
+
+/// The time available to simulate a frame at 60 FPS
+const FRAME_BUDGET: Duration = Duration::from_micros(16_667);
+
+/// The solver that the dense field replaced.
+///
+/// Everything in this module is copied verbatim from `signals/diffusion.rs`, `signals/mod.rs` and
+/// `signals/tile_signals.rs` as they were before signals were stored in dense layers,
+/// leaving out the methods that the benchmark does not call.
+#[allow(unused_mut)]
+mod baseline {
+    use bevy::utils::HashMap;
+    use emergence_lib::signals::configs::SignalConfigs;
+    use emergence_lib::signals::emitters::Emitter;
+    use emergence_lib::simulation::map::resources::MapResource;
+
+    /// The largest fraction of its value that a signal may send to its neighbors in a single substep.
+    ///
+    /// Keeping this below one ensures that signals never become negative, and do not oscillate.
+    const MAX_OUTFLOW: f32 = 0.5;
+
+    /// The number of neighbors of a tile that is not on the edge of the map
+    const MAX_NEIGHBORS: f32 = 6.0;
+
+    /// A diffusible signal at a given tile.
+    #[derive(Default, Debug, Clone, Copy)]
+    pub struct Signal {
+        /// The value of the signal at this tick.
+        current_value: f32,
+        /// The amount of signal that will be coming into this tile this tick.
+        ///
+        /// Generally, this will be based on [`current_value`](Signal::current_value) of neighboring tiles.
+        incoming: f32,
+        /// The amount of signal that will be leaving this tile this tick.
+        ///
+        /// Generally, this will be based on [`current_value`](Signal::current_value).
+        outgoing: f32,
+    }
+
+    impl Signal {
+        /// Create new [`Signal`] with given starting `value` and `color`.
+        pub fn new(value: f32) -> Signal {
+            Signal {
+                current_value: value,
+                incoming: 0.0,
+                outgoing: 0.0,
+            }
+        }
+
+        /// Apply accumulated `incoming`/`outgoing` to the `current_value`, while ensuring that the
+        /// signal's value does not go below `0.0`.
+        ///
+        /// `incoming` and `outgoing` are reset to `0.0` once applied.
+        fn apply_deltas(&mut self) {
+            self.current_value = (self.current_value + self.incoming - self.outgoing).max(0.0);
+            self.incoming = 0.0;
+            self.outgoing = 0.0;
+        }
+    }
+
+    /// Keeps track of the different signals present at a tile.
+    ///
+    /// Internally it is a [`HashMap`] with keys of type [`Emitter`] and values of type [`Signal`].
+    ///
+    /// It provides various public interfaces to interact with signals.
+    #[derive(Default, Debug)]
+    pub struct TileSignals {
+        /// Internal [`HashMap`] mapping emitters to signals
+        map: HashMap<Emitter, Signal>,
+    }
+
+    impl TileSignals {
+        /// Get the current values of the signals at this tile.
+        pub fn current_values(&self) -> Vec<(Emitter, f32)> {
+            self.map
+                .iter()
+                .map(|(emitter, signal)| (*emitter, signal.current_value))
+                .collect()
+        }
+
+        /// Increments a signal's `current_value` by the given value.
+        ///
+        /// If the signal does not exist, it inserts a new signal, with `incoming`/`outgoing` values
+        /// set to `0.0`.
+        pub fn increment(&mut self, emitter: &Emitter, increment: f32) {
+            if let Some(mut signal) = self.map.get_mut(emitter) {
+                signal.current_value += increment
+            } else {
+                self.map.insert(*emitter, Signal::new(increment));
+            }
+        }
+
+        /// Increment the change in signal due to signal entering this tile.
+        ///
+        /// If there is no signal with the specified `Emitter`, a new one will be initialized.
+        ///
+        /// This change will be applied before the next tick, but after all diffusion has been done.
+        pub fn increment_incoming(&mut self, emitter: &Emitter, delta: f32) {
+            if let Some(mut signal) = self.map.get_mut(emitter) {
+                signal.incoming += delta;
+            } else {
+                let mut new_signal = Signal::new(0.0);
+                new_signal.incoming = delta;
+                self.map.insert(*emitter, new_signal);
+            }
+        }
+
+        /// Increment the change in signal due to signal leaving this tile.
+        ///
+        /// Panics if there is no signal from the specified `Emitter`.
+        ///
+        /// This change will be applied before the next tick, but after all diffusion has been done.
+        pub fn increment_outgoing(&mut self, emitter: &Emitter, delta: f32) {
+            let mut signal = self.map.get_mut(emitter).unwrap();
+            signal.outgoing += delta;
+        }
+
+        /// Decay signal at the tile.
+        ///
+        /// Panics if there is no signal from the specified `Emitter`.
+        pub fn decay(&mut self, signal_configs: &SignalConfigs) {
+            for (emitter, signal) in self.map.iter_mut() {
+                let config = signal_configs.get(emitter).unwrap();
+                signal.current_value *= 1.0 - config.decay_probability;
+            }
+        }
+
+        /// Apply accumulated `incoming`/`outgoing` to the `current_value` for each signal at this tile.
+        pub fn apply_deltas(&mut self) {
+            for signal in self.map.values_mut() {
+                signal.apply_deltas();
+            }
+        }
+    }
+
+    /// The number of substeps each tick must be split into, so that no signal moves more than
+    /// [`MAX_OUTFLOW`] of its value in a single substep.
+    pub fn diffusion_substeps(signal_configs: &SignalConfigs) -> u32 {
+        signal_configs
+            .iter()
+            .map(|(_, config)| {
+                (MAX_NEIGHBORS * config.diffusion_factor / MAX_OUTFLOW).ceil() as u32
+            })
+            .max()
+            .unwrap_or_default()
+            .max(1)
+    }
+
+    /// Simulates a single tick of decay and diffusion for every signal on the map.
+    pub fn tick_signals(
+        map_signals: &mut MapResource<TileSignals>,
+        signal_configs: &SignalConfigs,
+    ) {
+        for tile_signals in map_signals.values_mut() {
+            tile_signals.decay(signal_configs);
+        }
+
+        diffuse(map_signals, signal_configs);
+    }
+
+    /// Moves signal between neighboring tiles, at the rate set by each signal's
+    /// [`diffusion_factor`](emergence_lib::signals::configs::SignalConfig::diffusion_factor).
+    fn diffuse(map_signals: &mut MapResource<TileSignals>, signal_configs: &SignalConfigs) {
+        let substeps = diffusion_substeps(signal_configs);
+        let (index, tiles) = map_signals.index_and_mut_slice();
+
+        for _ in 0..substeps {
+            for tile in 0..tiles.len() {
+                for (emitter, value) in tiles[tile].current_values() {
+                    let rate = match signal_configs.get(&emitter) {
+                        Some(config) => config.diffusion_factor / substeps as f32,
+                        None => continue,
+                    };
+                    if rate <= 0.0 || value <= 0.0 {
+                        continue;
+                    }
+
+                    let delta = rate * value;
+                    let mut total_outgoing = 0.0;
+                    // Patches include their center, which is not a neighbor
+                    for neighbor in index
+                        .patch(tile)
+                        .iter()
+                        .filter(|neighbor| **neighbor != tile)
+                    {
+                        tiles[*neighbor].increment_incoming(&emitter, delta);
+                        total_outgoing += delta;
+                    }
+                    tiles[tile].increment_outgoing(&emitter, total_outgoing);
+                }
+            }
+
+            for tile_signals in tiles.iter_mut() {
+                tile_signals.apply_deltas();
+            }
+        }
+    }
+}
+
+/// Runs `tick` the given number of times, returning the mean time taken per tick
+fn time_per_tick(ticks: u32, mut tick: impl FnMut()) -> Duration {
+    // Warm up caches, and the task pool
+    tick();
+
+    let start = Instant::now();
+    for _ in 0..ticks {
+        tick();
+    }
+    start.elapsed() / ticks
+}
+
+/// Prints the time taken per tick, relative to the frame budget
+fn report(name: &str, per_tick: Duration) {
+    println!(
+        "{name:>10}: {:>9.3} ms/tick ({:>5.1}% of a 60 FPS frame)",
+        per_tick.as_secs_f64() * 1000.0,
+        100.0 * per_tick.as_secs_f64() / FRAME_BUDGET.as_secs_f64()
+    );
+}
+
+fn main() {
+    let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: MAP_RADIUS });
+    let map_positions = MapPositions::new(&map_geometry);
+
+    // Add custom emitters until there are enough signals to simulate
+    let mut signal_configs = SignalConfigs::default();
+    let custom_config = *signal_configs
+        .get(&Emitter::Stock(StockEmitter::Ant))
+        .unwrap();
+    let mut custom_id = 0;
+    while signal_configs.iter().count() < N_EMITTERS {
+        signal_configs.insert(Emitter::Custom(custom_id), custom_config);
+        custom_id += 1;
+    }
+    let emitters: Vec<Emitter> = signal_configs
+        .iter()
+        .map(|(emitter, _)| *emitter)
+        .take(N_EMITTERS)
+        .collect();
+
+    // Start every signal with some value at every tile, so that no work can be skipped
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut signal_field = SignalField::new(&map_positions);
+    let mut baseline: MapResource<baseline::TileSignals> =
+        MapResource::default_from_template(&map_positions);
+    for position in map_positions.iter_positions() {
+        for emitter in &emitters {
+            let value = rng.gen_range(0.0..1.0);
+            signal_field.set(*emitter, position, value);
+            baseline
+                .get_mut(position)
+                .unwrap()
+                .increment(emitter, value);
+        }
+    }
+
+    println!(
+        "{} emitters on a map of radius {MAP_RADIUS} ({} tiles)",
+        N_EMITTERS,
+        map_positions.n_positions()
+    );
+
+    let baseline_time = time_per_tick(5, || {
+        baseline::tick_signals(&mut baseline, &signal_configs);
+    });
+    report("hash map", baseline_time);
+
+    let field_time = time_per_tick(50, || tick_signals(&mut signal_field, &signal_configs));
+    report("dense", field_time);
+
+    println!(
+        "speedup: {:.1}x",
+        baseline_time.as_secs_f64() / field_time.as_secs_f64()
+    );
+}
@@ -1,6 +1,7 @@
 //! Curves that are commonly useful when defining interesting game mechanics.
 
 use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
 
 /// A type which maps from an input value to an output value that lies on a curve.
 pub trait Mapping {
@@ -170,7 +171,7 @@ pub fn inverse_normal_sigmoid(y: f32) -> f32 {
 ///
 /// Use [`new`](Sigmoid::new), an ergonomic interface to smooth the process of defining the
 /// necessary numbers governing the curve.
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Sigmoid {
     /// The distance between asymptotic maximum (output value produced at `+infinity`) and asymptotic
     /// minimum value (output value produced at `-infinity`).
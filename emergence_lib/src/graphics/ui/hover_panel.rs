@@ -2,12 +2,17 @@
 use bevy::prelude::*;
 
 use crate::{
-    cursor::CursorTilePos, organisms::organism_details::HoverDetails,
+    cursor::CursorTilePos,
+    organisms::organism_details::HoverDetails,
+    signals::{configs::SignalConfigs, field::SignalField},
     structures::crafting::CraftingState,
 };
 
 use super::{FiraSansFontFamily, RightPanel, UiStage};
 
+/// The number of signals listed for the hovered tile.
+const N_HOVERED_SIGNALS: usize = 3;
+
 /// The panel to display information on hover.
 #[derive(Debug, Component)]
 struct HoverPanel;
@@ -20,6 +25,10 @@ struct PositionText;
 #[derive(Debug, Component)]
 struct OrganismText;
 
+/// The text to display the strongest signals on the tile.
+#[derive(Debug, Component)]
+struct SignalText;
+
 /// The text for all details regarding crafting.
 #[derive(Debug, Component)]
 struct CraftingText;
@@ -68,6 +77,15 @@ fn setup_hover_panel(
                 PositionText,
             ));
 
+            // Strongest signals
+            parent.spawn((
+                TextBundle::from_sections([
+                    TextSection::new("Signals: ", key_text_style.clone()),
+                    TextSection::from_style(value_text_style.clone()),
+                ]),
+                SignalText,
+            ));
+
             // Organism type
             parent.spawn((
                 TextBundle {
@@ -109,14 +127,25 @@ fn setup_hover_panel(
 fn update_hover_panel(
     cursor_tile_pos: Res<CursorTilePos>,
     hover_details: Res<HoverDetails>,
+    signal_field: Res<SignalField>,
+    signal_configs: Res<SignalConfigs>,
     mut panel_query: Query<&mut Visibility, With<HoverPanel>>,
     mut position_query: Query<&mut Text, With<PositionText>>,
+    mut signal_query: Query<
+        &mut Text,
+        (
+            With<SignalText>,
+            // Avoid conflicting queries
+            Without<PositionText>,
+        ),
+    >,
     mut organism_query: Query<
         (&mut Text, &mut Visibility),
         (
             With<OrganismText>,
             // Avoid conflicting queries
             Without<PositionText>,
+            Without<SignalText>,
             Without<HoverPanel>,
         ),
     >,
@@ -126,6 +155,7 @@ fn update_hover_panel(
             // Avoid conflicting queries
             With<CraftingText>,
             Without<PositionText>,
+            Without<SignalText>,
             Without<HoverPanel>,
             Without<OrganismText>,
         ),
@@ -139,6 +169,18 @@ fn update_hover_panel(
         position_query.single_mut().sections[1].value =
             format!("{}, {}", cursor_tile_pos.x, cursor_tile_pos.y);
 
+        // Update signal text
+        let strongest_signals: Vec<String> = signal_field
+            .top_emitters(&cursor_tile_pos, N_HOVERED_SIGNALS)
+            .iter()
+            .map(|(emitter, value)| format!("{} ({value:.2})", signal_configs.label(emitter)))
+            .collect();
+        signal_query.single_mut().sections[1].value = if strongest_signals.is_empty() {
+            "None".to_string()
+        } else {
+            strongest_signals.join(", ")
+        };
+
         // Update organism text
         if let Some(organism_details) = &**hover_details {
             let (mut text, mut visibility) = organism_query.single_mut();
@@ -161,6 +203,7 @@ fn update_hover_panel(
                 };
                 text.sections[7].value = match crafting_details.state {
                     CraftingState::WaitingForInput => "Waiting for input".to_string(),
+                    CraftingState::WaitingForWork => "Waiting for work".to_string(),
                     CraftingState::InProgress => {
                         format!("Crafting ({:.2}s)", crafting_details.timer.remaining_secs())
                     }
@@ -3,9 +3,11 @@
 use bevy::prelude::*;
 
 use self::hover_panel::HoverPanelPlugin;
+use self::overlay_legend::OverlayLegendPlugin;
 
 mod hover_panel;
 mod intent;
+mod overlay_legend;
 
 /// The different stages of the UI setup.
 ///
@@ -55,7 +57,8 @@ impl Plugin for UiPlugin {
             SystemStage::parallel(),
         )
         .add_startup_system_to_stage(UiStage::LayoutInitialization, setup_ui)
-        .add_plugin(HoverPanelPlugin);
+        .add_plugin(HoverPanelPlugin)
+        .add_plugin(OverlayLegendPlugin);
     }
 }
 
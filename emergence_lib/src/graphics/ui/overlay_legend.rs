@@ -0,0 +1,112 @@
+//! Create and update a legend explaining the colours of the signal overlay.
+use bevy::prelude::*;
+
+use crate::signals::{configs::SignalConfigs, map_overlay::SignalOverlay};
+
+use super::{FiraSansFontFamily, LeftPanel, UiStage};
+
+/// The number of values shown in the legend of a heatmap.
+const N_HEATMAP_STEPS: usize = 5;
+
+/// The panel containing the legend.
+#[derive(Debug, Component)]
+struct OverlayLegendPanel;
+
+/// Create the legend panel in the UI.
+fn setup_overlay_legend(mut commands: Commands, query: Query<Entity, With<LeftPanel>>) {
+    let left_panel = query.single();
+
+    let legend_panel = commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Auto),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(10.)),
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.9).into(),
+                ..default()
+            },
+            OverlayLegendPanel,
+        ))
+        .id();
+
+    commands.entity(left_panel).add_child(legend_panel);
+}
+
+/// Rebuild the legend whenever the overlay or the signal colours change.
+fn update_overlay_legend(
+    mut commands: Commands,
+    font_family: Res<FiraSansFontFamily>,
+    signal_overlay: Res<SignalOverlay>,
+    signal_configs: Res<SignalConfigs>,
+    panel_query: Query<Entity, With<OverlayLegendPanel>>,
+) {
+    if !signal_overlay.is_changed() && !signal_configs.is_changed() {
+        return;
+    }
+
+    let title_text_style = TextStyle {
+        color: Color::WHITE,
+        font: font_family.bold.clone_weak(),
+        font_size: 20.,
+    };
+    let entry_text_style = TextStyle {
+        color: Color::rgb(0.9, 0.9, 0.9),
+        font: font_family.regular.clone_weak(),
+        font_size: 16.,
+    };
+
+    let legend = signal_overlay.legend(&signal_configs, N_HEATMAP_STEPS);
+    let legend_panel = panel_query.single();
+
+    commands.entity(legend_panel).despawn_descendants();
+    commands.entity(legend_panel).with_children(|parent| {
+        parent.spawn(TextBundle::from_sections([TextSection::new(
+            legend.title,
+            title_text_style,
+        )]));
+
+        for entry in legend.entries {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        margin: UiRect::all(Val::Px(2.)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|row| {
+                    // Colour swatch
+                    row.spawn(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(16.), Val::Px(16.)),
+                            margin: UiRect::all(Val::Px(4.)),
+                            ..default()
+                        },
+                        background_color: entry.color.into(),
+                        ..default()
+                    });
+
+                    row.spawn(TextBundle::from_sections([TextSection::new(
+                        entry.label,
+                        entry_text_style.clone(),
+                    )]));
+                });
+        }
+    });
+}
+
+/// Functionality for the signal overlay legend.
+#[derive(Debug)]
+pub struct OverlayLegendPlugin;
+
+impl Plugin for OverlayLegendPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system_to_stage(UiStage::LayoutPopulation, setup_overlay_legend)
+            .add_system(update_overlay_legend);
+    }
+}
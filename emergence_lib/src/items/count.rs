@@ -2,10 +2,12 @@
 
 use std::fmt::Display;
 
+use serde::{Deserialize, Serialize};
+
 use super::ItemId;
 
 /// A specific amount of a given item.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ItemCount {
     /// The unique identifier of the item being counted.
     item_id: ItemId,
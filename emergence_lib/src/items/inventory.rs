@@ -2,6 +2,8 @@
 
 use std::fmt::Display;
 
+use serde::{Deserialize, Serialize};
+
 use super::{
     count::ItemCount,
     errors::{AddManyItemsError, AddOneItemError, RemoveManyItemsError, RemoveOneItemError},
@@ -9,7 +11,7 @@ use super::{
     ItemId,
 };
 /// An inventory to store multiple types of items.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Inventory {
     /// The item slots that are currently active.
     ///
@@ -119,7 +121,7 @@ impl Inventory {
 
         // Fill up the remaining free slots
         while items_to_add > 0 && self.slots.len() < self.max_slot_count {
-            let mut new_slot = ItemSlot::new(item_count.item_id().clone(), self.max_items_per_slot);
+            let mut new_slot = ItemSlot::new(*item_count.item_id(), self.max_items_per_slot);
 
             match new_slot.add_until_full(items_to_add) {
                 Ok(_) => {
@@ -198,7 +200,7 @@ impl Inventory {
                 }
 
                 if excess > 0 {
-                    Some(ItemCount::new(item_count.item_id().clone(), excess))
+                    Some(ItemCount::new(*item_count.item_id(), excess))
                 } else {
                     None
                 }
@@ -303,7 +305,7 @@ impl Inventory {
                     .saturating_sub(self.item_count(item_count.item_id()));
 
                 if missing > 0 {
-                    Some(ItemCount::new(item_count.item_id().clone(), missing))
+                    Some(ItemCount::new(*item_count.item_id(), missing))
                 } else {
                     None
                 }
@@ -2,6 +2,8 @@
 
 use std::fmt::Display;
 
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
 pub mod count;
 pub mod errors;
 pub mod inventory;
@@ -9,7 +11,7 @@ pub mod recipe;
 pub mod slot;
 
 /// The unique identifier of an item.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ItemId(&'static str);
 
 impl ItemId {
@@ -18,6 +20,18 @@ impl ItemId {
         Self("acacia_leaf")
     }
 
+    /// Every item ID that exists in the game.
+    ///
+    /// Used to look items up by name.
+    pub fn all() -> Vec<ItemId> {
+        vec![ItemId::acacia_leaf()]
+    }
+
+    /// Look up the item ID with the given name, if it exists.
+    pub fn from_name(name: &str) -> Option<ItemId> {
+        ItemId::all().into_iter().find(|item_id| item_id.0 == name)
+    }
+
     /// An item ID solely used for testing.
     #[cfg(test)]
     pub fn test() -> Self {
@@ -25,6 +39,19 @@ impl ItemId {
     }
 }
 
+impl Serialize for ItemId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        ItemId::from_name(&name).ok_or_else(|| D::Error::custom(format!("unknown item `{name}`")))
+    }
+}
+
 impl Display for ItemId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
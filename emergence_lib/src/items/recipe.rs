@@ -2,10 +2,12 @@
 
 use std::{fmt::Display, time::Duration};
 
+use serde::{Deserialize, Serialize};
+
 use super::count::ItemCount;
 
 /// A recipe to turn a set of items into different items.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Recipe {
     /// The inputs needed to craft the recipe.
     inputs: Vec<ItemCount>,
@@ -15,6 +17,10 @@ pub struct Recipe {
 
     /// The time needed to craft the recipe.
     craft_time: Duration,
+
+    /// Does a unit have to work at the structure before crafting can start?
+    #[serde(default)]
+    needs_work: bool,
 }
 
 impl Recipe {
@@ -24,9 +30,16 @@ impl Recipe {
             inputs,
             outputs,
             craft_time,
+            needs_work: false,
         }
     }
 
+    /// Requires a unit to work at the structure, once the inputs are paid, before crafting can start.
+    pub fn with_work(mut self) -> Self {
+        self.needs_work = true;
+        self
+    }
+
     /// The inputs needed to craft the recipe.
     pub fn inputs(&self) -> &Vec<ItemCount> {
         &self.inputs
@@ -41,6 +54,11 @@ impl Recipe {
     pub fn craft_time(&self) -> &Duration {
         &self.craft_time
     }
+
+    /// Does a unit have to work at the structure before crafting can start?
+    pub fn needs_work(&self) -> bool {
+        self.needs_work
+    }
 }
 
 impl Display for Recipe {
@@ -74,6 +92,7 @@ mod tests {
             inputs: Vec::new(),
             outputs: vec![ItemCount::one(ItemId::acacia_leaf())],
             craft_time: Duration::from_secs(1),
+            needs_work: false,
         };
 
         assert_eq!(format!("{recipe}"), "[] -> [acacia_leaf (1)] | 1.00s")
@@ -2,13 +2,15 @@
 
 use std::fmt::Display;
 
+use serde::{Deserialize, Serialize};
+
 use super::{
     errors::{AddOneItemError, RemoveOneItemError},
     ItemId,
 };
 
 /// Multiple items of the same type.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ItemSlot {
     /// The unique identifier of the item that occupies the slot.
     item_id: ItemId,
@@ -17,6 +17,7 @@ pub mod hive_mind;
 pub mod interactable;
 pub mod items;
 pub mod organisms;
+pub mod save;
 pub mod signals;
 pub mod simulation;
 pub mod structures;
@@ -32,7 +33,8 @@ impl Plugin for InteractionPlugin {
             // We add this here instead of the organisms plugin for better testability
             // Otherwise the organism plugin would depend on mouse input
             .add_plugin(organisms::organism_details::DetailsPlugin)
-            .add_plugin(hive_mind::HiveMindPlugin);
+            .add_plugin(hive_mind::HiveMindPlugin)
+            .add_plugin(signals::overlay_controls::OverlayControlsPlugin);
 
         #[cfg(feature = "debug_tools")]
         app.add_plugin(debug_tools::DebugToolsPlugin);
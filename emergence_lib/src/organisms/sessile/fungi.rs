@@ -1,5 +1,9 @@
 //! Fungi are structures powered by decomposition.
-use crate::{self as emergence_lib, organisms::life_cycles::LifeCycle};
+use crate::{
+    self as emergence_lib,
+    organisms::life_cycles::LifeCycle,
+    signals::emitters::{EmissionRate, Emitter, StockEmitter},
+};
 use bevy::prelude::*;
 use bevy_ecs_tilemap::tiles::TilePos;
 use emergence_macros::IterableEnum;
@@ -20,6 +24,11 @@ pub struct Fungi;
 #[derive(Component, Clone, Default)]
 pub struct Leuco;
 
+impl Leuco {
+    /// The amount of [`StockEmitter::Fungus`] signal emitted per tick
+    pub const EMISSION_RATE: f32 = 0.01;
+}
+
 /// The data needed to spawn a [`Leuco`] [`Fungi`].
 #[derive(Bundle)]
 pub struct LeucoBundle {
@@ -28,6 +37,12 @@ pub struct LeucoBundle {
 
     /// Fungi are sessile
     sessile_bundle: SessileBundle<Leuco>,
+
+    /// Fungi advertise their location
+    emitter: Emitter,
+
+    /// Fungi emit constantly
+    emission_rate: EmissionRate,
 }
 
 impl LeucoBundle {
@@ -36,6 +51,8 @@ impl LeucoBundle {
         Self {
             plant: Fungi,
             sessile_bundle: SessileBundle::new(tile_pos),
+            emitter: Emitter::Stock(StockEmitter::Fungus),
+            emission_rate: EmissionRate::constant(Leuco::EMISSION_RATE),
         }
     }
 }
@@ -4,6 +4,7 @@ use crate::{
     self as emergence_lib,
     items::{count::ItemCount, ItemId},
     organisms::life_cycles::LifeCycle,
+    signals::emitters::{EmissionCondition, EmissionRate, Emitter, StockEmitter},
 };
 use bevy::prelude::*;
 use bevy_ecs_tilemap::tiles::TilePos;
@@ -28,6 +29,11 @@ pub struct Plant;
 #[derive(Component, Default, Clone)]
 pub struct Acacia;
 
+impl Acacia {
+    /// The amount of [`StockEmitter::Plant`] signal emitted per tick, while leaves are ready to be collected
+    pub const EMISSION_RATE: f32 = 0.01;
+}
+
 /// The data needed to make an [`Acacia`] [`Plant`].
 #[derive(Bundle)]
 pub struct AcaciaBundle {
@@ -35,6 +41,10 @@ pub struct AcaciaBundle {
     plant: Plant,
     /// Plants are sessile
     sessile_bundle: SessileBundle<Acacia>,
+    /// Acacias advertise their leaves
+    emitter: Emitter,
+    /// Acacias only emit while they have leaves to collect
+    emission_rate: EmissionRate,
 }
 
 impl Species for Acacia {
@@ -85,6 +95,11 @@ impl AcaciaBundle {
                     Duration::from_secs(10),
                 ),
             ),
+            emitter: Emitter::Stock(StockEmitter::Plant),
+            emission_rate: EmissionRate {
+                rate: Acacia::EMISSION_RATE,
+                condition: EmissionCondition::OutputContains(ItemId::acacia_leaf()),
+            },
         }
     }
 }
@@ -4,61 +4,125 @@ use crate::organisms::units::pathfinding::get_weighted_position;
 
 use crate::curves::BottomClampedLine;
 use crate::signals::emitters::{Emitter, StockEmitter};
-use crate::signals::tile_signals::TileSignals;
-use crate::simulation::map::resources::MapResource;
+use crate::signals::field::SignalField;
+use crate::simulation::map::hex_patch::HexPatch;
 use crate::simulation::map::MapPositions;
-use crate::simulation::pathfinding::PassabilityCache;
+use crate::simulation::occupancy::{MoveBlocked, OccupancyLimits, TileOccupancy};
+use crate::simulation::pathfinding::path::{Destination, Path};
+use crate::simulation::pathfinding::{MovementClass, PassabilityCache};
+use crate::simulation::rng::{RngStream, SimulationRng};
 use bevy::prelude::*;
 use bevy_ecs_tilemap::tiles::TilePos;
+use rand::Rng;
 
 use super::{PheromoneTransducer, Unit, UnitTimer};
 
 /// Pathfinding for ants.
-fn wander(
+fn wander<R: Rng + ?Sized>(
     position: &TilePos,
     map_positions: &MapPositions,
     passable_filters: &PassabilityCache,
-    map_signals: &MapResource<TileSignals>,
+    signal_field: &SignalField,
     pheromone_sensor: &PheromoneTransducer<BottomClampedLine>,
+    rng: &mut R,
 ) -> TilePos {
-    let signals_to_weight = |tile_signals: &TileSignals| {
-        pheromone_sensor.signal_to_weight(
-            tile_signals.get(&Emitter::Stock(StockEmitter::PheromoneAttract)),
-            tile_signals.get(&Emitter::Stock(StockEmitter::PheromoneRepulse)),
-        )
-    };
+    let signals_to_weight =
+        |(attract, repulse): &(f32, f32)| pheromone_sensor.signal_to_weight(*attract, *repulse);
 
     let position_patch = map_positions.get_patch(position).unwrap();
-    let filter_patch = passable_filters.get_patch(position).unwrap();
-    let valid_possibilities = position_patch.apply_filter(filter_patch, false).cloned();
-    let signals_patch = map_signals.get_patch(position).unwrap();
+    let filter_patch = passable_filters
+        .get_patch(MovementClass::Walking, position)
+        .unwrap();
+    let valid_possibilities = position_patch.apply_filter(&filter_patch, false).cloned();
+    let attract_patch = signal_field
+        .get_patch(&Emitter::Stock(StockEmitter::PheromoneAttract), position)
+        .unwrap();
+    let repulse_patch = signal_field
+        .get_patch(&Emitter::Stock(StockEmitter::PheromoneRepulse), position)
+        .unwrap();
+    let signals_patch = HexPatch::from_locational_closure(|location| {
+        Some((*attract_patch.get(location)?, *repulse_patch.get(location)?))
+    });
 
-    let target = get_weighted_position(&valid_possibilities, signals_patch, signals_to_weight);
+    let target =
+        get_weighted_position(&valid_possibilities, &signals_patch, signals_to_weight, rng);
 
     target.unwrap_or(*position)
 }
 
 /// System modelling ant behaviour.
+///
+/// Every unit first reserves the tile it wants to move onto, and then all units with a
+/// reservation move at once. Units that cannot find room on their target tile stay put,
+/// and a [`MoveBlocked`] event is sent.
 #[allow(clippy::too_many_arguments)]
 pub(super) fn act(
     time: Res<Time>,
     mut timer: ResMut<UnitTimer>,
-    mut unit_query: Query<(&Unit, &mut TilePos)>,
+    mut unit_query: Query<(Entity, &Unit, &mut TilePos, Option<&mut Path>)>,
     map_positions: Res<MapPositions>,
     passable_filters: Res<PassabilityCache>,
-    map_signals: Res<MapResource<TileSignals>>,
+    signal_field: Res<SignalField>,
     pheromone_sensor: Res<PheromoneTransducer<BottomClampedLine>>,
+    mut rng: ResMut<SimulationRng>,
+    mut occupancy: ResMut<TileOccupancy>,
+    occupancy_limits: Res<OccupancyLimits>,
+    mut move_blocked: EventWriter<MoveBlocked>,
+    mut commands: Commands,
 ) {
     timer.0.tick(time.delta());
     if timer.0.finished() {
-        for (_, mut position) in unit_query.iter_mut() {
-            *position = wander(
-                &position,
-                &map_positions,
-                &passable_filters,
-                &map_signals,
-                &pheromone_sensor,
-            );
+        let rng = rng.stream(RngStream::Units);
+
+        let mut reserved = Vec::new();
+        for (entity, _, position, path) in unit_query.iter() {
+            // Units with somewhere to be follow their path, rather than wandering
+            let target = match path {
+                Some(path) => match path.next_step() {
+                    Some(next_step) => *next_step,
+                    None => {
+                        commands.entity(entity).remove::<(Path, Destination)>();
+                        continue;
+                    }
+                },
+                None => wander(
+                    position,
+                    &map_positions,
+                    &passable_filters,
+                    &signal_field,
+                    &pheromone_sensor,
+                    rng,
+                ),
+            };
+
+            if target == *position {
+                continue;
+            }
+
+            if occupancy.reserve(entity, target, &occupancy_limits) {
+                reserved.push(entity);
+            } else {
+                move_blocked.send(MoveBlocked {
+                    unit: entity,
+                    from: *position,
+                    to: target,
+                });
+            }
+        }
+
+        for entity in reserved {
+            let (_, _, mut position, path) = unit_query.get_mut(entity).unwrap();
+            let target = occupancy.reservation(entity).unwrap();
+
+            if let Some(mut path) = path {
+                path.advance();
+                if path.is_empty() {
+                    commands.entity(entity).remove::<(Path, Destination)>();
+                }
+            }
+
+            *position = target;
+            occupancy.move_unit(entity, target);
         }
     }
 }
@@ -5,6 +5,8 @@ use crate::enum_iter::IterableEnum;
 use crate::graphics::organisms::OrganismSprite;
 use crate::graphics::sprites::IntoSprite;
 use crate::graphics::Tilemap;
+use crate::signals::emitters::{EmissionRate, Emitter, StockEmitter};
+use crate::signals::SignalSystem;
 use bevy::prelude::*;
 use bevy_ecs_tilemap::tiles::TilePos;
 
@@ -14,7 +16,7 @@ use self::behavior::events::{
 use self::behavior::CurrentGoal;
 
 mod act;
-mod behavior;
+pub(crate) mod behavior;
 mod pathfinding;
 
 /// Available types of units
@@ -40,6 +42,11 @@ pub struct UnitBundle {
 #[derive(Component, Clone, Default)]
 pub struct Ant;
 
+impl Ant {
+    /// The amount of [`StockEmitter::Ant`] signal emitted per tick, leaving a faint trail
+    pub const EMISSION_RATE: f32 = 0.001;
+}
+
 impl IntoSprite for Ant {
     fn tilemap(&self) -> Tilemap {
         Tilemap::Organisms
@@ -59,6 +66,10 @@ pub struct AntBundle {
     unit_bundle: UnitBundle,
     /// Position in the world
     position: TilePos,
+    /// Ants leave a trail of signal behind them
+    emitter: Emitter,
+    /// Ants emit constantly
+    emission_rate: EmissionRate,
 }
 
 impl AntBundle {
@@ -70,6 +81,8 @@ impl AntBundle {
                 ..Default::default()
             },
             position,
+            emitter: Emitter::Stock(StockEmitter::Ant),
+            emission_rate: EmissionRate::constant(Ant::EMISSION_RATE),
         }
     }
 }
@@ -105,7 +118,9 @@ impl Plugin for UnitsPlugin {
             .add_system(
                 act::act
                     .label(UnitSystem::Act)
-                    .after(UnitSystem::ChooseAction),
+                    .after(UnitSystem::ChooseAction)
+                    // Units follow the signals as they are after this frame's simulation
+                    .after(SignalSystem::Simulate),
             );
     }
 }
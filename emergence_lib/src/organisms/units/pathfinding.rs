@@ -1,11 +1,9 @@
 //! Utilities to support organism pathfinding.
-use crate::signals::tile_signals::TileSignals;
 use crate::simulation::map::hex_patch::HexPatch;
-use crate::simulation::map::resources::MapData;
 use bevy_ecs_tilemap::tiles::TilePos;
 use rand::distributions::WeightedError;
 use rand::seq::SliceRandom;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 /// A tile position with an associated weight. Useful for making weighted selections from a
 /// set of tile positions.
@@ -22,35 +20,35 @@ pub struct WeightedTilePos {
 /// Select an adjacent neighboring tile at random, based on the provided weight function.
 ///
 /// Returns [`None`] if and only if no such tile exists.
-pub fn get_weighted_position<SignalsToWeight>(
+pub fn get_weighted_position<Signals, SignalsToWeight, R>(
     valid_possibilities: &HexPatch<TilePos>,
-    signals_patch: &HexPatch<MapData<TileSignals>>,
+    signals_patch: &HexPatch<Signals>,
     signals_to_weight: SignalsToWeight,
+    rng: &mut R,
 ) -> Option<TilePos>
 where
-    SignalsToWeight: Fn(&TileSignals) -> f32,
+    SignalsToWeight: Fn(&Signals) -> f32,
+    R: Rng + ?Sized,
 {
-    let mut rng = thread_rng();
-
     HexPatch::weighted_neighbors(valid_possibilities, signals_patch, signals_to_weight)
-        .choose_random(&mut rng)
+        .choose_random(rng)
         .map(|weighted_position| weighted_position.position)
 }
 
 impl HexPatch<WeightedTilePos> {
     /// Returns the set of neighboring cells, weighted according to signal values.
-    pub fn weighted_neighbors<SignalsToWeight>(
+    pub fn weighted_neighbors<Signals, SignalsToWeight>(
         valid_possibilities: &HexPatch<TilePos>,
-        signals_patch: &HexPatch<MapData<TileSignals>>,
+        signals_patch: &HexPatch<Signals>,
         signals_to_weight: SignalsToWeight,
     ) -> HexPatch<WeightedTilePos>
     where
-        SignalsToWeight: Fn(&TileSignals) -> f32,
+        SignalsToWeight: Fn(&Signals) -> f32,
     {
         let f = |location| {
             let position = *valid_possibilities.get(location)?;
             let signals = signals_patch.get(location)?;
-            let weight = signals_to_weight(&signals.read());
+            let weight = signals_to_weight(signals);
             Some(WeightedTilePos { position, weight })
         };
 
@@ -0,0 +1,180 @@
+//! The on-disk layout of save files.
+//!
+//! These types mirror the state of the ECS world, but only contain plain data,
+//! so that they can be serialized independently of the entities they describe.
+
+use crate::items::inventory::Inventory;
+use crate::items::recipe::Recipe;
+use crate::save::SaveError;
+use crate::signals::configs::SignalConfig;
+use crate::signals::emitters::Emitter;
+use crate::simulation::map::MapShape;
+use crate::simulation::rng::RngStream;
+use crate::structures::crafting::CraftingState;
+use crate::terrain::TerrainType;
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The version of the save format written by this build of the game.
+///
+/// This must be incremented whenever the layout of [`SaveFile`] changes,
+/// and a migration from the previous version must be added to [`SaveFile::from_ron_str`].
+pub const SAVE_FORMAT_VERSION: u32 = 1;
+
+/// The complete state of the game world.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveFile {
+    /// The version of the save format this file was written with.
+    pub version: u32,
+    /// The seed that the world was generated from.
+    pub seed: u64,
+    /// The size and shape of the map.
+    pub map: SavedMap,
+    /// The terrain, wind and signals at each tile of the map.
+    pub tiles: Vec<SavedTile>,
+    /// Every organism in the world.
+    pub organisms: Vec<SavedOrganism>,
+    /// The configuration of each signal, in the order they were registered.
+    pub signal_configs: Vec<(Emitter, SignalConfig)>,
+    /// The names of each registered custom emitter, in the order they were registered.
+    pub custom_emitters: Vec<SavedCustomEmitter>,
+    /// How far each random number stream has advanced.
+    pub rng_streams: Vec<SavedRngStream>,
+}
+
+impl SaveFile {
+    /// Serializes this save file into a human-readable RON string.
+    pub fn to_ron_string(&self) -> Result<String, SaveError> {
+        let pretty = ron::ser::PrettyConfig::default();
+        Ok(ron::ser::to_string_pretty(self, pretty)?)
+    }
+
+    /// Deserializes a save file from a RON string, migrating it to the current version if needed.
+    pub fn from_ron_str(contents: &str) -> Result<SaveFile, SaveError> {
+        let header: SaveHeader = ron::from_str(contents)?;
+
+        match header.version {
+            SAVE_FORMAT_VERSION => Ok(ron::from_str(contents)?),
+            // Migrations from older versions go here, upgrading one version at a time
+            found => Err(SaveError::UnsupportedVersion { found }),
+        }
+    }
+}
+
+/// The part of a [`SaveFile`] that is shared between all versions of the format.
+///
+/// Used to work out how the remainder of the file should be read.
+#[derive(Debug, Deserialize)]
+struct SaveHeader {
+    /// The version of the save format the file was written with.
+    version: u32,
+}
+
+/// The size and shape of the map.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMap {
+    /// The shape of the map.
+    pub shape: MapShape,
+}
+
+/// A position on the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedPosition {
+    /// The `x` coordinate of the [`TilePos`].
+    pub x: u32,
+    /// The `y` coordinate of the [`TilePos`].
+    pub y: u32,
+}
+
+impl From<TilePos> for SavedPosition {
+    fn from(tile_pos: TilePos) -> Self {
+        SavedPosition {
+            x: tile_pos.x,
+            y: tile_pos.y,
+        }
+    }
+}
+
+impl From<SavedPosition> for TilePos {
+    fn from(position: SavedPosition) -> Self {
+        TilePos {
+            x: position.x,
+            y: position.y,
+        }
+    }
+}
+
+/// The names of a registered [custom emitter](Emitter::Custom).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedCustomEmitter {
+    /// The id of the emitter.
+    pub id: u16,
+    /// The unique name the emitter was registered with.
+    pub name: String,
+    /// The name shown to players.
+    pub display_name: String,
+}
+
+/// The position of a single random number stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedRngStream {
+    /// The stream this describes.
+    pub stream: RngStream,
+    /// The [position](crate::simulation::rng::SimulationRng::position) of the stream.
+    pub position: u64,
+}
+
+/// The contents of a single tile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedTile {
+    /// The position of this tile.
+    pub position: SavedPosition,
+    /// The type of terrain at this tile.
+    pub terrain: TerrainType,
+    /// The [wind](crate::signals::field::SignalField::wind) at this tile.
+    pub wind: (f32, f32),
+    /// The current value of each signal present at this tile.
+    pub signals: Vec<(Emitter, f32)>,
+}
+
+/// The species of a saved organism.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SavedSpecies {
+    /// An [`Ant`](crate::organisms::units::Ant).
+    Ant,
+    /// An [`Acacia`](crate::organisms::sessile::plants::Acacia).
+    Acacia,
+    /// A [`Leuco`](crate::organisms::sessile::fungi::Leuco).
+    Leuco,
+}
+
+/// A single organism.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedOrganism {
+    /// The position of this organism.
+    pub position: SavedPosition,
+    /// The species of this organism.
+    pub species: SavedSpecies,
+    /// The index of the current [`LifeStage`](crate::organisms::Species::LifeStage), if the species has life stages.
+    pub life_stage: Option<usize>,
+    /// The state of this organism's crafting, if it can craft.
+    pub crafting: Option<SavedCrafting>,
+}
+
+/// The crafting state of a structure.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedCrafting {
+    /// The contents of the [`InputInventory`](crate::structures::crafting::InputInventory).
+    pub input_inventory: Inventory,
+    /// The contents of the [`OutputInventory`](crate::structures::crafting::OutputInventory).
+    pub output_inventory: Inventory,
+    /// The [`ActiveRecipe`](crate::structures::crafting::ActiveRecipe), if any.
+    pub active_recipe: Option<Recipe>,
+    /// The [`CraftingState`].
+    pub state: CraftingState,
+    /// The total duration of the [`CraftTimer`](crate::structures::crafting::CraftTimer).
+    pub craft_time: Duration,
+    /// The time that has already elapsed on the [`CraftTimer`](crate::structures::crafting::CraftTimer).
+    pub elapsed: Duration,
+}
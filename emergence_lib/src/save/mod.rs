@@ -0,0 +1,526 @@
+//! Saving and loading the complete state of the game world.
+//!
+//! Save files are written as [RON](https://github.com/ron-rs/ron), using the layout defined in
+//! [`format`]. Each file carries a version number, so that old saves can be migrated.
+
+pub mod format;
+
+use crate::enum_iter::IterableEnum;
+use crate::organisms::sessile::fungi::{Leuco, LeucoBundle, LeucoLifeStage};
+use crate::organisms::sessile::plants::{Acacia, AcaciaBundle, AcaciaLifeStage};
+use crate::organisms::units::{Ant, AntBundle};
+use crate::organisms::Species;
+use crate::save::format::{
+    SaveFile, SavedCrafting, SavedCustomEmitter, SavedMap, SavedOrganism, SavedRngStream,
+    SavedSpecies, SavedTile, SAVE_FORMAT_VERSION,
+};
+use crate::signals::configs::SignalConfigs;
+use crate::signals::emitters::Emitter;
+use crate::signals::field::SignalField;
+use crate::signals::registry::{CustomEmitter, RegistrationError};
+use crate::simulation::map::resources::MapResource;
+use crate::simulation::map::{MapGeometry, MapPositions};
+use crate::simulation::occupancy::TileOccupancy;
+use crate::simulation::pathfinding::costs::{MovementCostCache, TerrainCosts};
+use crate::simulation::pathfinding::path::PathCache;
+use crate::simulation::pathfinding::{Impassable, PassabilityCache};
+use crate::simulation::rng::{RngStream, SimulationRng};
+use crate::structures::crafting::{
+    ActiveRecipe, CraftTimer, CraftingState, InputInventory, OutputInventory,
+};
+use crate::structures::logistics::NeedsWork;
+use crate::terrain::entity_map::TerrainEntityMap;
+use crate::terrain::TerrainType;
+use bevy::ecs::system::{CommandQueue, EntityCommands};
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Handles [`SaveGame`] and [`LoadGame`] requests.
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveGame>()
+            .add_event::<LoadGame>()
+            .add_system_to_stage(CoreStage::Last, handle_save_and_load_requests);
+    }
+}
+
+/// Request that the world be saved to the file at `path`.
+pub struct SaveGame {
+    /// The file to write to.
+    pub path: PathBuf,
+}
+
+/// Request that the world be replaced by the one saved in the file at `path`.
+pub struct LoadGame {
+    /// The file to read from.
+    pub path: PathBuf,
+}
+
+/// Failed to save or load the game.
+#[derive(Debug)]
+pub enum SaveError {
+    /// The save file could not be read or written.
+    Io(std::io::Error),
+    /// The world could not be serialized.
+    Serialization(ron::Error),
+    /// The save file could not be parsed.
+    Deserialization(ron::error::SpannedError),
+    /// The save file was written by a version of the format that cannot be migrated.
+    UnsupportedVersion {
+        /// The version of the save file.
+        found: u32,
+    },
+    /// A resource required to save the world does not exist yet.
+    MissingResource(&'static str),
+    /// The save file places something outside of the map.
+    OutOfBounds(TilePos),
+    /// The save file does not describe the terrain at one of the positions on the map.
+    MissingTile(TilePos),
+    /// The save file contains a life stage that does not exist for the organism's species.
+    InvalidLifeStage {
+        /// The species of the organism.
+        species: SavedSpecies,
+        /// The index of the life stage.
+        index: usize,
+    },
+    /// The save file contains custom emitters that cannot be registered.
+    CustomEmitter(RegistrationError),
+    /// The save file names a custom emitter, but does not say how its signal is configured.
+    MissingSignalConfig(Emitter),
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "could not access save file: {error}"),
+            SaveError::Serialization(error) => write!(f, "could not serialize world: {error}"),
+            SaveError::Deserialization(error) => write!(f, "could not parse save file: {error}"),
+            SaveError::UnsupportedVersion { found } => write!(
+                f,
+                "save format version {found} is not supported (current version is {SAVE_FORMAT_VERSION})"
+            ),
+            SaveError::MissingResource(name) => write!(f, "missing resource {name}"),
+            SaveError::OutOfBounds(position) => write!(
+                f,
+                "tile position ({}, {}) lies outside of the map",
+                position.x, position.y
+            ),
+            SaveError::MissingTile(position) => write!(
+                f,
+                "no terrain was saved for tile position ({}, {})",
+                position.x, position.y
+            ),
+            SaveError::InvalidLifeStage { species, index } => {
+                write!(f, "{species:?} has no life stage with index {index}")
+            }
+            SaveError::CustomEmitter(error) => write!(f, "invalid custom emitter: {error}"),
+            SaveError::MissingSignalConfig(emitter) => {
+                write!(f, "no signal configuration was saved for {emitter}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(error: std::io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl From<RegistrationError> for SaveError {
+    fn from(error: RegistrationError) -> Self {
+        SaveError::CustomEmitter(error)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(error: ron::Error) -> Self {
+        SaveError::Serialization(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for SaveError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        SaveError::Deserialization(error)
+    }
+}
+
+/// Saves and loads the world when requested.
+///
+/// This is an exclusive system, as loading replaces the contents of the world wholesale.
+fn handle_save_and_load_requests(world: &mut World) {
+    let save_paths: Vec<PathBuf> = world
+        .resource_mut::<Events<SaveGame>>()
+        .drain()
+        .map(|request| request.path)
+        .collect();
+    for path in save_paths {
+        match save_to_file(world, &path) {
+            Ok(()) => info!("Saved game to {path:?}"),
+            Err(error) => error!("Failed to save game to {path:?}: {error}"),
+        }
+    }
+
+    let load_paths: Vec<PathBuf> = world
+        .resource_mut::<Events<LoadGame>>()
+        .drain()
+        .map(|request| request.path)
+        .collect();
+    for path in load_paths {
+        match load_from_file(world, &path) {
+            Ok(()) => info!("Loaded game from {path:?}"),
+            Err(error) => error!("Failed to load game from {path:?}: {error}"),
+        }
+    }
+}
+
+/// Writes the current state of the world to the file at `path`.
+pub fn save_to_file(world: &mut World, path: &Path) -> Result<(), SaveError> {
+    let contents = save_world(world)?.to_ron_string()?;
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Replaces the current state of the world with the one stored in the file at `path`.
+pub fn load_from_file(world: &mut World, path: &Path) -> Result<(), SaveError> {
+    let contents = std::fs::read_to_string(path)?;
+    load_world(world, SaveFile::from_ron_str(&contents)?)
+}
+
+/// Captures the current state of the world in a [`SaveFile`].
+pub fn save_world(world: &mut World) -> Result<SaveFile, SaveError> {
+    let rng = world
+        .get_resource::<SimulationRng>()
+        .ok_or(SaveError::MissingResource("SimulationRng"))?;
+    let seed = rng.seed();
+    let rng_streams = RngStream::variants()
+        .map(|stream| SavedRngStream {
+            stream,
+            position: rng.position(stream),
+        })
+        .collect();
+    let map = SavedMap {
+        shape: world
+            .get_resource::<MapGeometry>()
+            .ok_or(SaveError::MissingResource("MapGeometry"))?
+            .shape()
+            .clone(),
+    };
+
+    let mut terrain_query = world.query::<(&TilePos, &TerrainType)>();
+    let mut terrain: Vec<(TilePos, TerrainType)> = terrain_query
+        .iter(world)
+        .map(|(position, terrain_type)| (*position, *terrain_type))
+        .collect();
+    terrain.sort_by_key(|(position, _)| (position.y, position.x));
+
+    let signal_field = world
+        .get_resource::<SignalField>()
+        .ok_or(SaveError::MissingResource("SignalField"))?;
+    let tiles = terrain
+        .into_iter()
+        .map(|(position, terrain)| SavedTile {
+            position: position.into(),
+            terrain,
+            wind: {
+                let wind = signal_field.wind(&position).unwrap_or_default();
+                (wind.x, wind.y)
+            },
+            signals: {
+                // Emitters are stored in the order they were added, so sort them to keep save files stable
+                let mut signals = signal_field.tile_values(&position);
+                signals.sort_by_key(|(emitter, _)| *emitter);
+                signals
+            },
+        })
+        .collect();
+
+    let signal_configs = world
+        .get_resource::<SignalConfigs>()
+        .ok_or(SaveError::MissingResource("SignalConfigs"))?;
+    let custom_emitters = signal_configs
+        .custom_emitters()
+        .iter()
+        .filter_map(|(emitter, names)| match emitter {
+            Emitter::Custom(id) => Some(SavedCustomEmitter {
+                id,
+                name: names.name.clone(),
+                display_name: names.display_name.clone(),
+            }),
+            _ => None,
+        })
+        .collect();
+    let signal_configs = signal_configs
+        .iter()
+        .map(|(emitter, config)| (*emitter, *config))
+        .collect();
+
+    let mut organism_query = world.query::<(
+        &TilePos,
+        Option<&Ant>,
+        Option<&AcaciaLifeStage>,
+        Option<&LeucoLifeStage>,
+        Option<(
+            &InputInventory,
+            &OutputInventory,
+            &ActiveRecipe,
+            &CraftingState,
+            &CraftTimer,
+        )>,
+    )>();
+    let mut organisms: Vec<SavedOrganism> = organism_query
+        .iter(world)
+        .filter_map(|(position, ant, acacia, leuco, crafting)| {
+            let (species, life_stage) = if ant.is_some() {
+                (SavedSpecies::Ant, None)
+            } else if let Some(life_stage) = acacia {
+                (SavedSpecies::Acacia, Some(life_stage.index()))
+            } else if let Some(life_stage) = leuco {
+                (SavedSpecies::Leuco, Some(life_stage.index()))
+            } else {
+                return None;
+            };
+
+            let crafting = crafting.map(|(input, output, recipe, state, timer)| SavedCrafting {
+                input_inventory: input.inventory().clone(),
+                output_inventory: output.inventory().clone(),
+                active_recipe: recipe.maybe_recipe().clone(),
+                state: state.clone(),
+                craft_time: timer.timer().duration(),
+                elapsed: timer.timer().elapsed(),
+            });
+
+            Some(SavedOrganism {
+                position: (*position).into(),
+                species,
+                life_stage,
+                crafting,
+            })
+        })
+        .collect();
+    organisms.sort_by_key(|organism| (organism.position.y, organism.position.x));
+
+    Ok(SaveFile {
+        version: SAVE_FORMAT_VERSION,
+        seed,
+        map,
+        tiles,
+        organisms,
+        signal_configs,
+        custom_emitters,
+        rng_streams,
+    })
+}
+
+/// Replaces the current state of the world with the one described by `save_file`.
+///
+/// All entities with a [`TilePos`] are despawned, and every map-derived resource
+/// (such as [`MapPositions`] and [`PassabilityCache`]) is rebuilt from scratch.
+///
+/// The save file is validated before the world is modified, so the world is left untouched if an error is returned.
+pub fn load_world(world: &mut World, save_file: SaveFile) -> Result<(), SaveError> {
+    let map_geometry = MapGeometry::new(save_file.map.shape.clone());
+    let map_positions = MapPositions::new(&map_geometry);
+    validate(&save_file, &map_positions)?;
+    let signal_configs = load_signal_configs(&save_file)?;
+
+    let mut stale_entities = world.query_filtered::<Entity, With<TilePos>>();
+    let stale_entities: Vec<Entity> = stale_entities.iter(world).collect();
+    for entity in stale_entities {
+        world.despawn(entity);
+    }
+
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+
+    let terrain_entities = save_file.tiles.iter().map(|tile| {
+        let position = TilePos::from(tile.position);
+        (position, tile.terrain.instantiate(&mut commands, &position))
+    });
+    let terrain_entity_map = TerrainEntityMap {
+        inner: MapResource::new(&map_positions, terrain_entities),
+    };
+
+    for organism in &save_file.organisms {
+        spawn_organism(&mut commands, organism);
+    }
+    queue.apply(world);
+
+    let mut impassable_query = world.query::<(Entity, &TilePos, &Impassable)>();
+    let mut passability_cache = PassabilityCache::new(&map_positions);
+    passability_cache.rebuild(
+        impassable_query
+            .iter(world)
+            .map(|(entity, position, impassable)| (entity, *position, *impassable)),
+    );
+
+    let terrain_costs = world
+        .get_resource::<TerrainCosts>()
+        .cloned()
+        .unwrap_or_default();
+    let mut movement_costs = MovementCostCache::new(&map_positions, &terrain_costs);
+    movement_costs.update(
+        save_file
+            .tiles
+            .iter()
+            .map(|tile| (tile.position.into(), tile.terrain)),
+        &terrain_costs,
+    );
+
+    let mut signal_field = SignalField::new(&map_positions);
+    for tile in &save_file.tiles {
+        let (x, y) = tile.wind;
+        signal_field.set_wind(&tile.position.into(), Vec2::new(x, y));
+        for (emitter, value) in &tile.signals {
+            signal_field.set(*emitter, &tile.position.into(), *value);
+        }
+    }
+
+    let mut rng = SimulationRng::new(save_file.seed);
+    for saved in &save_file.rng_streams {
+        rng.set_position(saved.stream, saved.position);
+    }
+
+    world.insert_resource(rng);
+    world.insert_resource(TileOccupancy::new(&map_positions));
+    world.insert_resource(map_geometry);
+    world.insert_resource(map_positions);
+    world.insert_resource(terrain_entity_map);
+    world.insert_resource(passability_cache);
+    world.insert_resource(movement_costs);
+    world.insert_resource(PathCache::default());
+    world.insert_resource(signal_field);
+    world.insert_resource(signal_configs);
+
+    Ok(())
+}
+
+/// Rebuilds the [`SignalConfigs`] stored in `save_file`, registering its custom emitters with the
+/// ids they were saved with.
+fn load_signal_configs(save_file: &SaveFile) -> Result<SignalConfigs, SaveError> {
+    let mut signal_configs = SignalConfigs::default();
+    // Configs are restored in the order they were saved, so that saving again is lossless
+    for (emitter, config) in &save_file.signal_configs {
+        let custom_emitter = save_file
+            .custom_emitters
+            .iter()
+            .find(|custom_emitter| Emitter::Custom(custom_emitter.id) == *emitter);
+        match custom_emitter {
+            Some(custom_emitter) => {
+                signal_configs.register_custom_emitter_with_id(
+                    custom_emitter.id,
+                    CustomEmitter {
+                        name: custom_emitter.name.clone(),
+                        display_name: custom_emitter.display_name.clone(),
+                        config: *config,
+                    },
+                )?;
+            }
+            None => {
+                signal_configs.insert(*emitter, *config);
+            }
+        }
+    }
+
+    for custom_emitter in &save_file.custom_emitters {
+        let emitter = Emitter::Custom(custom_emitter.id);
+        if signal_configs.get(&emitter).is_none() {
+            return Err(SaveError::MissingSignalConfig(emitter));
+        }
+    }
+    Ok(signal_configs)
+}
+
+/// Checks that `save_file` describes every position of the given map, and nothing outside of it.
+fn validate(save_file: &SaveFile, map_positions: &MapPositions) -> Result<(), SaveError> {
+    let positions = save_file
+        .tiles
+        .iter()
+        .map(|tile| tile.position)
+        .chain(save_file.organisms.iter().map(|organism| organism.position));
+    for position in positions {
+        let tile_pos = TilePos::from(position);
+        if map_positions.index().get(&tile_pos).is_none() {
+            return Err(SaveError::OutOfBounds(tile_pos));
+        }
+    }
+
+    let saved_tiles: HashSet<TilePos> = save_file
+        .tiles
+        .iter()
+        .map(|tile| tile.position.into())
+        .collect();
+    if let Some(missing) = map_positions
+        .iter_positions()
+        .find(|position| !saved_tiles.contains(position))
+    {
+        return Err(SaveError::MissingTile(*missing));
+    }
+
+    for organism in &save_file.organisms {
+        let valid = match (organism.species, organism.life_stage) {
+            (_, None) => true,
+            (SavedSpecies::Ant, Some(_)) => false,
+            (SavedSpecies::Acacia, Some(index)) => AcaciaLifeStage::get_at(index).is_some(),
+            (SavedSpecies::Leuco, Some(index)) => LeucoLifeStage::get_at(index).is_some(),
+        };
+        if !valid {
+            return Err(SaveError::InvalidLifeStage {
+                species: organism.species,
+                index: organism.life_stage.unwrap_or_default(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Spawns the organism described by `organism`.
+///
+/// The organism must already have been checked by [`validate`].
+fn spawn_organism(commands: &mut Commands, organism: &SavedOrganism) {
+    let position = TilePos::from(organism.position);
+    let mut entity_commands = match organism.species {
+        SavedSpecies::Ant => commands.spawn(AntBundle::new(position)),
+        SavedSpecies::Acacia => {
+            let mut entity_commands = commands.spawn(AcaciaBundle::new(position));
+            insert_life_stage::<Acacia>(&mut entity_commands, organism.life_stage);
+            entity_commands
+        }
+        SavedSpecies::Leuco => {
+            let mut entity_commands = commands.spawn(LeucoBundle::new(position));
+            insert_life_stage::<Leuco>(&mut entity_commands, organism.life_stage);
+            entity_commands
+        }
+    };
+
+    if let Some(crafting) = &organism.crafting {
+        let mut timer = Timer::new(crafting.craft_time, TimerMode::Once);
+        timer.set_elapsed(crafting.elapsed);
+
+        entity_commands.insert((
+            InputInventory(crafting.input_inventory.clone()),
+            OutputInventory(crafting.output_inventory.clone()),
+            ActiveRecipe(crafting.active_recipe.clone()),
+            crafting.state.clone(),
+            CraftTimer(timer),
+        ));
+        if crafting.state == CraftingState::WaitingForWork {
+            entity_commands.insert(NeedsWork);
+        }
+    }
+}
+
+/// Overwrites the life stage of a freshly spawned organism of species `S`.
+fn insert_life_stage<S: Species>(entity_commands: &mut EntityCommands, index: Option<usize>) {
+    if let Some(life_stage) = index.and_then(S::LifeStage::get_at) {
+        entity_commands.insert(life_stage);
+    }
+}
@@ -0,0 +1,625 @@
+//! Loading [`SignalConfig`]s from asset files, so they can be tuned without recompiling.
+//!
+//! Signal configuration files are written in RON, and use the `.signals.ron` extension:
+//!
+//! ```ron
+//! (
+//!     stock: {
+//!         Ant: (
+//!             diffusion_factor: 1e-4,
+//!             decay_probability: 1e-4,
+//!             color: (0.3, 0.3, 0.9),
+//!             sigmoid: (min: 0.0, max: 1.0, first_percentile: 0.01, last_percentile: 0.1),
+//!             terrain: {
+//!                 Rocky: (diffusion: 0.1),
+//!                 High: (diffusion: 0.5, decay: 1e-3),
+//!             },
+//!         ),
+//!     },
+//!     custom: [
+//!         (
+//!             name: "trail",
+//!             display_name: Some("Foraging trail"),
+//!             config: (
+//!                 diffusion_factor: 1e-3,
+//!                 decay_probability: 1e-2,
+//!                 color: (0.9, 0.6, 0.1),
+//!                 sigmoid: (min: 0.0, max: 1.0, first_percentile: 0.01, last_percentile: 0.2),
+//!                 is_visible: false,
+//!                 advection: 1.0,
+//!                 saturation: Logistic(capacity: 5.0),
+//!             ),
+//!         ),
+//!     ],
+//! )
+//! ```
+//!
+//! Stock emitters that are not listed keep their current configuration.
+//! Terrain types that are not listed use the [default](TerrainSignalConfig::default) coefficients.
+//! Signals are not carried by the wind unless they set an
+//! [`advection`](SignalConfig::advection) coefficient, and do not
+//! [saturate](SignalConfig::saturation) unless they set a limit.
+//! Custom emitters are declared by name, and are [registered](SignalConfigs::register_custom_emitter)
+//! the first time the file is applied. The `display_name` is shown to players, and defaults to
+//! the `name`.
+//!
+//! When the game is run with asset watching enabled, edits to the file are applied as soon as it is saved.
+
+use crate::curves::Sigmoid;
+use crate::enum_iter::IterableEnum;
+use crate::signals::configs::{
+    Saturation, SignalColorConfig, SignalConfig, SignalConfigs, TerrainCoefficients,
+    TerrainSignalConfig,
+};
+use crate::signals::emitters::{Emitter, StockEmitter};
+use crate::signals::registry::CustomEmitter;
+use crate::terrain::TerrainType;
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::{HashMap, HashSet};
+use serde::Deserialize;
+use std::fmt::Display;
+
+/// Loads the signal configuration file at `path`, and applies it to [`SignalConfigs`]
+/// whenever it is loaded or modified.
+///
+/// Requires the [`AssetPlugin`](bevy::asset::AssetPlugin).
+pub struct SignalConfigFilePlugin {
+    /// The path to the configuration file, relative to the assets folder.
+    pub path: String,
+}
+
+impl Plugin for SignalConfigFilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<SignalConfigAsset>()
+            .init_asset_loader::<SignalConfigLoader>();
+
+        let asset_server = app.world.resource::<AssetServer>();
+        let handle = asset_server.load(self.path.as_str());
+        app.insert_resource(SignalConfigHandle(handle))
+            .add_system(apply_signal_config_file);
+    }
+}
+
+/// The handle to the signal configuration file that is applied to [`SignalConfigs`]
+#[derive(Resource, Debug, Clone)]
+pub struct SignalConfigHandle(pub Handle<SignalConfigAsset>);
+
+/// A validated signal configuration file
+#[derive(TypeUuid, Debug, Clone, PartialEq)]
+#[uuid = "3214127f-ab87-4814-b9f8-18cbfac4038f"]
+pub struct SignalConfigAsset {
+    /// The configuration of each stock emitter in the file
+    configs: Vec<(Emitter, SignalConfig)>,
+    /// The custom emitters declared in the file, in the order they were declared
+    custom_emitters: Vec<CustomEmitter>,
+}
+
+impl SignalConfigAsset {
+    /// Parses and validates the contents of a signal configuration file.
+    pub fn from_ron_str(contents: &str) -> Result<SignalConfigAsset, SignalConfigError> {
+        let file: SignalConfigFile = ron::from_str(contents).map_err(SignalConfigError::Parse)?;
+
+        let mut configs = Vec::new();
+        // Iterate over the variants, rather than the map, so that the order is always the same
+        for stock_emitter in StockEmitter::variants() {
+            if let Some(entry) = file.stock.get(&stock_emitter) {
+                let config = entry.validate(&format!("{stock_emitter:?}"))?;
+                configs.push((Emitter::Stock(stock_emitter), config));
+            }
+        }
+
+        if file.custom.len() > u16::MAX as usize + 1 {
+            return Err(SignalConfigError::TooManyCustomEmitters);
+        }
+        let mut custom_emitters = Vec::new();
+        let mut seen_names = HashSet::new();
+        for custom in file.custom.iter() {
+            if !seen_names.insert(custom.name.as_str()) {
+                return Err(SignalConfigError::DuplicateName(custom.name.clone()));
+            }
+
+            custom_emitters.push(CustomEmitter {
+                name: custom.name.clone(),
+                display_name: custom
+                    .display_name
+                    .clone()
+                    .unwrap_or_else(|| custom.name.clone()),
+                config: custom.config.validate(&custom.name)?,
+            });
+        }
+
+        Ok(SignalConfigAsset {
+            configs,
+            custom_emitters,
+        })
+    }
+
+    /// The configuration of each stock emitter in the file.
+    pub fn configs(&self) -> impl Iterator<Item = (&Emitter, &SignalConfig)> {
+        self.configs
+            .iter()
+            .map(|(emitter, config)| (emitter, config))
+    }
+
+    /// The custom emitters declared in the file, in the order they were declared.
+    pub fn custom_emitters(&self) -> impl Iterator<Item = &CustomEmitter> {
+        self.custom_emitters.iter()
+    }
+}
+
+/// Loads [`SignalConfigAsset`]s from `.signals.ron` files
+#[derive(Default)]
+pub struct SignalConfigLoader;
+
+impl AssetLoader for SignalConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let contents = std::str::from_utf8(bytes)?;
+            let asset = SignalConfigAsset::from_ron_str(contents)?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["signals.ron"]
+    }
+}
+
+/// Applies the signal configuration file to [`SignalConfigs`] whenever it is loaded or modified.
+fn apply_signal_config_file(
+    mut asset_events: EventReader<AssetEvent<SignalConfigAsset>>,
+    handle: Res<SignalConfigHandle>,
+    assets: Res<Assets<SignalConfigAsset>>,
+    mut signal_configs: ResMut<SignalConfigs>,
+) {
+    for event in asset_events.iter() {
+        match event {
+            AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed }
+                if *changed == handle.0 =>
+            {
+                if let Some(asset) = assets.get(changed) {
+                    info!("Applying signal configuration file...");
+                    if let Err(error) = signal_configs.apply_file(asset) {
+                        error!("Failed to apply signal configuration file: {error}");
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// A signal configuration file, as written
+#[derive(Deserialize)]
+struct SignalConfigFile {
+    /// The configuration of stock emitters
+    #[serde(default)]
+    stock: HashMap<StockEmitter, SignalConfigEntry>,
+    /// The configuration of custom emitters
+    #[serde(default)]
+    custom: Vec<CustomSignalEntry>,
+}
+
+/// A custom emitter, declared by name
+#[derive(Deserialize)]
+struct CustomSignalEntry {
+    /// The unique name of the emitter
+    name: String,
+    /// The name shown to players, if it differs from `name`
+    #[serde(default)]
+    display_name: Option<String>,
+    /// The emitter's configuration
+    config: SignalConfigEntry,
+}
+
+/// The written form of a [`SignalConfig`]
+#[derive(Deserialize)]
+struct SignalConfigEntry {
+    /// See [`SignalConfig::diffusion_factor`]
+    diffusion_factor: f32,
+    /// See [`SignalConfig::decay_probability`]
+    decay_probability: f32,
+    /// See [`SignalColorConfig::rgb_color`]
+    color: [f32; 3],
+    /// See [`SignalColorConfig::sigmoid`]
+    sigmoid: SigmoidEntry,
+    /// See [`SignalColorConfig::is_visible`]
+    #[serde(default = "visible_by_default")]
+    is_visible: bool,
+    /// See [`SignalConfig::terrain`]
+    #[serde(default)]
+    terrain: HashMap<TerrainType, TerrainCoefficients>,
+    /// See [`SignalConfig::advection`]
+    #[serde(default)]
+    advection: f32,
+    /// See [`SignalConfig::saturation`]
+    #[serde(default)]
+    saturation: Saturation,
+}
+
+/// Signals are visible, unless the file says otherwise
+fn visible_by_default() -> bool {
+    true
+}
+
+/// The written form of a [`Sigmoid`], using the arguments of [`Sigmoid::new`]
+#[derive(Deserialize)]
+struct SigmoidEntry {
+    /// The output value approached for very small inputs
+    min: f32,
+    /// The output value approached for very large inputs
+    max: f32,
+    /// The input value below which outputs are within 1% of `min`
+    first_percentile: f32,
+    /// The input value above which outputs are within 1% of `max`
+    last_percentile: f32,
+}
+
+impl SignalConfigEntry {
+    /// Checks that every value is in range, and converts this entry into a [`SignalConfig`].
+    ///
+    /// `emitter` names the emitter in any error that is returned.
+    fn validate(&self, emitter: &str) -> Result<SignalConfig, SignalConfigError> {
+        let invalid = |reason: String| {
+            Err(SignalConfigError::InvalidValue {
+                emitter: emitter.to_string(),
+                reason,
+            })
+        };
+
+        if !(self.diffusion_factor >= 0.0 && self.diffusion_factor.is_finite()) {
+            return invalid(format!(
+                "diffusion factor {} must be a non-negative number",
+                self.diffusion_factor
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.decay_probability) {
+            return invalid(format!(
+                "decay probability {} must be between 0 and 1",
+                self.decay_probability
+            ));
+        }
+        if !self
+            .color
+            .iter()
+            .all(|component| (0.0..=1.0).contains(component))
+        {
+            return invalid(format!(
+                "colour {:?} must have components between 0 and 1",
+                self.color
+            ));
+        }
+
+        if !(self.advection >= 0.0 && self.advection.is_finite()) {
+            return invalid(format!(
+                "advection {} must be a non-negative number",
+                self.advection
+            ));
+        }
+
+        match self.saturation {
+            Saturation::Unbounded => (),
+            Saturation::Hard { max } => {
+                if !(max >= 0.0 && max.is_finite()) {
+                    return invalid(format!(
+                        "saturation maximum {max} must be a non-negative number"
+                    ));
+                }
+            }
+            Saturation::Logistic { capacity } => {
+                if !(capacity > 0.0 && capacity.is_finite()) {
+                    return invalid(format!(
+                        "saturation capacity {capacity} must be a positive number"
+                    ));
+                }
+            }
+        }
+
+        let sigmoid = &self.sigmoid;
+        if !(sigmoid.min.is_finite() && sigmoid.max.is_finite() && sigmoid.min <= sigmoid.max) {
+            return invalid(format!(
+                "sigmoid minimum {} must not be greater than its maximum {}",
+                sigmoid.min, sigmoid.max
+            ));
+        }
+        if !(sigmoid.first_percentile.is_finite()
+            && sigmoid.last_percentile.is_finite()
+            && sigmoid.first_percentile < sigmoid.last_percentile)
+        {
+            return invalid(format!(
+                "sigmoid first percentile {} must be less than its last percentile {}",
+                sigmoid.first_percentile, sigmoid.last_percentile
+            ));
+        }
+
+        let mut terrain = TerrainSignalConfig::default();
+        // Iterate over the variants, rather than the map, so that errors are always reported in the same order
+        for terrain_type in TerrainType::variants() {
+            if let Some(coefficients) = self.terrain.get(&terrain_type) {
+                if !(0.0..=1.0).contains(&coefficients.diffusion) {
+                    return invalid(format!(
+                        "diffusion coefficient {} on {terrain_type:?} terrain must be between 0 and 1",
+                        coefficients.diffusion
+                    ));
+                }
+                if !(0.0..=1.0).contains(&coefficients.decay) {
+                    return invalid(format!(
+                        "decay coefficient {} on {terrain_type:?} terrain must be between 0 and 1",
+                        coefficients.decay
+                    ));
+                }
+                *terrain.get_mut(&terrain_type) = *coefficients;
+            }
+        }
+
+        Ok(SignalConfig {
+            diffusion_factor: self.diffusion_factor,
+            decay_probability: self.decay_probability,
+            color_config: SignalColorConfig {
+                rgb_color: self.color,
+                sigmoid: Sigmoid::new(
+                    sigmoid.min,
+                    sigmoid.max,
+                    sigmoid.first_percentile,
+                    sigmoid.last_percentile,
+                ),
+                is_visible: self.is_visible,
+            },
+            terrain,
+            advection: self.advection,
+            saturation: self.saturation,
+        })
+    }
+}
+
+/// A signal configuration file could not be loaded.
+#[derive(Debug)]
+pub enum SignalConfigError {
+    /// The file could not be parsed.
+    Parse(ron::error::SpannedError),
+    /// One of the values in the file is out of range.
+    InvalidValue {
+        /// The name of the emitter whose configuration is invalid.
+        emitter: String,
+        /// What is wrong with the value.
+        reason: String,
+    },
+    /// Two custom emitters were declared with the same name.
+    DuplicateName(String),
+    /// More custom emitters were declared than there are ids.
+    TooManyCustomEmitters,
+}
+
+impl Display for SignalConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignalConfigError::Parse(error) => {
+                write!(f, "could not parse signal configuration: {error}")
+            }
+            SignalConfigError::InvalidValue { emitter, reason } => {
+                write!(f, "invalid configuration for emitter {emitter}: {reason}")
+            }
+            SignalConfigError::DuplicateName(name) => {
+                write!(f, "custom emitter {name:?} was declared more than once")
+            }
+            SignalConfigError::TooManyCustomEmitters => {
+                write!(
+                    f,
+                    "at most {} custom emitters can be declared",
+                    u16::MAX as usize + 1
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SignalConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::AssetPlugin;
+
+    /// A configuration file with one stock and two custom emitters
+    const FILE: &str = r#"(
+        stock: {
+            Ant: (
+                diffusion_factor: 0.5,
+                decay_probability: 0.25,
+                color: (1.0, 0.0, 0.0),
+                sigmoid: (min: 0.0, max: 1.0, first_percentile: 0.01, last_percentile: 0.1),
+                terrain: {
+                    Rocky: (diffusion: 0.1),
+                },
+            ),
+        },
+        custom: [
+            (
+                name: "trail",
+                display_name: Some("Foraging trail"),
+                config: (
+                    diffusion_factor: 0.1,
+                    decay_probability: 0.1,
+                    color: (0.0, 1.0, 0.0),
+                    sigmoid: (min: 0.0, max: 1.0, first_percentile: 0.0, last_percentile: 1.0),
+                    is_visible: false,
+                    advection: 0.5,
+                    saturation: Hard(max: 2.0),
+                ),
+            ),
+            (
+                name: "alarm",
+                config: (
+                    diffusion_factor: 0.2,
+                    decay_probability: 0.2,
+                    color: (0.0, 0.0, 1.0),
+                    sigmoid: (min: 0.0, max: 1.0, first_percentile: 0.0, last_percentile: 1.0),
+                ),
+            ),
+        ],
+    )"#;
+
+    #[test]
+    fn files_are_parsed_and_applied() {
+        let asset = SignalConfigAsset::from_ron_str(FILE).unwrap();
+        let mut signal_configs = SignalConfigs::default();
+        let plant_config = *signal_configs
+            .get(&Emitter::Stock(StockEmitter::Plant))
+            .unwrap();
+
+        signal_configs.apply_file(&asset).unwrap();
+
+        let ant_config = signal_configs
+            .get(&Emitter::Stock(StockEmitter::Ant))
+            .unwrap();
+        assert_eq!(ant_config.diffusion_factor, 0.5);
+        assert_eq!(ant_config.decay_probability, 0.25);
+        assert_eq!(
+            ant_config.terrain.rocky,
+            TerrainCoefficients {
+                diffusion: 0.1,
+                decay: 0.0
+            }
+        );
+        assert_eq!(ant_config.terrain.high, TerrainSignalConfig::default().high);
+        assert_eq!(ant_config.advection, 0.0);
+        assert_eq!(
+            signal_configs.get(&Emitter::Stock(StockEmitter::Plant)),
+            Some(&plant_config)
+        );
+
+        assert_eq!(
+            signal_configs.custom_emitter("trail"),
+            Some(Emitter::Custom(0))
+        );
+        assert_eq!(
+            signal_configs.custom_emitter("alarm"),
+            Some(Emitter::Custom(1))
+        );
+        assert_eq!(signal_configs.label(&Emitter::Custom(0)), "Foraging trail");
+        assert_eq!(signal_configs.label(&Emitter::Custom(1)), "alarm");
+
+        // Reapplying the file updates the existing emitters, rather than registering them again
+        signal_configs.apply_file(&asset).unwrap();
+        assert_eq!(signal_configs.custom_emitters().iter().count(), 2);
+        assert_eq!(
+            signal_configs.custom_emitter("alarm"),
+            Some(Emitter::Custom(1))
+        );
+
+        let trail_config = signal_configs.get(&Emitter::Custom(0)).unwrap();
+        assert!(!trail_config.color_config.is_visible);
+        assert_eq!(trail_config.advection, 0.5);
+        assert_eq!(trail_config.saturation, Saturation::Hard { max: 2.0 });
+        assert!(
+            signal_configs
+                .get(&Emitter::Custom(1))
+                .unwrap()
+                .color_config
+                .is_visible
+        );
+    }
+
+    #[test]
+    fn shipped_file_matches_defaults() {
+        let contents = include_str!("../../../emergence_game/assets/signals/default.signals.ron");
+        let asset = SignalConfigAsset::from_ron_str(contents).unwrap();
+        let mut signal_configs = SignalConfigs::default();
+        signal_configs.apply_file(&asset).unwrap();
+
+        for (emitter, config) in SignalConfigs::default().iter() {
+            assert_eq!(signal_configs.get(emitter), Some(config));
+        }
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let bad_decay = FILE.replace("decay_probability: 0.25", "decay_probability: 1.5");
+        assert!(matches!(
+            SignalConfigAsset::from_ron_str(&bad_decay),
+            Err(SignalConfigError::InvalidValue { emitter, .. }) if emitter == "Ant"
+        ));
+
+        let bad_sigmoid = FILE.replace("first_percentile: 0.01", "first_percentile: 0.5");
+        assert!(matches!(
+            SignalConfigAsset::from_ron_str(&bad_sigmoid),
+            Err(SignalConfigError::InvalidValue { .. })
+        ));
+
+        let bad_terrain = FILE.replace("diffusion: 0.1", "diffusion: 2.0");
+        assert!(matches!(
+            SignalConfigAsset::from_ron_str(&bad_terrain),
+            Err(SignalConfigError::InvalidValue { emitter, .. }) if emitter == "Ant"
+        ));
+
+        let bad_advection = FILE.replace("advection: 0.5", "advection: -1.0");
+        assert!(matches!(
+            SignalConfigAsset::from_ron_str(&bad_advection),
+            Err(SignalConfigError::InvalidValue { emitter, .. }) if emitter == "trail"
+        ));
+
+        let bad_saturation = FILE.replace("Hard(max: 2.0)", "Logistic(capacity: 0.0)");
+        assert!(matches!(
+            SignalConfigAsset::from_ron_str(&bad_saturation),
+            Err(SignalConfigError::InvalidValue { emitter, .. }) if emitter == "trail"
+        ));
+
+        let duplicate = FILE.replace("\"alarm\"", "\"trail\"");
+        assert!(matches!(
+            SignalConfigAsset::from_ron_str(&duplicate),
+            Err(SignalConfigError::DuplicateName(name)) if name == "trail"
+        ));
+
+        assert!(matches!(
+            SignalConfigAsset::from_ron_str("(stock: {Ant: ()})"),
+            Err(SignalConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn modified_files_are_reapplied() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<SignalConfigAsset>()
+            .init_resource::<SignalConfigs>()
+            .add_system(apply_signal_config_file);
+
+        let asset = SignalConfigAsset::from_ron_str(FILE).unwrap();
+        let handle = app
+            .world
+            .resource_mut::<Assets<SignalConfigAsset>>()
+            .add(asset);
+        app.insert_resource(SignalConfigHandle(handle.clone()));
+
+        let ant_decay = |app: &App| {
+            app.world
+                .resource::<SignalConfigs>()
+                .get(&Emitter::Stock(StockEmitter::Ant))
+                .unwrap()
+                .decay_probability
+        };
+
+        // Asset events are sent at the end of the frame, so take effect on the next one
+        app.update();
+        app.update();
+        assert_eq!(ant_decay(&app), 0.25);
+
+        let edited = FILE.replace("decay_probability: 0.25", "decay_probability: 0.75");
+        *app.world
+            .resource_mut::<Assets<SignalConfigAsset>>()
+            .get_mut(&handle)
+            .unwrap() = SignalConfigAsset::from_ron_str(&edited).unwrap();
+
+        app.update();
+        app.update();
+        assert_eq!(ant_decay(&app), 0.75);
+    }
+}
@@ -1,10 +1,18 @@
 //! Utilities to manage configuration of signals (colour, decay rate, etc.).
 
-use crate::curves::Sigmoid;
+use crate::curves::{Mapping, Sigmoid};
 use crate::enum_iter::IterableEnum;
+use crate::items::ItemId;
+use crate::signals::config_file::SignalConfigAsset;
 use crate::signals::emitters::{Emitter, StockEmitter};
+use crate::signals::registry::{
+    CustomEmitter, CustomEmitterNames, CustomEmitterRegistry, RegistrationError,
+};
+use crate::terrain::TerrainType;
 use bevy::ecs::system::Resource;
+use bevy::render::color::Color;
 use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 
 /// A dictionary of available [`SignalConfig`]s.
 ///
@@ -15,6 +23,8 @@ use indexmap::IndexMap;
 pub struct SignalConfigs {
     /// Stores the configuration associated with each emitter.
     configs: IndexMap<Emitter, SignalConfig>,
+    /// The names of the custom emitters that have been registered.
+    custom_emitters: CustomEmitterRegistry,
 }
 
 impl Default for SignalConfigs {
@@ -31,6 +41,9 @@ impl Default for SignalConfigs {
                         sigmoid: Sigmoid::new(0.0, 1.0, 0.01, 0.1),
                         is_visible: true,
                     },
+                    terrain: TerrainSignalConfig::default(),
+                    advection: 0.0,
+                    saturation: Saturation::Unbounded,
                 },
                 StockEmitter::Plant => SignalConfig {
                     diffusion_factor: 1e-4,
@@ -40,6 +53,9 @@ impl Default for SignalConfigs {
                         sigmoid: Sigmoid::new(0.0, 1.0, 0.01, 0.1),
                         is_visible: true,
                     },
+                    terrain: TerrainSignalConfig::default(),
+                    advection: 0.0,
+                    saturation: Saturation::Unbounded,
                 },
                 StockEmitter::Fungus => SignalConfig {
                     diffusion_factor: 1e-4,
@@ -49,6 +65,9 @@ impl Default for SignalConfigs {
                         sigmoid: Sigmoid::new(0.0, 1.0, 0.01, 0.1),
                         is_visible: true,
                     },
+                    terrain: TerrainSignalConfig::default(),
+                    advection: 0.0,
+                    saturation: Saturation::Unbounded,
                 },
                 StockEmitter::Unspecified => SignalConfig {
                     diffusion_factor: 1e-4,
@@ -58,6 +77,9 @@ impl Default for SignalConfigs {
                         sigmoid: Sigmoid::new(0.0, 1.0, 0.01, 0.1),
                         is_visible: true,
                     },
+                    terrain: TerrainSignalConfig::default(),
+                    advection: 0.0,
+                    saturation: Saturation::Unbounded,
                 },
                 StockEmitter::PheromoneAttract => SignalConfig {
                     diffusion_factor: 1e-4,
@@ -67,6 +89,9 @@ impl Default for SignalConfigs {
                         sigmoid: Sigmoid::new(0.0, 1.0, 0.01, 0.2),
                         is_visible: true,
                     },
+                    terrain: TerrainSignalConfig::default(),
+                    advection: 0.0,
+                    saturation: Saturation::Unbounded,
                 },
                 StockEmitter::PheromoneRepulse => SignalConfig {
                     diffusion_factor: 1e-4,
@@ -76,12 +101,67 @@ impl Default for SignalConfigs {
                         sigmoid: Sigmoid::new(0.0, 1.0, 0.01, 0.1),
                         is_visible: true,
                     },
+                    terrain: TerrainSignalConfig::default(),
+                    advection: 0.0,
+                    saturation: Saturation::Unbounded,
                 },
             };
             configs.insert(Emitter::Stock(variant), config);
         }
 
-        SignalConfigs { configs }
+        // Logistic signals are hidden by default, as there are two for every item
+        for item_id in ItemId::all() {
+            configs.insert(
+                Emitter::Push(item_id),
+                SignalConfig {
+                    diffusion_factor: 1e-4,
+                    decay_probability: 1e-2,
+                    color_config: SignalColorConfig {
+                        rgb_color: [0.2, 0.8, 0.2],
+                        sigmoid: Sigmoid::new(0.0, 1.0, 0.01, 0.1),
+                        is_visible: false,
+                    },
+                    terrain: TerrainSignalConfig::default(),
+                    advection: 0.0,
+                    saturation: Saturation::Unbounded,
+                },
+            );
+            configs.insert(
+                Emitter::Pull(item_id),
+                SignalConfig {
+                    diffusion_factor: 1e-4,
+                    decay_probability: 1e-2,
+                    color_config: SignalColorConfig {
+                        rgb_color: [0.9, 0.5, 0.1],
+                        sigmoid: Sigmoid::new(0.0, 1.0, 0.01, 0.1),
+                        is_visible: false,
+                    },
+                    terrain: TerrainSignalConfig::default(),
+                    advection: 0.0,
+                    saturation: Saturation::Unbounded,
+                },
+            );
+        }
+        configs.insert(
+            Emitter::Work,
+            SignalConfig {
+                diffusion_factor: 1e-4,
+                decay_probability: 1e-2,
+                color_config: SignalColorConfig {
+                    rgb_color: [0.9, 0.9, 0.2],
+                    sigmoid: Sigmoid::new(0.0, 1.0, 0.01, 0.1),
+                    is_visible: false,
+                },
+                terrain: TerrainSignalConfig::default(),
+                advection: 0.0,
+                saturation: Saturation::Unbounded,
+            },
+        );
+
+        SignalConfigs {
+            configs,
+            custom_emitters: CustomEmitterRegistry::default(),
+        }
     }
 }
 
@@ -100,36 +180,248 @@ impl SignalConfigs {
         self.configs.insert(emitter, config)
     }
 
+    /// Registers a new custom emitter, giving it the lowest id that is not already in use.
+    ///
+    /// Fails if an emitter with the same name has already been registered.
+    pub fn register_custom_emitter(
+        &mut self,
+        custom_emitter: CustomEmitter,
+    ) -> Result<Emitter, RegistrationError> {
+        let id = self
+            .custom_emitters
+            .free_id(|id| !self.configs.contains_key(&Emitter::Custom(id)))?;
+        self.register_custom_emitter_with_id(id, custom_emitter)
+    }
+
+    /// Registers a new custom emitter with the given `id`, as recorded in a save file.
+    ///
+    /// Fails if an emitter with the same name has already been registered, or if `id` is
+    /// already in use.
+    pub fn register_custom_emitter_with_id(
+        &mut self,
+        id: u16,
+        custom_emitter: CustomEmitter,
+    ) -> Result<Emitter, RegistrationError> {
+        let emitter = Emitter::Custom(id);
+        if self.configs.contains_key(&emitter) && self.custom_emitters.names(&emitter).is_none() {
+            return Err(RegistrationError::IdTaken { id, existing: None });
+        }
+
+        self.custom_emitters.insert(
+            id,
+            CustomEmitterNames {
+                name: custom_emitter.name,
+                display_name: custom_emitter.display_name,
+            },
+        )?;
+        self.configs.insert(emitter, custom_emitter.config);
+        Ok(emitter)
+    }
+
+    /// The custom emitter registered with the given `name`, if any.
+    pub fn custom_emitter(&self, name: &str) -> Option<Emitter> {
+        self.custom_emitters.emitter(name)
+    }
+
+    /// The names of every registered custom emitter.
+    pub fn custom_emitters(&self) -> &CustomEmitterRegistry {
+        &self.custom_emitters
+    }
+
+    /// A human-readable name for `emitter`, preferring the display name of registered custom
+    /// emitters.
+    pub fn label(&self, emitter: &Emitter) -> String {
+        match self.custom_emitters.names(emitter) {
+            Some(names) => names.display_name.clone(),
+            None => emitter.to_string(),
+        }
+    }
+
+    /// Applies the configuration from a signal configuration file.
+    ///
+    /// Emitters in the file replace their existing configuration, keeping their position in
+    /// the order; emitters that are not in the file are left untouched.
+    /// Custom emitters that have already been registered under the same name are updated, rather
+    /// than registered again, so that files can be reapplied after they are edited.
+    pub fn apply_file(&mut self, file: &SignalConfigAsset) -> Result<(), RegistrationError> {
+        for (emitter, config) in file.configs() {
+            self.configs.insert(*emitter, *config);
+        }
+        for custom_emitter in file.custom_emitters() {
+            match self.custom_emitter(&custom_emitter.name) {
+                Some(emitter) => {
+                    self.configs.insert(emitter, custom_emitter.config);
+                    self.custom_emitters
+                        .rename(&custom_emitter.name, custom_emitter.display_name.clone());
+                }
+                None => {
+                    self.register_custom_emitter(custom_emitter.clone())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Iterate over the signals at this tile, in the order they were inserted.
     pub fn iter(&self) -> impl Iterator<Item = (&Emitter, &SignalConfig)> {
         self.configs.iter()
     }
 }
 
-/// Configuration settings for a particular [`Signal`](crate::signals::Signal).
-#[derive(Clone, Copy, Debug)]
+/// Configuration settings for the signal from a particular [`Emitter`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SignalConfig {
-    /// The factor with which a unit of signal diffuses to a neighboring tile per tick.
+    /// The fraction of a tile's signal that diffuses to each neighboring tile per
+    /// [tick](crate::signals::diffusion::SignalTimestep).
     ///
-    /// Note that this is not a probability, as it is un-normalized.
+    /// Large factors are simulated in several smaller steps, so that signals never become negative.
     pub diffusion_factor: f32,
-    /// The probability with which a signal naturally decays per tick.
+    /// The fraction of a tile's signal that naturally decays per
+    /// [tick](crate::signals::diffusion::SignalTimestep).
     pub decay_probability: f32,
     /// Color settings.
     pub color_config: SignalColorConfig,
+    /// How the signal spreads and decays on each type of terrain.
+    #[serde(default)]
+    pub terrain: TerrainSignalConfig,
+    /// How strongly the signal is carried by the [wind](crate::signals::field::SignalField::wind).
+    ///
+    /// Ground-laid signals use `0.0`, and are not carried at all, while airborne signals use `1.0`,
+    /// and move at the speed of the wind.
+    #[serde(default)]
+    pub advection: f32,
+    /// How the signal saturates as more of it is deposited on a single tile.
+    #[serde(default)]
+    pub saturation: Saturation,
 }
 
-/// Color configuration for a [`Signal`](crate::signals::Signal).
+/// How the value of a signal saturates as more of it is deposited on a single tile.
 ///
-/// The final coloration for a [`Signal`](crate::signals::Signal) at a particular tile position will depend upon the
-/// `rgb_color` specified in the color configuration, and the `value` of the [`Signal`](crate::signals::Signal), which will
+/// Saturation limits what [emitters](crate::signals::emitters::EmissionRate) deposit, so that
+/// signals build up over time without growing without bound. Signal that arrives by diffusion is
+/// not limited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Saturation {
+    /// Deposits are never limited.
+    #[default]
+    Unbounded,
+    /// Deposits stop once the signal on a tile reaches `max`.
+    Hard {
+        /// The largest value that deposits can raise the signal to.
+        max: f32,
+    },
+    /// Deposits are scaled down as the signal on a tile approaches `capacity`, following the
+    /// logistic curve.
+    Logistic {
+        /// The value that the signal approaches, but never exceeds, as more is deposited.
+        capacity: f32,
+    },
+}
+
+impl Saturation {
+    /// The value of a signal with the given `value` after `amount` is deposited on top of it.
+    ///
+    /// Negative amounts, which remove signal, are never limited. Deposits never reduce signal that
+    /// is already above the limit.
+    pub fn deposit(&self, value: f32, amount: f32) -> f32 {
+        if amount <= 0.0 {
+            return value + amount;
+        }
+
+        match *self {
+            Saturation::Unbounded => value + amount,
+            Saturation::Hard { max } => (value + amount).min(max.max(value)),
+            Saturation::Logistic { capacity } => {
+                let headroom = (1.0 - value / capacity).max(0.0);
+                (value + amount * headroom).min(capacity.max(value))
+            }
+        }
+    }
+}
+
+/// How a signal spreads and decays on a single type of terrain.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TerrainCoefficients {
+    /// Scales the [`diffusion_factor`](SignalConfig::diffusion_factor) of signal moving onto or
+    /// off of this terrain, from `0.0` to `1.0`.
+    ///
+    /// Signal moves between two tiles at the smaller of their coefficients, so terrain with a
+    /// coefficient of `0.0` blocks the signal entirely.
+    pub diffusion: f32,
+    /// The fraction of signal on this terrain that is lost per tick, on top of the signal's
+    /// [`decay_probability`](SignalConfig::decay_probability).
+    #[serde(default)]
+    pub decay: f32,
+}
+
+impl TerrainCoefficients {
+    /// Terrain that does not affect the signal at all
+    pub const OPEN: TerrainCoefficients = TerrainCoefficients {
+        diffusion: 1.0,
+        decay: 0.0,
+    };
+}
+
+/// The [`TerrainCoefficients`] of a signal on each [`TerrainType`].
+///
+/// By default, rock blocks signals, and high ground slows them.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TerrainSignalConfig {
+    /// The coefficients for [`TerrainType::Plain`]
+    pub plain: TerrainCoefficients,
+    /// The coefficients for [`TerrainType::Rocky`]
+    pub rocky: TerrainCoefficients,
+    /// The coefficients for [`TerrainType::High`]
+    pub high: TerrainCoefficients,
+}
+
+impl TerrainSignalConfig {
+    /// The coefficients for the given `terrain_type`
+    pub fn get(&self, terrain_type: &TerrainType) -> &TerrainCoefficients {
+        match terrain_type {
+            TerrainType::Plain => &self.plain,
+            TerrainType::Rocky => &self.rocky,
+            TerrainType::High => &self.high,
+        }
+    }
+
+    /// Mutable access to the coefficients for the given `terrain_type`
+    pub fn get_mut(&mut self, terrain_type: &TerrainType) -> &mut TerrainCoefficients {
+        match terrain_type {
+            TerrainType::Plain => &mut self.plain,
+            TerrainType::Rocky => &mut self.rocky,
+            TerrainType::High => &mut self.high,
+        }
+    }
+}
+
+impl Default for TerrainSignalConfig {
+    fn default() -> Self {
+        TerrainSignalConfig {
+            plain: TerrainCoefficients::OPEN,
+            rocky: TerrainCoefficients {
+                diffusion: 0.0,
+                decay: 0.0,
+            },
+            high: TerrainCoefficients {
+                diffusion: 0.5,
+                decay: 0.0,
+            },
+        }
+    }
+}
+
+/// Color configuration for the signal from a particular [`Emitter`].
+///
+/// The final coloration for a signal at a particular tile position will depend upon the
+/// `rgb_color` specified in the color configuration, and the `value` of the signal, which will
 /// affect the computed color's `alpha`.
 ///
 /// If `value` is below `zero_value`, then the computed color will have alpha `0.0`, otherwise if
 /// the value is above `one_value`, then the computed color will have alpha `1.0`. If `value` is
 /// between `zero_value` and `one_value`, then `alpha` will be mapped to some point between these
 /// two.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SignalColorConfig {
     /// The three primary colour values (rgb) defining the colour used.
     pub rgb_color: [f32; 3],
@@ -138,3 +430,20 @@ pub struct SignalColorConfig {
     /// Should this signal be visible on the map?
     pub is_visible: bool,
 }
+
+impl SignalColorConfig {
+    /// Compute the color of a signal with the given `value`.
+    ///
+    /// This ignores [`is_visible`](SignalColorConfig::is_visible): check it before calling this.
+    pub fn compute_color(&self, value: f32) -> Color {
+        // This produces a Color::Rgba variant.
+        let mut color = Color::from(self.rgb_color);
+
+        // What are the possible values for a signal? [0, \infty)
+        // What are we mapping to? [0, 1), the alpha component of our color
+        // Use a shifted sigmoid to represent this
+        color.set_a(self.sigmoid.map(value));
+
+        color
+    }
+}
@@ -0,0 +1,729 @@
+//! Decay and diffusion of signals, integrated over a fixed simulation tick.
+//!
+//! Each tick, every signal first decays, then diffuses: each tile exchanges signal with each of its
+//! neighbors in proportion to the difference between their values. As every unit of signal that
+//! leaves a tile arrives at one of its neighbors, diffusion conserves the total amount of signal.
+//!
+//! Terrain changes both rates, according to each signal's
+//! [`TerrainSignalConfig`](crate::signals::configs::TerrainSignalConfig): rock can stop a signal
+//! from spreading at all, while other terrain can slow it down, or wash it away.
+//!
+//! Signals are also carried by the [wind](SignalField::wind), in proportion to their
+//! [`advection`](SignalConfig::advection). Each tile sends signal to the one or two neighbors that
+//! lie downwind of it, so that the signal moves, on average, at the speed of the wind. Like
+//! diffusion, advection conserves signal: signal is never carried off the edge of the map.
+//!
+//! Every tile's next value depends only on the current values, so the tiles of each
+//! [`SignalLayer`] are updated in parallel, double-buffered.
+
+use crate::enum_iter::IterableEnum;
+use crate::signals::configs::{SignalConfig, SignalConfigs};
+use crate::signals::field::{Neighbors, SignalField, SignalLayer, Topology};
+use crate::terrain::TerrainType;
+use bevy::prelude::*;
+use bevy::tasks::{ComputeTaskPool, TaskPool};
+use std::time::Duration;
+
+/// The largest fraction of its value that a signal may send to its neighbors in a single substep.
+///
+/// Keeping this below one ensures that signals never become negative, and do not oscillate.
+const MAX_OUTFLOW: f32 = 0.5;
+
+/// The number of neighbors of a tile that is not on the edge of the map
+const MAX_NEIGHBORS: f32 = 6.0;
+
+/// Controls how often signals are simulated.
+///
+/// Signals are simulated in ticks of a fixed length, so that their behavior does not depend on the frame rate.
+#[derive(Resource, Debug, Clone)]
+pub struct SignalTimestep {
+    /// The amount of time simulated by each tick.
+    pub tick: Duration,
+    /// The most ticks that can be simulated in a single frame.
+    ///
+    /// If the game falls further behind than this, the remaining time is dropped, rather than
+    /// making the next frame even slower.
+    pub max_ticks_per_frame: u32,
+    /// Time that has passed, but has not been simulated yet
+    accumulated: Duration,
+    /// The number of ticks simulated this frame
+    ticks_this_frame: u32,
+}
+
+impl SignalTimestep {
+    /// Creates a new [`SignalTimestep`] with the given tick length.
+    pub fn new(tick: Duration) -> SignalTimestep {
+        SignalTimestep {
+            tick,
+            max_ticks_per_frame: 10,
+            accumulated: Duration::ZERO,
+            ticks_this_frame: 0,
+        }
+    }
+
+    /// Records that `delta` time has passed, returning the number of ticks to simulate this frame.
+    pub fn advance(&mut self, delta: Duration) -> u32 {
+        self.accumulated += delta;
+
+        let mut ticks = 0;
+        while self.accumulated >= self.tick && !self.tick.is_zero() {
+            self.accumulated -= self.tick;
+            ticks += 1;
+        }
+
+        if ticks > self.max_ticks_per_frame {
+            ticks = self.max_ticks_per_frame;
+            self.accumulated = Duration::ZERO;
+        }
+
+        self.ticks_this_frame = ticks;
+        ticks
+    }
+
+    /// The number of ticks simulated this frame.
+    ///
+    /// Anything that adds signal at a constant rate per tick should multiply it by this.
+    pub fn ticks_this_frame(&self) -> u32 {
+        self.ticks_this_frame
+    }
+}
+
+impl Default for SignalTimestep {
+    fn default() -> Self {
+        SignalTimestep::new(Duration::from_millis(50))
+    }
+}
+
+/// Controls when faint signals are removed from the [`SignalField`].
+///
+/// Decay only ever shrinks signals, so without pruning, every tile would keep a tiny value of every
+/// signal that ever reached it.
+#[derive(Resource, Debug, Clone)]
+pub struct SignalPruning {
+    /// Signals weaker than this are set to `0.0` after each frame's ticks are simulated.
+    ///
+    /// See [`SignalField::prune`].
+    pub epsilon: f32,
+}
+
+impl Default for SignalPruning {
+    fn default() -> Self {
+        SignalPruning { epsilon: 1e-6 }
+    }
+}
+
+/// The number of substeps each tick must be split into, so that no signal with the given
+/// `diffusion_factor` moves more than [`MAX_OUTFLOW`] of its value in a single substep.
+pub fn diffusion_substeps(diffusion_factor: f32) -> u32 {
+    transport_substeps(diffusion_factor, 0.0)
+}
+
+/// The number of substeps each tick must be split into, so that no signal with the given
+/// `diffusion_factor`, of which the wind carries at most `advected_fraction` away from a tile each
+/// tick, moves more than [`MAX_OUTFLOW`] of its value in a single substep.
+pub fn transport_substeps(diffusion_factor: f32, advected_fraction: f32) -> u32 {
+    (((MAX_NEIGHBORS * diffusion_factor + advected_fraction) / MAX_OUTFLOW).ceil() as u32).max(1)
+}
+
+/// The fraction of a tile's signal that a `wind` of unit strength carries to the neighbor in
+/// `direction`, which must be a unit vector.
+///
+/// The wind is split between the two neighbors on either side of it, so that the shares of all six
+/// neighbors add up to the wind itself. Neighbors that are 60 degrees or more from the wind get
+/// nothing.
+fn downwind_share(wind: Vec2, direction: Vec2) -> f32 {
+    (wind.dot(direction) - wind.perp_dot(direction).abs() / 3f32.sqrt()).max(0.0)
+}
+
+/// The number of tiles updated by each task.
+///
+/// Large enough that the cost of spawning a task is small compared to the work it does.
+const CHUNK_SIZE: usize = 4096;
+
+/// A value for each [`TerrainType`], indexed by [`IterableEnum::index`]
+type TerrainTable = [f32; TerrainType::N_VARIANTS];
+
+/// The work needed to simulate a single [`SignalLayer`] for one tick
+struct LayerStep<'a> {
+    /// The layer being simulated
+    layer: &'a mut SignalLayer,
+    /// The number of substeps the tick is split into for this layer
+    substeps: u32,
+    /// The fraction of its value that a tile sends to each open neighbor in each substep
+    rate: f32,
+    /// How strongly the wind carries the signal in each substep
+    advection: f32,
+    /// How freely the signal moves across each type of terrain
+    conductance: TerrainTable,
+    /// The fraction of the signal that remains after decay, on each type of terrain
+    retained: TerrainTable,
+    /// How freely the signal moves across each tile, given its terrain
+    tile_conductance: Vec<f32>,
+}
+
+impl<'a> LayerStep<'a> {
+    /// Prepares to simulate `layer`, according to its `config`, where no tile has a wind stronger
+    /// than `max_wind`
+    fn new(layer: &'a mut SignalLayer, config: &SignalConfig, max_wind: f32) -> LayerStep<'a> {
+        let advection = config.advection.max(0.0);
+        // The shares of the neighbors add up to at most 2 / sqrt(3) of the wind's strength
+        let advected_fraction = advection * max_wind * 2.0 / 3f32.sqrt();
+        let substeps = transport_substeps(config.diffusion_factor, advected_fraction);
+        let mut conductance = [0.0; TerrainType::N_VARIANTS];
+        let mut retained = [0.0; TerrainType::N_VARIANTS];
+        for terrain_type in TerrainType::variants() {
+            let coefficients = config.terrain.get(&terrain_type);
+            conductance[terrain_type.index()] = coefficients.diffusion.clamp(0.0, 1.0);
+            retained[terrain_type.index()] =
+                ((1.0 - config.decay_probability) * (1.0 - coefficients.decay)).clamp(0.0, 1.0);
+        }
+
+        LayerStep {
+            layer,
+            substeps,
+            rate: config.diffusion_factor.max(0.0) / substeps as f32,
+            advection: advection / substeps as f32,
+            conductance,
+            retained,
+            tile_conductance: Vec::new(),
+        }
+    }
+}
+
+/// Everything needed to compute the next values of any chunk of a layer, for a single substep
+#[derive(Clone, Copy)]
+struct Substep<'a> {
+    /// The current value of the signal at each tile
+    values: &'a [f32],
+    /// The neighbors of each tile
+    neighbors: &'a [Neighbors],
+    /// How freely the signal moves across each tile
+    conductance: &'a [f32],
+    /// The wind at each tile
+    wind: &'a [Vec2],
+    /// The fraction of its value that a tile sends to each open neighbor
+    rate: f32,
+    /// How strongly the wind carries the signal
+    advection: f32,
+}
+
+/// Simulates a single tick of decay and diffusion for every signal on the map.
+///
+/// Signals first decay, and then diffuse and are carried by the wind. Each [`SignalLayer`] is split into chunks of tiles,
+/// which are updated in parallel on the [`ComputeTaskPool`]. Every substep of diffusion reads from
+/// the current values of a layer and writes into its second buffer, so tiles can be updated in any
+/// order.
+///
+/// Signals from emitters without a [`SignalConfig`] are left untouched.
+pub fn tick_signals(signal_field: &mut SignalField, signal_configs: &SignalConfigs) {
+    let task_pool = ComputeTaskPool::init(TaskPool::default);
+    let (topology, layers) = signal_field.topology_and_layers_mut();
+    let Topology {
+        neighbors,
+        terrain,
+        wind,
+    } = topology;
+    let max_wind = wind.iter().map(|wind| wind.length()).fold(0.0, f32::max);
+
+    let mut steps: Vec<LayerStep> = layers
+        .filter_map(|(emitter, layer)| {
+            Some(LayerStep::new(
+                layer,
+                signal_configs.get(emitter)?,
+                max_wind,
+            ))
+        })
+        .collect();
+    let max_substeps = steps.iter().map(|step| step.substeps).max().unwrap_or(0);
+
+    // Decay each tile, while looking up how freely the signal moves across it
+    task_pool.scope(|scope| {
+        for step in steps.iter_mut() {
+            step.tile_conductance.resize(terrain.len(), 0.0);
+            let (retained, conductance) = (&step.retained, &step.conductance);
+            let chunks = step
+                .layer
+                .values_mut()
+                .chunks_mut(CHUNK_SIZE)
+                .zip(step.tile_conductance.chunks_mut(CHUNK_SIZE))
+                .zip(terrain.chunks(CHUNK_SIZE));
+
+            for ((values, tile_conductance), terrain) in chunks {
+                scope.spawn(async move {
+                    for ((value, tile_conductance), terrain_type) in
+                        values.iter_mut().zip(tile_conductance).zip(terrain)
+                    {
+                        *value *= retained[terrain_type.index()];
+                        *tile_conductance = conductance[terrain_type.index()];
+                    }
+                });
+            }
+        }
+    });
+
+    for substep in 0..max_substeps {
+        let active = steps.iter_mut().filter(|step| substep < step.substeps);
+        task_pool.scope(|scope| {
+            for step in active {
+                let (values, next) = step.layer.buffers();
+                let params = Substep {
+                    values,
+                    neighbors,
+                    conductance: &step.tile_conductance,
+                    wind,
+                    rate: step.rate,
+                    advection: step.advection,
+                };
+
+                for (chunk, next) in next.chunks_mut(CHUNK_SIZE).enumerate() {
+                    let start = chunk * CHUNK_SIZE;
+                    scope.spawn(async move {
+                        diffuse_chunk(params, next, start);
+                    });
+                }
+            }
+        });
+
+        for step in steps.iter_mut().filter(|step| substep < step.substeps) {
+            step.layer.swap();
+        }
+    }
+}
+
+/// Computes the next values of the tiles from `start` to `start + next.len()`.
+///
+/// Each tile exchanges signal with each of its neighbors. The rate of exchange is scaled by the
+/// smaller conductance of the two tiles' terrain, so that the flow between any pair of tiles is
+/// the same in both directions.
+///
+/// The wind then carries signal from each tile to its downwind neighbors, scaled by the same
+/// conductance.
+fn diffuse_chunk(substep: Substep, next: &mut [f32], start: usize) {
+    let Substep {
+        values,
+        neighbors,
+        conductance,
+        wind,
+        rate,
+        advection,
+    } = substep;
+
+    for (offset, next) in next.iter_mut().enumerate() {
+        let tile = start + offset;
+        let value = values[tile];
+        let tile_conductance = conductance[tile];
+
+        let mut flow = 0.0;
+        for neighbor in neighbors[tile].as_slice() {
+            let neighbor = *neighbor as usize;
+            let edge_conductance = tile_conductance.min(conductance[neighbor]);
+            flow += edge_conductance * (values[neighbor] - value);
+        }
+        flow *= rate;
+
+        if advection > 0.0 {
+            let tile_neighbors = &neighbors[tile];
+            let mut advected = 0.0;
+            for (neighbor, direction) in tile_neighbors
+                .as_slice()
+                .iter()
+                .zip(tile_neighbors.directions())
+            {
+                let neighbor = *neighbor as usize;
+                let edge_conductance = tile_conductance.min(conductance[neighbor]);
+                let inflow = downwind_share(wind[neighbor], -*direction) * values[neighbor];
+                let outflow = downwind_share(wind[tile], *direction) * value;
+                advected += edge_conductance * (inflow - outflow);
+            }
+            flow += advection * advected;
+        }
+
+        *next = (value + flow).max(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signals::emitters::{Emitter, StockEmitter};
+    use crate::signals::field::test_utils::{map, EMITTER};
+    use crate::signals::sensing::plane_position;
+    use crate::simulation::map::{MapGeometry, MapPositions, MapShape};
+    use bevy_ecs_tilemap::tiles::TilePos;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Signal configs where [`EMITTER`] has the given rates
+    fn configs(diffusion_factor: f32, decay_probability: f32) -> SignalConfigs {
+        let mut signal_configs = SignalConfigs::default();
+        let config = *signal_configs.get(&EMITTER).unwrap();
+        signal_configs.insert(
+            EMITTER,
+            SignalConfig {
+                diffusion_factor,
+                decay_probability,
+                ..config
+            },
+        );
+        signal_configs
+    }
+
+    /// The total amount of signal from [`EMITTER`] on the map
+    fn total(signal_field: &SignalField) -> f32 {
+        signal_field.layer(&EMITTER).unwrap().total()
+    }
+
+    /// Adds a random amount of signal to random tiles, including those on the edge of the map
+    fn randomize(signal_field: &mut SignalField, rng: &mut StdRng) {
+        let positions: Vec<TilePos> = signal_field.index().positions().to_vec();
+        for _ in 0..10 {
+            let position = positions[rng.gen_range(0..positions.len())];
+            let amount = rng.gen_range(0.0..10.0);
+            signal_field.increment(EMITTER, &position, amount);
+        }
+    }
+
+    #[test]
+    fn diffusion_conserves_mass() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..20 {
+            let (_, _, mut signal_field) = map();
+            randomize(&mut signal_field, &mut rng);
+            // Includes rates large enough to need several substeps
+            let signal_configs = configs(rng.gen_range(0.0..0.5), 0.0);
+
+            let initial = total(&signal_field);
+            for _ in 0..50 {
+                tick_signals(&mut signal_field, &signal_configs);
+                assert!(signal_field
+                    .layer(&EMITTER)
+                    .unwrap()
+                    .values()
+                    .iter()
+                    .all(|value| *value >= 0.0));
+            }
+
+            assert!((total(&signal_field) - initial).abs() <= 1e-3 * initial);
+        }
+    }
+
+    #[test]
+    fn decay_removes_mass_at_its_rate() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (_, _, mut signal_field) = map();
+        randomize(&mut signal_field, &mut rng);
+        let signal_configs = configs(0.05, 0.1);
+
+        let initial = total(&signal_field);
+        for _ in 0..10 {
+            tick_signals(&mut signal_field, &signal_configs);
+        }
+
+        let expected = initial * 0.9f32.powi(10);
+        assert!((total(&signal_field) - expected).abs() <= 1e-3 * initial);
+    }
+
+    #[test]
+    fn diffusion_is_symmetric() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let (_, map_positions, _) = map();
+        let positions: Vec<TilePos> = map_positions.iter_positions().copied().collect();
+        let signal_configs = configs(0.1, 0.0);
+
+        for _ in 0..10 {
+            let a = positions[rng.gen_range(0..positions.len())];
+            let b = positions[rng.gen_range(0..positions.len())];
+
+            // The amount of signal that travels from `from` to `to` in a few ticks
+            let transfer = |from: TilePos, to: TilePos| {
+                let (_, _, mut signal_field) = map();
+                signal_field.increment(EMITTER, &from, 1.0);
+                for _ in 0..5 {
+                    tick_signals(&mut signal_field, &signal_configs);
+                }
+                signal_field.get(&EMITTER, &to)
+            };
+
+            assert!((transfer(a, b) - transfer(b, a)).abs() <= 1e-6);
+        }
+    }
+
+    #[test]
+    fn diffusion_reaches_uniform_steady_state() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let (_, map_positions, mut signal_field) = map();
+        randomize(&mut signal_field, &mut rng);
+        let signal_configs = configs(0.1, 0.0);
+
+        let mean = total(&signal_field) / map_positions.n_positions() as f32;
+        for _ in 0..1000 {
+            tick_signals(&mut signal_field, &signal_configs);
+        }
+
+        for value in signal_field.layer(&EMITTER).unwrap().values() {
+            assert!((value - mean).abs() <= 1e-3 * mean);
+        }
+    }
+
+    #[test]
+    fn diffusion_rate_is_honored() {
+        let (map_geometry, map_positions, _) = map();
+        let center = map_geometry.center();
+
+        // The amount that reaches each neighbor of the center in one tick
+        let spread = |diffusion_factor: f32| {
+            let (_, _, mut signal_field) = map();
+            signal_field.increment(EMITTER, &center, 1.0);
+            tick_signals(&mut signal_field, &configs(diffusion_factor, 0.0));
+
+            let neighbor = map_positions.ring(&center, 1).next().unwrap();
+            signal_field.get(&EMITTER, &neighbor)
+        };
+
+        assert!((spread(0.01) - 0.01).abs() <= 1e-6);
+        assert!((spread(0.02) - 2.0 * spread(0.01)).abs() <= 1e-4);
+        assert_eq!(spread(0.0), 0.0);
+    }
+
+    #[test]
+    fn rock_blocks_diffusion() {
+        let (map_geometry, map_positions, mut signal_field) = map();
+        let center = map_geometry.center();
+        for position in map_positions.ring(&center, 1) {
+            signal_field.set_terrain(&position, TerrainType::Rocky);
+        }
+        signal_field.increment(EMITTER, &center, 1.0);
+
+        for _ in 0..100 {
+            tick_signals(&mut signal_field, &configs(0.1, 0.0));
+        }
+
+        assert_eq!(signal_field.get(&EMITTER, &center), 1.0);
+    }
+
+    #[test]
+    fn high_ground_slows_diffusion() {
+        let (map_geometry, map_positions, _) = map();
+        let center = map_geometry.center();
+        let neighbor = map_positions.ring(&center, 1).next().unwrap();
+
+        // The amount that reaches a neighbor of the center in one tick
+        let spread = |terrain_type: TerrainType| {
+            let (_, _, mut signal_field) = map();
+            signal_field.set_terrain(&center, terrain_type);
+            signal_field.increment(EMITTER, &center, 1.0);
+            tick_signals(&mut signal_field, &configs(0.01, 0.0));
+            signal_field.get(&EMITTER, &neighbor)
+        };
+
+        assert!((spread(TerrainType::High) - 0.5 * spread(TerrainType::Plain)).abs() <= 1e-6);
+    }
+
+    #[test]
+    fn terrain_decay_removes_mass() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let (_, _, mut signal_field) = map();
+        randomize(&mut signal_field, &mut rng);
+        let mut signal_configs = configs(0.0, 0.1);
+        let mut config = *signal_configs.get(&EMITTER).unwrap();
+        config.terrain.plain.decay = 0.5;
+        signal_configs.insert(EMITTER, config);
+
+        let initial = total(&signal_field);
+        tick_signals(&mut signal_field, &signal_configs);
+
+        assert!((total(&signal_field) - initial * 0.9 * 0.5).abs() <= 1e-4 * initial);
+    }
+
+    #[test]
+    fn diffusion_conserves_mass_across_mixed_terrain() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let (_, map_positions, mut signal_field) = map();
+        for position in map_positions.iter_positions() {
+            let terrain_type = TerrainType::get_at(rng.gen_range(0..TerrainType::N_VARIANTS));
+            signal_field.set_terrain(position, terrain_type.unwrap());
+        }
+        randomize(&mut signal_field, &mut rng);
+        let mut signal_configs = configs(0.3, 0.0);
+        let mut config = *signal_configs.get(&EMITTER).unwrap();
+        config.terrain.rocky.diffusion = 0.2;
+        signal_configs.insert(EMITTER, config);
+
+        let initial = total(&signal_field);
+        for _ in 0..50 {
+            tick_signals(&mut signal_field, &signal_configs);
+        }
+
+        assert!((total(&signal_field) - initial).abs() <= 1e-3 * initial);
+    }
+
+    #[test]
+    fn layers_larger_than_a_chunk_are_simulated_in_parallel() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 60 });
+        let map_positions = MapPositions::new(&map_geometry);
+        assert!(map_positions.n_positions() > 2 * CHUNK_SIZE);
+
+        let other = Emitter::Stock(StockEmitter::Plant);
+        let mut signal_configs = configs(0.1, 0.0);
+        let other_config = *signal_configs.get(&other).unwrap();
+        signal_configs.insert(
+            other,
+            SignalConfig {
+                diffusion_factor: 0.2,
+                decay_probability: 0.0,
+                ..other_config
+            },
+        );
+
+        // Place signal on either side of each boundary between chunks
+        let mut signal_field = SignalField::new(&map_positions);
+        for boundary in [CHUNK_SIZE, 2 * CHUNK_SIZE] {
+            for i in [boundary - 1, boundary] {
+                let position = signal_field.index().position(i);
+                signal_field.increment(EMITTER, &position, 1.0);
+                signal_field.increment(other, &position, 2.0);
+            }
+        }
+
+        for _ in 0..20 {
+            tick_signals(&mut signal_field, &signal_configs);
+        }
+
+        assert!((total(&signal_field) - 4.0).abs() <= 1e-4);
+        assert!((signal_field.layer(&other).unwrap().total() - 8.0).abs() <= 1e-4);
+    }
+
+    /// Signal configs where [`EMITTER`] has the given rates, and is carried by the wind
+    fn airborne_configs(diffusion_factor: f32, advection: f32) -> SignalConfigs {
+        let mut signal_configs = configs(diffusion_factor, 0.0);
+        let mut config = *signal_configs.get(&EMITTER).unwrap();
+        config.advection = advection;
+        signal_configs.insert(EMITTER, config);
+        signal_configs
+    }
+
+    /// The center of mass of the signal from [`EMITTER`], in the plane of the map
+    fn centroid(signal_field: &SignalField) -> Vec2 {
+        let values = signal_field.layer(&EMITTER).unwrap().values();
+        let weighted: Vec2 = signal_field
+            .index()
+            .positions()
+            .iter()
+            .zip(values)
+            .map(|(position, value)| plane_position(position) * *value)
+            .sum();
+        weighted / total(signal_field)
+    }
+
+    #[test]
+    fn wind_carries_signal_downwind() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let (map_geometry, _, _) = map();
+        let center = map_geometry.center();
+
+        for _ in 0..10 {
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let wind = Vec2::new(angle.cos(), angle.sin()) * 0.1;
+
+            let (_, _, mut signal_field) = map();
+            signal_field.set_uniform_wind(wind);
+            signal_field.increment(EMITTER, &center, 1.0);
+            tick_signals(&mut signal_field, &airborne_configs(0.0, 1.0));
+
+            // The signal moves at the speed of the wind, whichever way it blows
+            let moved = centroid(&signal_field) - plane_position(&center);
+            assert!((moved - wind).length() <= 1e-5);
+            assert!((total(&signal_field) - 1.0).abs() <= 1e-6);
+        }
+    }
+
+    #[test]
+    fn wind_blowing_at_a_neighbor_only_reaches_that_neighbor() {
+        let (map_geometry, map_positions, mut signal_field) = map();
+        let center = map_geometry.center();
+        let downwind = map_positions.ring(&center, 1).next().unwrap();
+        let wind = (plane_position(&downwind) - plane_position(&center)) * 0.2;
+
+        signal_field.set_uniform_wind(wind);
+        signal_field.increment(EMITTER, &center, 1.0);
+        tick_signals(&mut signal_field, &airborne_configs(0.0, 0.5));
+
+        assert!((signal_field.get(&EMITTER, &center) - 0.9).abs() <= 1e-6);
+        assert!((signal_field.get(&EMITTER, &downwind) - 0.1).abs() <= 1e-6);
+    }
+
+    #[test]
+    fn ground_laid_signals_ignore_the_wind() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (_, _, mut calm) = map();
+        randomize(&mut calm, &mut rng);
+        let mut windy = calm.clone();
+        windy.set_uniform_wind(Vec2::new(0.3, -0.2));
+
+        let signal_configs = configs(0.1, 0.0);
+        for _ in 0..10 {
+            tick_signals(&mut calm, &signal_configs);
+            tick_signals(&mut windy, &signal_configs);
+        }
+
+        assert_eq!(
+            calm.layer(&EMITTER).unwrap().values(),
+            windy.layer(&EMITTER).unwrap().values()
+        );
+    }
+
+    #[test]
+    fn advection_conserves_mass() {
+        let mut rng = StdRng::seed_from_u64(8);
+
+        for _ in 0..10 {
+            let (_, map_positions, mut signal_field) = map();
+            for position in map_positions.iter_positions() {
+                let wind = Vec2::new(rng.gen_range(-2.0..2.0), rng.gen_range(-2.0..2.0));
+                signal_field.set_wind(position, wind);
+                let terrain_type = TerrainType::get_at(rng.gen_range(0..TerrainType::N_VARIANTS));
+                signal_field.set_terrain(position, terrain_type.unwrap());
+            }
+            randomize(&mut signal_field, &mut rng);
+            // Strong winds need several substeps
+            let signal_configs = airborne_configs(rng.gen_range(0.0..0.2), 1.0);
+
+            let initial = total(&signal_field);
+            for _ in 0..50 {
+                tick_signals(&mut signal_field, &signal_configs);
+                assert!(signal_field
+                    .layer(&EMITTER)
+                    .unwrap()
+                    .values()
+                    .iter()
+                    .all(|value| *value >= 0.0));
+            }
+
+            assert!((total(&signal_field) - initial).abs() <= 1e-3 * initial);
+        }
+    }
+
+    #[test]
+    fn timestep_is_independent_of_frame_rate() {
+        let mut fast = SignalTimestep::new(Duration::from_millis(50));
+        let mut slow = SignalTimestep::new(Duration::from_millis(50));
+
+        let fast_ticks: u32 = (0..60)
+            .map(|_| fast.advance(Duration::from_millis(1000 / 60)))
+            .sum();
+        let slow_ticks: u32 = (0..10)
+            .map(|_| slow.advance(Duration::from_millis(100)))
+            .sum();
+
+        assert_eq!(fast_ticks, 19);
+        assert_eq!(slow_ticks, 20);
+        assert_eq!(slow.ticks_this_frame(), 2);
+
+        // Long frames are capped, rather than simulating an unbounded number of ticks
+        assert_eq!(
+            slow.advance(Duration::from_secs(10)),
+            slow.max_ticks_per_frame
+        );
+    }
+}
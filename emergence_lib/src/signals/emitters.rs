@@ -1,15 +1,58 @@
 //! Data for entities which can emit a signal.
+//!
+//! Entities with both an [`Emitter`] and an [`EmissionRate`] deposit signal on their tile every tick.
 
+use crate::items::ItemId;
+use crate::signals::configs::SignalConfigs;
+use crate::signals::diffusion::SignalTimestep;
+use crate::signals::field::SignalField;
+use crate::signals::SignalInfo;
+use crate::structures::crafting::OutputInventory;
 use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
 use emergence_macros::IterableEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
 
 /// All signal emitters have an `EmitterId`, which is essentially a `u16`.
-#[derive(Component, Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[derive(
+    Component, Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Serialize, Deserialize,
+)]
 pub enum Emitter {
     /// A custom signal, designed by the player.
     Custom(u16),
     /// A stock signal, which comes pre-defined by the game.
     Stock(StockEmitter),
+    /// Emitted by structures that have this item ready to be collected.
+    Push(ItemId),
+    /// Emitted by structures that need this item delivered.
+    Pull(ItemId),
+    /// Emitted by structures that need a unit to work at them.
+    Work,
+}
+
+impl Emitter {
+    /// The instruction carried by signals from this emitter.
+    pub fn info(&self) -> SignalInfo {
+        match self {
+            Emitter::Push(item_id) => SignalInfo::Push(*item_id),
+            Emitter::Pull(item_id) => SignalInfo::Pull(*item_id),
+            Emitter::Work => SignalInfo::Work,
+            Emitter::Custom(_) | Emitter::Stock(_) => SignalInfo::Passive(*self),
+        }
+    }
+}
+
+impl Display for Emitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Emitter::Custom(id) => write!(f, "Custom {id}"),
+            Emitter::Stock(stock_emitter) => write!(f, "{stock_emitter:?}"),
+            Emitter::Push(item_id) => write!(f, "Push {item_id}"),
+            Emitter::Pull(item_id) => write!(f, "Pull {item_id}"),
+            Emitter::Work => write!(f, "Work"),
+        }
+    }
 }
 
 impl Default for Emitter {
@@ -20,7 +63,20 @@ impl Default for Emitter {
 
 use crate as emergence_lib;
 /// Enumerates stock signal emitters.
-#[derive(Debug, Default, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, IterableEnum)]
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Hash,
+    IterableEnum,
+    Serialize,
+    Deserialize,
+)]
 pub enum StockEmitter {
     /// Emitter is unspecified.
     #[default]
@@ -36,3 +92,101 @@ pub enum StockEmitter {
     /// Emitter is a plant.
     Plant,
 }
+
+/// How much signal an entity with an [`Emitter`] deposits on its tile each tick.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct EmissionRate {
+    /// The amount of signal deposited per [tick](crate::signals::diffusion::SignalTimestep), while the `condition` holds.
+    ///
+    /// Other systems may change this to modulate emission based on the entity's state.
+    pub rate: f32,
+    /// When the entity emits.
+    pub condition: EmissionCondition,
+}
+
+impl EmissionRate {
+    /// Creates an [`EmissionRate`] that always emits at the given `rate`.
+    pub fn constant(rate: f32) -> EmissionRate {
+        EmissionRate {
+            rate,
+            condition: EmissionCondition::Always,
+        }
+    }
+
+    /// The amount of signal to deposit this tick, given the entity's output inventory (if any).
+    pub fn current_rate(&self, output: Option<&OutputInventory>) -> f32 {
+        let active = match &self.condition {
+            EmissionCondition::Always => true,
+            EmissionCondition::OutputContains(item_id) => match output {
+                Some(output) => output.inventory().item_count(item_id) > 0,
+                None => false,
+            },
+        };
+
+        if active {
+            self.rate
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Conditions under which an entity emits its signal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmissionCondition {
+    /// The entity emits every tick.
+    Always,
+    /// The entity emits only while its [`OutputInventory`] holds at least one of the given item.
+    OutputContains(ItemId),
+}
+
+/// Deposits signal on the tile of every entity with an [`Emitter`] and an [`EmissionRate`],
+/// for each tick simulated this frame.
+///
+/// Emitters without a [`SignalConfig`](crate::signals::configs::SignalConfig) are skipped,
+/// as their signal could not be simulated. Deposits are limited by the
+/// [saturation](crate::signals::configs::Saturation) of each signal.
+pub(super) fn emit_signals(
+    emitter_query: Query<(&Emitter, &EmissionRate, &TilePos, Option<&OutputInventory>)>,
+    signal_configs: Res<SignalConfigs>,
+    timestep: Res<SignalTimestep>,
+    mut signal_field: ResMut<SignalField>,
+) {
+    let ticks = timestep.ticks_this_frame() as f32;
+    for (emitter, emission_rate, tile_pos, output) in emitter_query.iter() {
+        let amount = emission_rate.current_rate(output) * ticks;
+        if amount <= 0.0 {
+            continue;
+        }
+
+        if let Some(config) = signal_configs.get(emitter) {
+            signal_field.deposit(*emitter, tile_pos, amount, &config.saturation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::count::ItemCount;
+    use crate::items::inventory::Inventory;
+
+    #[test]
+    fn emission_follows_output_inventory() {
+        let emission_rate = EmissionRate {
+            rate: 0.5,
+            condition: EmissionCondition::OutputContains(ItemId::acacia_leaf()),
+        };
+        let mut output = OutputInventory(Inventory::new(1, 10));
+
+        assert_eq!(emission_rate.current_rate(None), 0.0);
+        assert_eq!(emission_rate.current_rate(Some(&output)), 0.0);
+
+        output
+            .0
+            .add_all_or_nothing_one_item(&ItemCount::one(ItemId::acacia_leaf()))
+            .unwrap();
+        assert_eq!(emission_rate.current_rate(Some(&output)), 0.5);
+        assert_eq!(EmissionRate::constant(0.25).current_rate(None), 0.25);
+    }
+}
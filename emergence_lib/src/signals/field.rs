@@ -0,0 +1,476 @@
+//! Dense storage for the value of every signal at every tile.
+//!
+//! Rather than storing a map of signals at each tile, the [`SignalField`] stores one
+//! [`SignalLayer`] per [`Emitter`], holding the value of that emitter's signal at every tile in
+//! [`MapIndex`] order. This keeps the values of a signal contiguous in memory, so that each layer
+//! can be simulated independently, and in parallel.
+//!
+//! The field also records the terrain and wind at each tile, which control how signals spread.
+
+use crate::signals::configs::{Saturation, SignalConfigs};
+use crate::signals::emitters::Emitter;
+use crate::signals::map_overlay::{AlphaCompose, RGBA_WHITE};
+use crate::signals::sensing::plane_position;
+use crate::simulation::map::hex_patch::HexPatch;
+use crate::simulation::map::index::MapIndex;
+use crate::simulation::map::MapPositions;
+use crate::terrain::TerrainType;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use indexmap::IndexMap;
+use std::sync::Arc;
+
+/// The indices of the neighbors of a tile, not including the tile itself
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Neighbors {
+    /// The number of entries of `indices` that are in use
+    count: u8,
+    /// The index of each neighbor; only the first `count` entries are meaningful
+    indices: [u32; 6],
+    /// The unit vector from the tile towards each neighbor, in the plane of the map
+    directions: [Vec2; 6],
+}
+
+impl Neighbors {
+    /// The index of each neighbor
+    #[inline]
+    pub(super) fn as_slice(&self) -> &[u32] {
+        &self.indices[..self.count as usize]
+    }
+
+    /// The unit vector from the tile towards each neighbor, in the same order as [`Neighbors::as_slice`]
+    #[inline]
+    pub(super) fn directions(&self) -> &[Vec2] {
+        &self.directions[..self.count as usize]
+    }
+}
+
+/// The terrain and wind at each tile, in [`MapIndex`] order, along with the neighbors of each tile
+pub(super) struct Topology<'a> {
+    /// The neighbors of each tile
+    pub(super) neighbors: &'a [Neighbors],
+    /// The terrain at each tile
+    pub(super) terrain: &'a [TerrainType],
+    /// The wind at each tile
+    pub(super) wind: &'a [Vec2],
+}
+
+/// The values of the signal from a single [`Emitter`] at every tile, in [`MapIndex`] order.
+///
+/// Layers are double-buffered: the simulation reads from the current values, and writes the
+/// values for the next step into a second buffer, before swapping the two.
+#[derive(Debug, Clone)]
+pub struct SignalLayer {
+    /// The current value of the signal at each tile
+    values: Vec<f32>,
+    /// Scratch space that the next step of the simulation is written into
+    next: Vec<f32>,
+}
+
+impl SignalLayer {
+    /// Creates a layer with no signal at any of `n_positions` tiles
+    fn new(n_positions: usize) -> SignalLayer {
+        SignalLayer {
+            values: vec![0.0; n_positions],
+            next: vec![0.0; n_positions],
+        }
+    }
+
+    /// The value of the signal at each tile, in [`MapIndex`] order
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    /// Mutable access to the value of the signal at each tile, in [`MapIndex`] order
+    pub fn values_mut(&mut self) -> &mut [f32] {
+        &mut self.values
+    }
+
+    /// The current values, along with the buffer that the next step should be written into
+    pub(super) fn buffers(&mut self) -> (&[f32], &mut [f32]) {
+        (&self.values, &mut self.next)
+    }
+
+    /// Makes the values written into the [next buffer](SignalLayer::buffers) current
+    pub(super) fn swap(&mut self) {
+        std::mem::swap(&mut self.values, &mut self.next);
+    }
+
+    /// The total amount of signal on the map
+    pub fn total(&self) -> f32 {
+        self.values.iter().sum()
+    }
+}
+
+/// The value of every signal at every tile on the map.
+///
+/// Signals from an emitter that has never been added to the field have a value of `0.0`
+/// everywhere.
+#[derive(Resource, Debug, Clone)]
+pub struct SignalField {
+    /// The index used to convert tile positions into offsets into each layer
+    index: Arc<MapIndex>,
+    /// The neighbors of each tile, in index order
+    neighbors: Arc<Vec<Neighbors>>,
+    /// The terrain at each tile, in index order, which controls how signals spread across it
+    terrain: Vec<TerrainType>,
+    /// The wind at each tile, in index order, which carries signals downwind
+    wind: Vec<Vec2>,
+    /// The values of the signal from each emitter, in the order the emitters were first added
+    layers: IndexMap<Emitter, SignalLayer>,
+}
+
+impl SignalField {
+    /// Creates a field with no signals, covering every position in `map_positions`
+    pub fn new(map_positions: &MapPositions) -> SignalField {
+        let index = map_positions.index().clone();
+        let neighbors = (0..index.len())
+            .map(|tile| {
+                let mut neighbors = Neighbors::default();
+                let center = plane_position(&index.position(tile));
+                // Patches include their center, which is not a neighbor
+                for neighbor in index
+                    .patch(tile)
+                    .iter()
+                    .filter(|neighbor| **neighbor != tile)
+                {
+                    let direction = plane_position(&index.position(*neighbor)) - center;
+                    neighbors.indices[neighbors.count as usize] = *neighbor as u32;
+                    neighbors.directions[neighbors.count as usize] = direction.normalize();
+                    neighbors.count += 1;
+                }
+                neighbors
+            })
+            .collect();
+
+        SignalField {
+            terrain: vec![TerrainType::Plain; index.len()],
+            wind: vec![Vec2::ZERO; index.len()],
+            index,
+            neighbors: Arc::new(neighbors),
+            layers: IndexMap::new(),
+        }
+    }
+
+    /// The [`MapIndex`] that determines the order of the values in each [`SignalLayer`]
+    pub fn index(&self) -> &Arc<MapIndex> {
+        &self.index
+    }
+
+    /// The neighbors, terrain and wind of each tile, along with mutable access to every layer
+    ///
+    /// Useful when updating each tile requires access to the values at its neighbors.
+    pub(super) fn topology_and_layers_mut(
+        &mut self,
+    ) -> (
+        Topology<'_>,
+        impl Iterator<Item = (&Emitter, &mut SignalLayer)>,
+    ) {
+        let topology = Topology {
+            neighbors: &self.neighbors,
+            terrain: &self.terrain,
+            wind: &self.wind,
+        };
+        (topology, self.layers.iter_mut())
+    }
+
+    /// The terrain at `position`, as last recorded by [`set_terrain`](SignalField::set_terrain)
+    ///
+    /// Every tile starts out as [`TerrainType::Plain`].
+    pub fn terrain(&self, position: &TilePos) -> Option<TerrainType> {
+        Some(self.terrain[self.index.get(position)?])
+    }
+
+    /// Records the terrain at `position`, which controls how signals spread across it.
+    ///
+    /// Positions that are not on the map are ignored.
+    pub fn set_terrain(&mut self, position: &TilePos, terrain_type: TerrainType) {
+        if let Some(i) = self.index.get(position) {
+            self.terrain[i] = terrain_type;
+        }
+    }
+
+    /// The wind at `position`, as last recorded by [`set_wind`](SignalField::set_wind)
+    ///
+    /// Wind is measured in tiles per [tick](crate::signals::diffusion::SignalTimestep), in the
+    /// plane of the map. Every tile starts out with no wind.
+    pub fn wind(&self, position: &TilePos) -> Option<Vec2> {
+        Some(self.wind[self.index.get(position)?])
+    }
+
+    /// Records the wind at `position`, which carries signals downwind.
+    ///
+    /// How strongly each signal is carried is controlled by its
+    /// [`advection`](crate::signals::configs::SignalConfig::advection).
+    /// Positions that are not on the map are ignored.
+    pub fn set_wind(&mut self, position: &TilePos, wind: Vec2) {
+        if let Some(i) = self.index.get(position) {
+            self.wind[i] = wind;
+        }
+    }
+
+    /// Sets the wind at every tile on the map to the same value.
+    pub fn set_uniform_wind(&mut self, wind: Vec2) {
+        self.wind.fill(wind);
+    }
+
+    /// The value of the signal from `emitter` at `position`.
+    ///
+    /// Returns `0.0` for positions that are not on the map.
+    pub fn get(&self, emitter: &Emitter, position: &TilePos) -> f32 {
+        match (self.layers.get(emitter), self.index.get(position)) {
+            (Some(layer), Some(i)) => layer.values[i],
+            _ => 0.0,
+        }
+    }
+
+    /// Sets the value of the signal from `emitter` at `position`.
+    ///
+    /// Positions that are not on the map are ignored.
+    pub fn set(&mut self, emitter: Emitter, position: &TilePos, value: f32) {
+        if let Some(i) = self.index.get(position) {
+            self.layer_mut(emitter).values[i] = value;
+        }
+    }
+
+    /// Changes the value of the signal from `emitter` at `position` by `increment`.
+    ///
+    /// Positions that are not on the map are ignored.
+    pub fn increment(&mut self, emitter: Emitter, position: &TilePos, increment: f32) {
+        if let Some(i) = self.index.get(position) {
+            self.layer_mut(emitter).values[i] += increment;
+        }
+    }
+
+    /// Deposits `amount` of the signal from `emitter` at `position`, limited by its `saturation`.
+    ///
+    /// Positions that are not on the map are ignored.
+    pub fn deposit(
+        &mut self,
+        emitter: Emitter,
+        position: &TilePos,
+        amount: f32,
+        saturation: &Saturation,
+    ) {
+        if let Some(i) = self.index.get(position) {
+            let value = &mut self.layer_mut(emitter).values[i];
+            *value = saturation.deposit(*value, amount);
+        }
+    }
+
+    /// Removes signals weaker than `epsilon`, setting them to `0.0`.
+    ///
+    /// Layers with no signal left are removed from the field entirely, so that the cost of
+    /// simulating signals depends on the signals that are present, rather than every emitter that
+    /// has ever been added. The remaining layers keep their order.
+    pub fn prune(&mut self, epsilon: f32) {
+        self.layers.retain(|_, layer| {
+            let mut is_empty = true;
+            for value in layer.values.iter_mut() {
+                if *value < epsilon {
+                    *value = 0.0;
+                } else if *value != 0.0 {
+                    is_empty = false;
+                }
+            }
+            !is_empty
+        });
+    }
+
+    /// The values of the signal from `emitter`, if it has ever been added to the field
+    pub fn layer(&self, emitter: &Emitter) -> Option<&SignalLayer> {
+        self.layers.get(emitter)
+    }
+
+    /// The values of the signal from `emitter`, adding an empty layer if there is none yet
+    pub fn layer_mut(&mut self, emitter: Emitter) -> &mut SignalLayer {
+        let n_positions = self.index.len();
+        self.layers
+            .entry(emitter)
+            .or_insert_with(|| SignalLayer::new(n_positions))
+    }
+
+    /// Iterate over each emitter that has been added to the field, along with its layer
+    pub fn layers(&self) -> impl Iterator<Item = (&Emitter, &SignalLayer)> {
+        self.layers.iter()
+    }
+
+    /// Iterate mutably over each emitter that has been added to the field, along with its layer
+    pub fn layers_mut(&mut self) -> impl Iterator<Item = (&Emitter, &mut SignalLayer)> {
+        self.layers.iter_mut()
+    }
+
+    /// The emitters whose signal is present at `position`, along with the value of their signal
+    pub fn tile_values(&self, position: &TilePos) -> Vec<(Emitter, f32)> {
+        let i = match self.index.get(position) {
+            Some(i) => i,
+            None => return Vec::new(),
+        };
+
+        self.layers
+            .iter()
+            .map(|(emitter, layer)| (*emitter, layer.values[i]))
+            .filter(|(_, value)| *value != 0.0)
+            .collect()
+    }
+
+    /// Get a [`HexPatch`] of the values of the signal from `emitter` surrounding `position`
+    pub fn get_patch(&self, emitter: &Emitter, position: &TilePos) -> Option<HexPatch<f32>> {
+        let center = self.index.get(position)?;
+        let layer = self.layers.get(emitter);
+        Some(self.index.patch(center).map_ref(|neighbor| match layer {
+            Some(layer) => layer.values[*neighbor],
+            None => 0.0,
+        }))
+    }
+
+    /// Compute color by combining (using the over operator) the color for each emitter at
+    /// `position`, in order, using the [`over`](AlphaCompose::over) to the baseline [`RGBA_WHITE`].
+    ///
+    /// The order of the emitters is governed by the order they were registered into the given
+    /// [`SignalConfigs`].
+    pub fn compute_combined_color(
+        &self,
+        position: &TilePos,
+        signal_configs: &SignalConfigs,
+    ) -> Color {
+        let i = match self.index.get(position) {
+            Some(i) => i,
+            None => return RGBA_WHITE,
+        };
+
+        let mut total_color = RGBA_WHITE;
+        for (emitter, config) in signal_configs.iter() {
+            if !config.color_config.is_visible {
+                continue;
+            }
+            if let Some(layer) = self.layers.get(emitter) {
+                let color = config.color_config.compute_color(layer.values[i]);
+                total_color = color.over(&total_color);
+            }
+        }
+
+        total_color
+    }
+}
+
+/// Fixtures shared by the tests of the signal modules
+#[cfg(test)]
+pub(super) mod test_utils {
+    use super::SignalField;
+    use crate::signals::emitters::{Emitter, StockEmitter};
+    use crate::simulation::map::{MapGeometry, MapPositions, MapShape};
+
+    /// The emitter used in signal tests
+    pub(in crate::signals) const EMITTER: Emitter = Emitter::Stock(StockEmitter::Ant);
+
+    /// A small map with no signals on it
+    pub(in crate::signals) fn map() -> (MapGeometry, MapPositions, SignalField) {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 3 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let signal_field = SignalField::new(&map_positions);
+        (map_geometry, map_positions, signal_field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::{map, EMITTER};
+    use super::*;
+    use crate::signals::emitters::StockEmitter;
+    use crate::simulation::map::hex_patch::HexPatchLocation;
+
+    #[test]
+    fn values_are_stored_per_emitter_and_position() {
+        let (map_geometry, _, mut field) = map();
+        let center = map_geometry.center();
+        let plant = Emitter::Stock(StockEmitter::Plant);
+
+        field.increment(EMITTER, &center, 0.5);
+        field.increment(EMITTER, &center, 0.25);
+        field.set(plant, &center, 2.0);
+        field.increment(EMITTER, &TilePos { x: 1000, y: 0 }, 1.0);
+
+        assert_eq!(field.get(&EMITTER, &center), 0.75);
+        assert_eq!(field.get(&plant, &center), 2.0);
+        assert_eq!(
+            field.get(&Emitter::Stock(StockEmitter::Fungus), &center),
+            0.0
+        );
+        assert_eq!(
+            field.tile_values(&center),
+            vec![(EMITTER, 0.75), (plant, 2.0)]
+        );
+        assert_eq!(field.layer(&EMITTER).unwrap().total(), 0.75);
+
+        let patch = field.get_patch(&EMITTER, &center).unwrap();
+        assert_eq!(patch.get(HexPatchLocation::Center), Some(&0.75));
+        assert_eq!(patch.get(HexPatchLocation::North), Some(&0.0));
+    }
+
+    #[test]
+    fn faint_signals_are_pruned() {
+        let (map_geometry, map_positions, mut field) = map();
+        let center = map_geometry.center();
+        let neighbor = map_positions.ring(&center, 1).next().unwrap();
+        let plant = Emitter::Stock(StockEmitter::Plant);
+        let fungus = Emitter::Stock(StockEmitter::Fungus);
+
+        field.set(EMITTER, &center, 1e-30);
+        field.set(plant, &center, 1e-30);
+        field.set(plant, &neighbor, 0.5);
+        field.set(fungus, &center, 0.25);
+        field.prune(1e-6);
+
+        // Empty layers are removed, and the rest keep their order
+        assert!(field.layer(&EMITTER).is_none());
+        let emitters: Vec<Emitter> = field.layers().map(|(emitter, _)| *emitter).collect();
+        assert_eq!(emitters, vec![plant, fungus]);
+        assert_eq!(field.get(&plant, &center), 0.0);
+        assert_eq!(field.get(&plant, &neighbor), 0.5);
+        assert_eq!(field.tile_values(&center), vec![(fungus, 0.25)]);
+    }
+
+    #[test]
+    fn deposits_saturate() {
+        let (map_geometry, _, mut field) = map();
+        let center = map_geometry.center();
+
+        let hard = Saturation::Hard { max: 1.0 };
+        for _ in 0..10 {
+            field.deposit(EMITTER, &center, 0.3, &hard);
+        }
+        assert_eq!(field.get(&EMITTER, &center), 1.0);
+
+        // Logistic deposits slow down as they approach the capacity, but never reach it
+        let logistic = Saturation::Logistic { capacity: 2.0 };
+        let mut previous = 0.0;
+        let mut previous_step = f32::INFINITY;
+        field.set(EMITTER, &center, 0.0);
+        for _ in 0..20 {
+            field.deposit(EMITTER, &center, 0.5, &logistic);
+            let value = field.get(&EMITTER, &center);
+            assert!(value - previous < previous_step);
+            assert!(value < 2.0);
+            previous_step = value - previous;
+            previous = value;
+        }
+        assert!(previous > 1.9);
+
+        // Removing signal is never limited
+        field.deposit(EMITTER, &center, -0.5, &hard);
+        assert!((field.get(&EMITTER, &center) - (previous - 0.5)).abs() <= 1e-6);
+    }
+
+    #[test]
+    fn neighbors_do_not_include_the_tile_itself() {
+        let (map_geometry, _, field) = map();
+        let center = field.index().get(&map_geometry.center()).unwrap();
+
+        assert_eq!(field.neighbors[center].as_slice().len(), 6);
+        for (tile, neighbors) in field.neighbors.iter().enumerate() {
+            assert!(!neighbors.as_slice().contains(&(tile as u32)));
+            assert!((3..=6).contains(&neighbors.as_slice().len()));
+        }
+    }
+}
@@ -1,19 +1,24 @@
 //! Plugin for displaying signals as coloured overlays on the game map.
+//!
+//! The [`SignalOverlay`] resource controls which signals are shown, and how their values are
+//! turned into colours. Only terrain tiles are tinted, so that units and organisms stay readable.
 
-use crate::curves::linear_combination;
+use crate::curves::{linear_combination, Mapping, Sigmoid};
 use crate::signals::configs::SignalConfigs;
-use crate::signals::tile_signals::TileSignals;
-use crate::simulation::map::resources::MapResource;
+use crate::signals::emitters::Emitter;
+use crate::signals::field::SignalField;
+use crate::terrain::TerrainType;
 use bevy::prelude::*;
 use bevy_ecs_tilemap::tiles::{TileColor, TilePos};
 
-/// Colours tiles based on the signals present. Signal colours are defined in their
-/// [`SignalConfig`](crate::signals::configs::SignalConfig).
+/// Colours terrain tiles based on the signals present. Which signals are shown is controlled
+/// by the [`SignalOverlay`] resource.
 pub struct MapOverlayPlugin;
 
 impl Plugin for MapOverlayPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_to_stage(CoreStage::Last, color_tiles);
+        app.init_resource::<SignalOverlay>()
+            .add_system_to_stage(CoreStage::Last, color_tiles);
     }
 }
 
@@ -21,18 +26,307 @@ impl Plugin for MapOverlayPlugin {
 /// RGBA variant
 pub const RGBA_WHITE: Color = Color::rgba(1.0, 1.0, 1.0, 1.0);
 
-/// Color tiles based on the signals present.
+/// The colours of the heatmap used to show a single emitter, from no signal to the strongest.
+///
+/// Low intensities are close to white, so that empty tiles are left untinted.
+const HEATMAP_STOPS: [[f32; 3]; 4] = [
+    [1.0, 1.0, 1.0],
+    [1.0, 0.9, 0.2],
+    [0.95, 0.4, 0.1],
+    [0.5, 0.0, 0.1],
+];
+
+/// Which signals are shown on the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlaySelection {
+    /// No signals are shown.
+    Hidden,
+    /// Every [visible](crate::signals::configs::SignalColorConfig::is_visible) signal is shown,
+    /// each coloured according to its own [`SignalColorConfig`](crate::signals::configs::SignalColorConfig).
+    #[default]
+    AllVisible,
+    /// The emitters in the [`blend`](SignalOverlay::blend) set are shown together, each in its own
+    /// colour, with an opacity set by the [`HeatmapPalette`].
+    Blended,
+    /// A single emitter is shown as a heatmap.
+    Single(Emitter),
+}
+
+/// How the value of a signal is mapped to an intensity between `0.0` and `1.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeatmapPalette {
+    /// Intensity grows linearly between the low and high values.
+    #[default]
+    Linear,
+    /// Intensity grows with the logarithm of the value, which shows faint signals more clearly.
+    Logarithmic,
+    /// Intensity follows a sigmoid that is close to `0.0` at the low value, and close to `1.0` at
+    /// the high value.
+    Sigmoid,
+}
+
+impl HeatmapPalette {
+    /// The palette that follows this one when cycling through them
+    pub fn next(&self) -> HeatmapPalette {
+        match self {
+            HeatmapPalette::Linear => HeatmapPalette::Logarithmic,
+            HeatmapPalette::Logarithmic => HeatmapPalette::Sigmoid,
+            HeatmapPalette::Sigmoid => HeatmapPalette::Linear,
+        }
+    }
+
+    /// The intensity of a signal with the given `value`, where `low` and `high` bound the range of
+    /// interesting values.
+    ///
+    /// `low` must be positive for the [`Logarithmic`](HeatmapPalette::Logarithmic) palette.
+    pub fn intensity(&self, value: f32, low: f32, high: f32) -> f32 {
+        let intensity = match self {
+            HeatmapPalette::Linear => (value - low) / (high - low),
+            HeatmapPalette::Logarithmic => {
+                if value <= low {
+                    0.0
+                } else {
+                    (value / low).ln() / (high / low).ln()
+                }
+            }
+            HeatmapPalette::Sigmoid => Sigmoid::new(0.0, 1.0, low, high).map(value),
+        };
+
+        if intensity.is_nan() {
+            0.0
+        } else {
+            intensity.clamp(0.0, 1.0)
+        }
+    }
+
+    /// The value found a fraction `t` of the way from `low` to `high`, spaced to suit this palette
+    fn value_at(&self, t: f32, low: f32, high: f32) -> f32 {
+        match self {
+            HeatmapPalette::Linear | HeatmapPalette::Sigmoid => low + t * (high - low),
+            HeatmapPalette::Logarithmic => low * (high / low).powf(t),
+        }
+    }
+}
+
+/// The colour of the single-emitter heatmap at the given `intensity`, between `0.0` and `1.0`
+pub fn heatmap_color(intensity: f32) -> Color {
+    let scaled = intensity.clamp(0.0, 1.0) * (HEATMAP_STOPS.len() - 1) as f32;
+    let lower = (scaled.floor() as usize).min(HEATMAP_STOPS.len() - 2);
+    let t = scaled - lower as f32;
+    let [r0, g0, b0] = HEATMAP_STOPS[lower];
+    let [r1, g1, b1] = HEATMAP_STOPS[lower + 1];
+
+    Color::rgba(
+        r0 + t * (r1 - r0),
+        g0 + t * (g1 - g0),
+        b0 + t * (b1 - b0),
+        1.0,
+    )
+}
+
+/// A single entry of an [`OverlayLegend`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegendEntry {
+    /// What this entry represents: a signal value, or the name of an emitter.
+    pub label: String,
+    /// The colour used on the map.
+    pub color: Color,
+}
+
+/// Describes the colours currently used by the overlay, so that they can be shown on screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayLegend {
+    /// A summary of what is being shown.
+    pub title: String,
+    /// The colours used, and what they mean.
+    pub entries: Vec<LegendEntry>,
+}
+
+/// Controls which signals are drawn over the terrain, and how.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct SignalOverlay {
+    /// Which signals are shown.
+    pub selection: OverlaySelection,
+    /// The emitters shown when the selection is [`OverlaySelection::Blended`], in the order
+    /// their colours are layered.
+    pub blend: Vec<Emitter>,
+    /// How signal values are turned into intensities.
+    pub palette: HeatmapPalette,
+    /// The value at which signals start to be shown.
+    ///
+    /// Must be positive for the [`Logarithmic`](HeatmapPalette::Logarithmic) palette.
+    pub low: f32,
+    /// The value at which signals are shown at full intensity.
+    pub high: f32,
+}
+
+impl Default for SignalOverlay {
+    fn default() -> Self {
+        SignalOverlay {
+            selection: OverlaySelection::default(),
+            blend: Vec::new(),
+            palette: HeatmapPalette::default(),
+            low: 0.01,
+            high: 1.0,
+        }
+    }
+}
+
+impl SignalOverlay {
+    /// The intensity of a signal with the given `value`, using the current palette
+    pub fn intensity(&self, value: f32) -> f32 {
+        self.palette.intensity(value, self.low, self.high)
+    }
+
+    /// The colour to tint the tile at `position`.
+    pub fn color(
+        &self,
+        position: &TilePos,
+        signal_field: &SignalField,
+        signal_configs: &SignalConfigs,
+    ) -> Color {
+        match &self.selection {
+            OverlaySelection::Hidden => RGBA_WHITE,
+            OverlaySelection::AllVisible => {
+                signal_field.compute_combined_color(position, signal_configs)
+            }
+            OverlaySelection::Blended => {
+                let mut total_color = RGBA_WHITE;
+                for emitter in &self.blend {
+                    if let Some(config) = signal_configs.get(emitter) {
+                        let [red, green, blue] = config.color_config.rgb_color;
+                        let alpha = self.intensity(signal_field.get(emitter, position));
+                        total_color = Color::rgba(red, green, blue, alpha).over(&total_color);
+                    }
+                }
+                total_color
+            }
+            OverlaySelection::Single(emitter) => {
+                heatmap_color(self.intensity(signal_field.get(emitter, position)))
+            }
+        }
+    }
+
+    /// Shows the next (or previous, if `forward` is `false`) option when cycling through emitters.
+    ///
+    /// Every visible signal is shown first, then the blended set (if it is not empty), followed
+    /// by each configured emitter on its own, in the order they were configured.
+    /// If the overlay is hidden, it is shown again, starting with every visible signal.
+    pub fn cycle(&mut self, signal_configs: &SignalConfigs, forward: bool) {
+        let mut options = vec![OverlaySelection::AllVisible];
+        if !self.blend.is_empty() {
+            options.push(OverlaySelection::Blended);
+        }
+        options.extend(
+            signal_configs
+                .iter()
+                .map(|(emitter, _)| OverlaySelection::Single(*emitter)),
+        );
+
+        let current = match options.iter().position(|option| *option == self.selection) {
+            Some(current) => current,
+            None => {
+                self.selection = OverlaySelection::AllVisible;
+                return;
+            }
+        };
+        let step = if forward { 1 } else { options.len() - 1 };
+        self.selection = options[(current + step) % options.len()];
+    }
+
+    /// Adds the emitter currently shown on its own to the blended set, or removes it if it is
+    /// already there.
+    ///
+    /// Does nothing unless a single emitter is selected.
+    pub fn toggle_blended(&mut self) {
+        if let OverlaySelection::Single(emitter) = self.selection {
+            match self.blend.iter().position(|blended| *blended == emitter) {
+                Some(i) => {
+                    self.blend.remove(i);
+                }
+                None => self.blend.push(emitter),
+            }
+        }
+    }
+
+    /// Hides the overlay, or shows every visible signal if it is already hidden.
+    pub fn toggle_hidden(&mut self) {
+        self.selection = match self.selection {
+            OverlaySelection::Hidden => OverlaySelection::AllVisible,
+            _ => OverlaySelection::Hidden,
+        };
+    }
+
+    /// Describes the colours currently shown on the map.
+    ///
+    /// Heatmaps are described by `n_steps` (at least two) evenly spaced values between
+    /// [`low`](SignalOverlay::low) and [`high`](SignalOverlay::high); other selections list the
+    /// colour of each emitter shown.
+    pub fn legend(&self, signal_configs: &SignalConfigs, n_steps: usize) -> OverlayLegend {
+        let emitter_entries = |emitters: &mut dyn Iterator<Item = &Emitter>| {
+            emitters
+                .filter_map(|emitter| {
+                    signal_configs.get(emitter).map(|config| LegendEntry {
+                        label: signal_configs.label(emitter),
+                        color: Color::from(config.color_config.rgb_color),
+                    })
+                })
+                .collect()
+        };
+
+        match &self.selection {
+            OverlaySelection::Hidden => OverlayLegend {
+                title: "Signals hidden".to_string(),
+                entries: Vec::new(),
+            },
+            OverlaySelection::AllVisible => OverlayLegend {
+                title: "All visible signals".to_string(),
+                entries: emitter_entries(
+                    &mut signal_configs
+                        .iter()
+                        .filter(|(_, config)| config.color_config.is_visible)
+                        .map(|(emitter, _)| emitter),
+                ),
+            },
+            OverlaySelection::Blended => OverlayLegend {
+                title: format!("Blended signals ({:?})", self.palette),
+                entries: emitter_entries(&mut self.blend.iter()),
+            },
+            OverlaySelection::Single(emitter) => {
+                let n_steps = n_steps.max(2);
+                let entries = (0..n_steps)
+                    .map(|step| {
+                        let t = step as f32 / (n_steps - 1) as f32;
+                        let value = self.palette.value_at(t, self.low, self.high);
+                        LegendEntry {
+                            label: format!("{value:.3}"),
+                            color: heatmap_color(self.intensity(value)),
+                        }
+                    })
+                    .collect();
+
+                OverlayLegend {
+                    title: format!("{} ({:?})", signal_configs.label(emitter), self.palette),
+                    entries,
+                }
+            }
+        }
+    }
+}
+
+/// Color terrain tiles based on the signals selected by the [`SignalOverlay`].
 fn color_tiles(
     mut commands: Commands,
-    terrain_tile_query: Query<(Entity, &TilePos)>,
-    map_signals: Res<MapResource<TileSignals>>,
+    terrain_tile_query: Query<(Entity, &TilePos), With<TerrainType>>,
+    signal_field: Res<SignalField>,
     signal_configs: Res<SignalConfigs>,
+    signal_overlay: Res<SignalOverlay>,
 ) {
     let tile_colors: Vec<(Entity, TileColor)> = terrain_tile_query
         .iter()
         .map(|(entity, position)| {
-            let tile_signals = map_signals.get(position).unwrap();
-            let tile_color = TileColor(tile_signals.read().compute_combined_color(&signal_configs));
+            let tile_color =
+                TileColor(signal_overlay.color(position, &signal_field, &signal_configs));
             (entity, tile_color)
         })
         .collect();
@@ -81,3 +375,95 @@ impl AlphaCompose for Color {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signals::emitters::StockEmitter;
+    use crate::simulation::map::{MapGeometry, MapPositions, MapShape};
+
+    #[test]
+    fn palettes_span_the_range() {
+        for palette in [
+            HeatmapPalette::Linear,
+            HeatmapPalette::Logarithmic,
+            HeatmapPalette::Sigmoid,
+        ] {
+            assert!(palette.intensity(0.0, 0.01, 1.0) <= 0.01);
+            assert!(palette.intensity(10.0, 0.01, 1.0) >= 0.99);
+            assert!(palette.intensity(0.1, 0.01, 1.0) < palette.intensity(0.5, 0.01, 1.0));
+        }
+
+        // Logarithmic palettes place each order of magnitude evenly
+        let log = HeatmapPalette::Logarithmic;
+        assert!((log.intensity(0.1, 0.01, 1.0) - 0.5).abs() <= 1e-6);
+        assert!((log.value_at(0.5, 0.01, 1.0) - 0.1).abs() <= 1e-6);
+    }
+
+    #[test]
+    fn heatmap_is_white_without_signal() {
+        assert_eq!(heatmap_color(0.0), RGBA_WHITE);
+        assert_eq!(heatmap_color(1.0), Color::rgba(0.5, 0.0, 0.1, 1.0));
+    }
+
+    #[test]
+    fn single_emitters_ignore_other_signals() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 1 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let center = map_geometry.center();
+        let signal_configs = SignalConfigs::default();
+        let ant = Emitter::Stock(StockEmitter::Ant);
+        let mut signal_field = SignalField::new(&map_positions);
+        signal_field.set(Emitter::Stock(StockEmitter::Plant), &center, 1.0);
+
+        let mut overlay = SignalOverlay {
+            selection: OverlaySelection::Single(ant),
+            ..default()
+        };
+        assert_eq!(
+            overlay.color(&center, &signal_field, &signal_configs),
+            RGBA_WHITE
+        );
+
+        signal_field.set(ant, &center, 1.0);
+        assert_eq!(
+            overlay.color(&center, &signal_field, &signal_configs),
+            heatmap_color(1.0)
+        );
+
+        overlay.toggle_hidden();
+        assert_eq!(
+            overlay.color(&center, &signal_field, &signal_configs),
+            RGBA_WHITE
+        );
+    }
+
+    #[test]
+    fn cycling_visits_every_emitter() {
+        let signal_configs = SignalConfigs::default();
+        let n_emitters = signal_configs.iter().count();
+        let mut overlay = SignalOverlay::default();
+
+        overlay.cycle(&signal_configs, true);
+        let first = overlay.selection;
+        assert_eq!(
+            first,
+            OverlaySelection::Single(*signal_configs.iter().next().unwrap().0)
+        );
+
+        // Blending the current emitter adds the blended set to the cycle
+        overlay.toggle_blended();
+        assert_eq!(overlay.blend.len(), 1);
+        overlay.cycle(&signal_configs, false);
+        assert_eq!(overlay.selection, OverlaySelection::Blended);
+        assert_eq!(overlay.legend(&signal_configs, 5).entries.len(), 1);
+
+        for _ in 0..n_emitters + 2 {
+            overlay.cycle(&signal_configs, true);
+        }
+        assert_eq!(overlay.selection, OverlaySelection::Blended);
+        overlay.cycle(&signal_configs, true);
+        assert_eq!(overlay.selection, first);
+        assert_eq!(overlay.legend(&signal_configs, 5).entries.len(), 5);
+    }
+}
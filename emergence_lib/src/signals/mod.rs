@@ -1,54 +1,113 @@
 //! Models signals emitted by the hive mind, or units of the hive.
 //!
 //! Signals diffuse, can be convected, and so on.
+pub mod config_file;
 pub mod configs;
+pub mod diffusion;
 pub mod emitters;
+pub mod field;
 pub mod map_overlay;
-pub mod tile_signals;
-use crate::curves::Mapping;
-use crate::enum_iter::IterableEnum;
-use crate::signals::configs::{SignalColorConfig, SignalConfig, SignalConfigs};
-use crate::signals::emitters::Emitter;
+pub mod overlay_controls;
+pub mod recording;
+pub mod registry;
+pub mod sensing;
+pub mod thresholds;
+use crate::items::ItemId;
+use crate::signals::configs::{SignalConfig, SignalConfigs};
+use crate::signals::diffusion::{tick_signals, SignalPruning, SignalTimestep};
+use crate::signals::emitters::{emit_signals, Emitter};
+use crate::signals::field::SignalField;
 use crate::signals::map_overlay::MapOverlayPlugin;
-use crate::signals::tile_signals::TileSignals;
-use crate::simulation::map::hex_patch::HexPatchLocation;
-use crate::simulation::map::resources::MapResource;
+use crate::signals::recording::{export_signal_recordings, ExportSignalRecording, SignalRecorder};
+use crate::signals::thresholds::{fire_threshold_events, SignalThresholdCrossed, SignalThresholds};
 use crate::simulation::map::MapPositions;
+use crate::structures::crafting::CraftingSystem;
+use crate::terrain::TerrainType;
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::TilePos;
 
+/// System labels for signals
+#[derive(SystemLabel)]
+pub enum SignalSystem {
+    /// Deposit signal from emitting entities
+    Emit,
+    /// Simulate the decay and diffusion of every signal
+    Simulate,
+}
+
 /// This plugin manages all aspects of signals:
-/// * creation,
+/// * creation, both on request and by [emitting](emitters::EmissionRate) entities,
 /// * diffusion, advection, reaction
+/// * [saturation](configs::Saturation) and [pruning](diffusion::SignalPruning) of signals
+/// * firing events when signals cross [thresholds](thresholds::ThresholdWatcher)
+/// * [recording](recording::SignalRecorder) signal statistics over time
 /// * presenting map overlays
 pub struct SignalsPlugin;
 
 impl Plugin for SignalsPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SignalConfigs>()
+            .init_resource::<SignalTimestep>()
+            .init_resource::<SignalPruning>()
+            .init_resource::<SignalThresholds>()
+            .init_resource::<SignalRecorder>()
             .add_event::<SignalModificationEvent>()
+            .add_event::<SignalThresholdCrossed>()
+            .add_event::<ExportSignalRecording>()
             .add_plugin(MapOverlayPlugin)
             .add_startup_system_to_stage(StartupStage::PostStartup, initialize_map_signals)
+            .add_system_to_stage(CoreStage::PreUpdate, advance_signal_timestep)
             .add_system(handle_signal_modification_events)
-            .add_system(decay.after(handle_signal_modification_events))
-            .add_system(compute_deltas.after(decay))
-            .add_system(apply_deltas.after(compute_deltas));
+            .add_system(update_signal_terrain.before(SignalSystem::Simulate))
+            .add_system(
+                emit_signals
+                    .label(SignalSystem::Emit)
+                    .after(handle_signal_modification_events)
+                    // Emission can depend on the output of crafting
+                    .after(CraftingSystem::StartAndFinish),
+            )
+            .add_system(
+                simulate_signals
+                    .label(SignalSystem::Simulate)
+                    .after(SignalSystem::Emit),
+            )
+            .add_system(fire_threshold_events.after(SignalSystem::Simulate))
+            .add_system_to_stage(CoreStage::Last, export_signal_recordings);
     }
 }
 
-/// Initialize [`TileSignals`] for each position.
+/// Initialize the [`SignalField`], covering every position.
 ///
 /// This is a startup system that should run after terrain generation, i.e. in
 /// [`StartupStage::PostStartup`]. It will panic if it cannot find the [`MapPositions`] resource.
 fn initialize_map_signals(mut commands: Commands, map_positions: Res<MapPositions>) {
-    commands.insert_resource(MapResource::<TileSignals>::default_from_template(
-        &map_positions,
-    ))
+    commands.insert_resource(SignalField::new(&map_positions))
+}
+
+/// Keeps the terrain recorded in the [`SignalField`] up to date, so that signals spread according
+/// to the terrain they cross.
+fn update_signal_terrain(
+    changed_terrain: Query<(&TilePos, &TerrainType), Changed<TerrainType>>,
+    all_terrain: Query<(&TilePos, &TerrainType)>,
+    mut signal_field: ResMut<SignalField>,
+) {
+    // A new field, such as one that was just loaded, knows nothing about the terrain
+    if signal_field.is_added() {
+        for (position, terrain_type) in all_terrain.iter() {
+            signal_field.set_terrain(position, *terrain_type);
+        }
+    } else {
+        for (position, terrain_type) in changed_terrain.iter() {
+            signal_field.set_terrain(position, *terrain_type);
+        }
+    }
 }
 
 /// Event modifying a signal at a tile.
 pub enum SignalModificationEvent {
     /// Increment/decrement a signal by requested amount.
+    ///
+    /// Increments are limited by the [saturation](configs::Saturation) of the emitter's signal.
     SignalIncrement {
         /// Emitter id of the signal.
         emitter: Emitter,
@@ -58,13 +117,17 @@ pub enum SignalModificationEvent {
         increment: f32,
     },
     /// Create a signal at a tile, initialized with the given settings.
+    ///
+    /// The configuration is only used if the emitter does not have one yet: existing
+    /// configurations are never replaced. Custom emitters must be
+    /// [registered](SignalConfigs::register_custom_emitter) first.
     SignalCreate {
         /// Emitter id of the signal.
         emitter: Emitter,
         /// Tile position of the signal.
         pos: TilePos,
         /// Initial signal value.
-        initial: Signal,
+        initial: f32,
         /// Configuration of the signal.
         config: SignalConfig,
     },
@@ -76,7 +139,7 @@ pub struct SignalIncrementEvent {}
 /// Reads [`SignalIncrementEvent`]s to create new signals on the map.
 fn handle_signal_modification_events(
     mut modification_events: EventReader<SignalModificationEvent>,
-    map_signals: Res<MapResource<TileSignals>>,
+    mut signal_field: ResMut<SignalField>,
     mut signal_configs: ResMut<SignalConfigs>,
 ) {
     for creation_event in modification_events.iter() {
@@ -86,11 +149,11 @@ fn handle_signal_modification_events(
                 pos,
                 increment,
             } => {
-                map_signals
-                    .get(pos)
-                    .unwrap()
-                    .get_mut()
-                    .increment(emitter, *increment);
+                let saturation = signal_configs
+                    .get(emitter)
+                    .map(|config| config.saturation)
+                    .unwrap_or_default();
+                signal_field.deposit(*emitter, pos, *increment, &saturation);
             }
             SignalModificationEvent::SignalCreate {
                 emitter,
@@ -98,140 +161,82 @@ fn handle_signal_modification_events(
                 initial,
                 config,
             } => {
-                signal_configs.insert(*emitter, *config);
-                map_signals
-                    .get(pos)
-                    .unwrap()
-                    .get_mut()
-                    .insert(*emitter, *initial);
+                match signal_configs.get(emitter) {
+                    Some(existing) => {
+                        if existing != config {
+                            warn!(
+                                "Ignoring new configuration for {}, which is already configured",
+                                signal_configs.label(emitter)
+                            );
+                        }
+                    }
+                    None => {
+                        if let Emitter::Custom(_) = emitter {
+                            warn!(
+                                "Cannot create signal from unregistered custom emitter {emitter}"
+                            );
+                            continue;
+                        }
+                        signal_configs.insert(*emitter, *config);
+                    }
+                }
+                signal_field.set(*emitter, pos, *initial);
             }
         }
     }
 }
 
-/// System that decays signals at all positions, at their configured per-tick decay probability
-fn decay(mut map_signals: ResMut<MapResource<TileSignals>>, signal_configs: Res<SignalConfigs>) {
-    for tile_signals in map_signals.values_mut() {
-        tile_signals.get_mut().decay(&signal_configs);
-    }
+/// Works out how many signal ticks should be simulated this frame.
+fn advance_signal_timestep(time: Res<Time>, mut timestep: ResMut<SignalTimestep>) {
+    timestep.advance(time.delta());
 }
 
-/// Compute changes (deltas) in signal values at tiles, due to movement of signal between
-/// tiles.
-///
-/// Currently movement only occurs due to diffusion.
-fn compute_deltas(
-    map_positions: Res<MapPositions>,
-    mut map_signals: ResMut<MapResource<TileSignals>>,
+/// Decays and diffuses signals, once for each tick that has passed this frame, and then prunes
+/// any that have become too faint to matter.
+fn simulate_signals(
+    timestep: Res<SignalTimestep>,
+    signal_pruning: Res<SignalPruning>,
+    mut signal_field: ResMut<SignalField>,
     signal_configs: Res<SignalConfigs>,
+    mut signal_recorder: ResMut<SignalRecorder>,
 ) {
-    for tile_pos in map_positions.iter_positions() {
-        let current_values = map_signals.get(tile_pos).unwrap().read().current_values();
-        let signals_patch = map_signals.get_patch_mut(tile_pos).unwrap();
-
-        for (emitter_id, current_value) in current_values {
-            let signal_config = signal_configs.get(&emitter_id).unwrap();
-
-            // TODO: this should also be cached in a MapResource?
-            // normalize the diffusion factors into a probability
-            let neighbor_diffusion_probability = if signal_config.diffusion_factor > 0.0 {
-                let count = map_positions.get_patch_count(tile_pos).unwrap();
-                signal_config.diffusion_factor / (signal_config.diffusion_factor * count as f32)
-            } else {
-                0.0
-            };
-
-            let mut total_outgoing = 0.0;
-            for location in HexPatchLocation::variants() {
-                if let Some(s) = signals_patch.get_inner_mut(location) {
-                    let delta = neighbor_diffusion_probability * current_value;
-                    s.get_mut().increment_incoming(&emitter_id, delta);
-                    total_outgoing += delta;
-                }
-            }
-            signals_patch
-                .get_inner_mut(HexPatchLocation::Center)
-                .unwrap()
-                .get_mut()
-                .increment_outgoing(&emitter_id, total_outgoing);
-        }
+    for _ in 0..timestep.ticks_this_frame() {
+        tick_signals(&mut signal_field, &signal_configs);
+        signal_recorder.record_tick(&signal_field, timestep.tick);
     }
-}
 
-/// Applies deltas due to movement of signals between tiles.
-///
-/// Should run after [`compute_deltas`].
-fn apply_deltas(mut map_signals: ResMut<MapResource<TileSignals>>) {
-    for tile_signals in map_signals.values_mut() {
-        tile_signals.get_mut().apply_deltas();
-    }
-}
-
-/// A diffusible signal at a given tile.
-#[derive(Default, Debug, Clone, Copy)]
-pub struct Signal {
-    /// The value of the signal at this tick.
-    current_value: f32,
-    /// The amount of signal that will be coming into this tile this tick.
-    ///
-    /// Generally, this will be based on [`current_value`](Signal::current_value) of neighboring tiles.
-    incoming: f32,
-    /// The amount of signal that will be leaving this tile this tick.
-    ///
-    /// Generally, this will be based on [`current_value`](Signal::current_value).
-    outgoing: f32,
-}
-
-impl Signal {
-    /// Create new [`Signal`] with given starting `value` and `color`.
-    pub fn new(value: f32) -> Signal {
-        Signal {
-            current_value: value,
-            incoming: 0.0,
-            outgoing: 0.0,
-        }
-    }
-
-    /// Apply accumulated `incoming`/`outgoing` to the `current_value`, while ensuring that the
-    /// signal's value does not go below `0.0`.
-    ///
-    /// `incoming` and `outgoing` are reset to `0.0` once applied.
-    fn apply_deltas(&mut self) {
-        self.current_value = (self.current_value + self.incoming - self.outgoing).max(0.0);
-        self.incoming = 0.0;
-        self.outgoing = 0.0;
-    }
-
-    /// Compute the color of this signal given a color configuration, if the signal's color
-    /// configuration indicates it is to be visible.
-    fn compute_color(&self, color_config: &SignalColorConfig) -> Option<Color> {
-        color_config.is_visible.then_some({
-            // This produces a Color::Rgba variant.
-            let mut color = Color::from(color_config.rgb_color);
-
-            // What are the possible values for a signal? [0, \infty)
-            // What are we mapping to? [0, 1), the alpha component of our color
-            // Use a shifted sigmoid to represent this
-            color.set_a(color_config.sigmoid.map(self.current_value));
-
-            color
-        })
+    if timestep.ticks_this_frame() > 0 {
+        signal_field.prune(signal_pruning.epsilon);
     }
 }
 
 /// Information carried by the signal, which is typically translated into an activity instruction.
-#[derive(Debug, Clone, Copy)]
+///
+/// Use [`Emitter::info`] to find the information carried by signals from an emitter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SignalInfo {
     /// Signal that does not carry an instruction.
     Passive(Emitter),
-    /// Signal with a push (drop-off) instruction.
-    Push(Emitter),
-    /// Signal with a pull (fetch) instruction.
-    Pull(Emitter),
+    /// Signal with a push instruction: this item should be picked up and taken away.
+    Push(ItemId),
+    /// Signal with a pull instruction: this item should be fetched and dropped off here.
+    Pull(ItemId),
     /// Signal that requests work be carried out.
     Work,
 }
 
+impl SignalInfo {
+    /// The emitter whose signals carry this information.
+    pub fn emitter(&self) -> Emitter {
+        match self {
+            SignalInfo::Passive(emitter) => *emitter,
+            SignalInfo::Push(item_id) => Emitter::Push(*item_id),
+            SignalInfo::Pull(item_id) => Emitter::Pull(*item_id),
+            SignalInfo::Work => Emitter::Work,
+        }
+    }
+}
+
 impl Default for SignalInfo {
     fn default() -> Self {
         Self::Passive(Emitter::default())
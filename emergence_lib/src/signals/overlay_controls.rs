@@ -0,0 +1,78 @@
+//! Keybindings for choosing which signals the [`SignalOverlay`] shows.
+
+use crate::signals::configs::SignalConfigs;
+use crate::signals::map_overlay::SignalOverlay;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::*;
+
+/// Lets the player change the [`SignalOverlay`] from the keyboard.
+pub struct OverlayControlsPlugin;
+
+impl Plugin for OverlayControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(InputManagerPlugin::<OverlayAction>::default())
+            .add_startup_system(initialize_overlay_controls)
+            .add_system(control_overlay);
+    }
+}
+
+/// Marks the entity that holds the input state for [`OverlayAction`]s.
+#[derive(Component, Clone, Copy)]
+struct OverlayControls;
+
+/// Enumerates the ways the player can change the [`SignalOverlay`].
+#[derive(Actionlike, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OverlayAction {
+    /// Show the next emitter.
+    NextEmitter,
+    /// Show the previous emitter.
+    PreviousEmitter,
+    /// Add the emitter being shown to the blended set, or remove it.
+    ToggleBlended,
+    /// Switch to the next heatmap palette.
+    CyclePalette,
+    /// Hide or show the overlay.
+    ToggleHidden,
+}
+
+/// Startup system that binds keys to each [`OverlayAction`].
+fn initialize_overlay_controls(mut commands: Commands) {
+    commands.spawn((
+        OverlayControls,
+        InputManagerBundle::<OverlayAction> {
+            action_state: ActionState::default(),
+            input_map: InputMap::new([
+                (KeyCode::RBracket.into(), OverlayAction::NextEmitter),
+                (KeyCode::LBracket.into(), OverlayAction::PreviousEmitter),
+                (KeyCode::B.into(), OverlayAction::ToggleBlended),
+                (KeyCode::P.into(), OverlayAction::CyclePalette),
+                (KeyCode::O.into(), OverlayAction::ToggleHidden),
+            ]),
+        },
+    ));
+}
+
+/// Applies the [`OverlayAction`]s pressed this frame to the [`SignalOverlay`].
+fn control_overlay(
+    controls_query: Query<&ActionState<OverlayAction>, With<OverlayControls>>,
+    signal_configs: Res<SignalConfigs>,
+    mut signal_overlay: ResMut<SignalOverlay>,
+) {
+    let action_state = controls_query.single();
+
+    if action_state.just_pressed(OverlayAction::NextEmitter) {
+        signal_overlay.cycle(&signal_configs, true);
+    }
+    if action_state.just_pressed(OverlayAction::PreviousEmitter) {
+        signal_overlay.cycle(&signal_configs, false);
+    }
+    if action_state.just_pressed(OverlayAction::ToggleBlended) {
+        signal_overlay.toggle_blended();
+    }
+    if action_state.just_pressed(OverlayAction::CyclePalette) {
+        signal_overlay.palette = signal_overlay.palette.next();
+    }
+    if action_state.just_pressed(OverlayAction::ToggleHidden) {
+        signal_overlay.toggle_hidden();
+    }
+}
@@ -0,0 +1,462 @@
+//! Records summary statistics of every signal over time, so that diffusion and decay can be
+//! plotted and compared between tuning runs.
+//!
+//! The [`SignalRecorder`] samples the [`SignalField`] every [`interval`](SignalRecorder::interval)
+//! ticks into a ring buffer. Recordings are written to disk as CSV or JSON when an
+//! [`ExportSignalRecording`] event is sent, and when the app exits if
+//! [`export_on_exit`](SignalRecorder::export_on_exit) is set.
+//!
+//! Positions are measured in the plane of the map, as returned by [`plane_position`].
+
+use crate::signals::configs::SignalConfigs;
+use crate::signals::emitters::Emitter;
+use crate::signals::field::SignalField;
+use crate::signals::sensing::plane_position;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt::{Display, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Summary statistics of the signal from a single emitter, at a single moment.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SignalStatistics {
+    /// The total amount of signal on the map.
+    pub total: f32,
+    /// The largest value of the signal at any tile.
+    pub max: f32,
+    /// The mean squared distance of the signal from its centroid, weighted by the value at each
+    /// tile.
+    ///
+    /// This grows steadily as a signal diffuses away from where it was emitted.
+    pub variance: f32,
+    /// The center of mass of the signal, or [`Vec2::ZERO`] if there is no signal.
+    pub centroid: Vec2,
+}
+
+impl SignalStatistics {
+    /// Computes the statistics of a signal with the given `values` at each of the `positions`.
+    pub fn compute(values: &[f32], positions: &[Vec2]) -> SignalStatistics {
+        let mut total = 0.0;
+        let mut max = 0.0f32;
+        let mut weighted_position = Vec2::ZERO;
+        for (value, position) in values.iter().zip(positions) {
+            total += value;
+            max = max.max(*value);
+            weighted_position += *value * *position;
+        }
+
+        if total <= 0.0 {
+            return SignalStatistics {
+                total,
+                max,
+                ..Default::default()
+            };
+        }
+
+        let centroid = weighted_position / total;
+        let variance = values
+            .iter()
+            .zip(positions)
+            .map(|(value, position)| value * position.distance_squared(centroid))
+            .sum::<f32>()
+            / total;
+
+        SignalStatistics {
+            total,
+            max,
+            variance,
+            centroid,
+        }
+    }
+}
+
+/// The statistics of every signal at a single tick.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalSample {
+    /// The number of ticks simulated before this sample was taken.
+    pub tick: u64,
+    /// The amount of simulated time that had passed when this sample was taken.
+    pub time: Duration,
+    /// The statistics of the signal from each emitter in the field, in the field's order.
+    pub emitters: Vec<(Emitter, SignalStatistics)>,
+}
+
+/// A file format that recordings can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// One row per emitter per sample, with a header row.
+    Csv,
+    /// An array of samples, each listing the statistics of every emitter.
+    Json,
+}
+
+/// Failed to export a signal recording.
+#[derive(Debug)]
+pub enum RecordingError {
+    /// The file could not be written.
+    Io(std::io::Error),
+    /// The recording could not be serialized as JSON.
+    Json(serde_json::Error),
+}
+
+impl Display for RecordingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordingError::Io(error) => write!(f, "could not write recording: {error}"),
+            RecordingError::Json(error) => write!(f, "could not serialize recording: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+impl From<std::io::Error> for RecordingError {
+    fn from(error: std::io::Error) -> Self {
+        RecordingError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for RecordingError {
+    fn from(error: serde_json::Error) -> Self {
+        RecordingError::Json(error)
+    }
+}
+
+/// The JSON layout of a [`SignalSample`]
+#[derive(Serialize)]
+struct JsonSample {
+    /// The number of ticks simulated before the sample was taken
+    tick: u64,
+    /// The simulated time, in seconds
+    time: f64,
+    /// The statistics of each emitter
+    emitters: Vec<JsonStatistics>,
+}
+
+/// The JSON layout of the [`SignalStatistics`] of a single emitter
+#[derive(Serialize)]
+struct JsonStatistics {
+    /// The name of the emitter
+    emitter: String,
+    /// See [`SignalStatistics::total`]
+    total: f32,
+    /// See [`SignalStatistics::max`]
+    max: f32,
+    /// See [`SignalStatistics::variance`]
+    variance: f32,
+    /// See [`SignalStatistics::centroid`]
+    centroid: [f32; 2],
+}
+
+/// Samples the statistics of every signal at a regular interval.
+#[derive(Resource, Debug, Clone)]
+pub struct SignalRecorder {
+    /// The number of ticks between samples, or `0` to stop recording.
+    pub interval: u32,
+    /// The most samples that are kept; once full, the oldest samples are dropped.
+    pub capacity: usize,
+    /// Where, and in what format, to export the recording when the app exits.
+    pub export_on_exit: Option<(PathBuf, RecordingFormat)>,
+    /// The number of ticks that have been simulated
+    ticks: u64,
+    /// The number of ticks that have been simulated since the last sample was taken
+    ticks_since_sample: u32,
+    /// The samples taken so far, oldest first
+    samples: VecDeque<SignalSample>,
+}
+
+impl Default for SignalRecorder {
+    /// Recording is off by default, as it is only needed while tuning signals.
+    fn default() -> Self {
+        SignalRecorder::new(0, 4096)
+    }
+}
+
+impl SignalRecorder {
+    /// Creates a recorder that samples every `interval` ticks, keeping at most `capacity` samples.
+    pub fn new(interval: u32, capacity: usize) -> SignalRecorder {
+        SignalRecorder {
+            interval,
+            capacity,
+            export_on_exit: None,
+            ticks: 0,
+            ticks_since_sample: 0,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// The samples recorded so far, oldest first
+    pub fn samples(&self) -> impl Iterator<Item = &SignalSample> {
+        self.samples.iter()
+    }
+
+    /// Drops every sample recorded so far.
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Records that a tick of length `tick_length` was simulated, sampling `signal_field` if it is
+    /// time to do so.
+    pub fn record_tick(&mut self, signal_field: &SignalField, tick_length: Duration) {
+        self.ticks += 1;
+        if self.interval == 0 || self.capacity == 0 {
+            return;
+        }
+        self.ticks_since_sample += 1;
+        if self.ticks_since_sample < self.interval {
+            return;
+        }
+        self.ticks_since_sample = 0;
+
+        let positions: Vec<Vec2> = signal_field
+            .index()
+            .positions()
+            .iter()
+            .map(plane_position)
+            .collect();
+        let emitters = signal_field
+            .layers()
+            .map(|(emitter, layer)| {
+                (
+                    *emitter,
+                    SignalStatistics::compute(layer.values(), &positions),
+                )
+            })
+            .collect();
+
+        while self.samples.len() >= self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(SignalSample {
+            tick: self.ticks,
+            time: Duration::from_secs_f64(tick_length.as_secs_f64() * self.ticks as f64),
+            emitters,
+        });
+    }
+
+    /// The recording as CSV, with one row per emitter per sample.
+    ///
+    /// Emitters are named using [`SignalConfigs::label`].
+    pub fn to_csv(&self, signal_configs: &SignalConfigs) -> String {
+        let mut csv = String::from("tick,time,emitter,total,max,variance,centroid_x,centroid_y\n");
+        for sample in &self.samples {
+            for (emitter, statistics) in &sample.emitters {
+                // Writing to a string cannot fail
+                let _ = writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{},{}",
+                    sample.tick,
+                    sample.time.as_secs_f64(),
+                    csv_field(&signal_configs.label(emitter)),
+                    statistics.total,
+                    statistics.max,
+                    statistics.variance,
+                    statistics.centroid.x,
+                    statistics.centroid.y,
+                );
+            }
+        }
+        csv
+    }
+
+    /// The recording as a JSON array of samples.
+    ///
+    /// Emitters are named using [`SignalConfigs::label`].
+    pub fn to_json(&self, signal_configs: &SignalConfigs) -> Result<String, RecordingError> {
+        let samples: Vec<JsonSample> = self
+            .samples
+            .iter()
+            .map(|sample| JsonSample {
+                tick: sample.tick,
+                time: sample.time.as_secs_f64(),
+                emitters: sample
+                    .emitters
+                    .iter()
+                    .map(|(emitter, statistics)| JsonStatistics {
+                        emitter: signal_configs.label(emitter),
+                        total: statistics.total,
+                        max: statistics.max,
+                        variance: statistics.variance,
+                        centroid: statistics.centroid.to_array(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(serde_json::to_string_pretty(&samples)?)
+    }
+
+    /// Writes the recording to the file at `path`.
+    pub fn export(
+        &self,
+        signal_configs: &SignalConfigs,
+        path: &Path,
+        format: RecordingFormat,
+    ) -> Result<(), RecordingError> {
+        let contents = match format {
+            RecordingFormat::Csv => self.to_csv(signal_configs),
+            RecordingFormat::Json => self.to_json(signal_configs)?,
+        };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// Quotes `field` for use in a CSV file, so that it can contain commas, quotes and line breaks.
+fn csv_field(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// Request that the current [`SignalRecorder`] recording be written to the file at `path`.
+pub struct ExportSignalRecording {
+    /// The file to write to.
+    pub path: PathBuf,
+    /// The format to write the recording in.
+    pub format: RecordingFormat,
+}
+
+/// Writes the recording to disk when requested, and when the app exits.
+pub(super) fn export_signal_recordings(
+    mut export_events: EventReader<ExportSignalRecording>,
+    mut exit_events: EventReader<AppExit>,
+    signal_recorder: Res<SignalRecorder>,
+    signal_configs: Res<SignalConfigs>,
+) {
+    let mut requests: Vec<(PathBuf, RecordingFormat)> = export_events
+        .iter()
+        .map(|request| (request.path.clone(), request.format))
+        .collect();
+    if exit_events.iter().next().is_some() {
+        requests.extend(signal_recorder.export_on_exit.clone());
+    }
+
+    for (path, format) in requests {
+        match signal_recorder.export(&signal_configs, &path, format) {
+            Ok(()) => info!("Exported signal recording to {path:?}"),
+            Err(error) => error!("Failed to export signal recording to {path:?}: {error}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signals::field::test_utils::{map, EMITTER};
+    use crate::signals::registry::CustomEmitter;
+    use bevy_ecs_tilemap::tiles::TilePos;
+
+    /// The length of each tick in these tests
+    const TICK: Duration = Duration::from_millis(100);
+
+    /// A small map, with some signal at its center and one of its neighbors
+    fn field() -> (TilePos, TilePos, SignalField) {
+        let (map_geometry, map_positions, mut signal_field) = map();
+        let center = map_geometry.center();
+        let neighbor = map_positions.ring(&center, 1).next().unwrap();
+        signal_field.set(EMITTER, &center, 3.0);
+        signal_field.set(EMITTER, &neighbor, 1.0);
+        (center, neighbor, signal_field)
+    }
+
+    #[test]
+    fn statistics_describe_the_spread_of_a_signal() {
+        let (center, neighbor, _) = field();
+        let positions = [plane_position(&center), plane_position(&neighbor)];
+
+        let statistics = SignalStatistics::compute(&[3.0, 1.0], &positions);
+        assert_eq!(statistics.total, 4.0);
+        assert_eq!(statistics.max, 3.0);
+        // Neighbors are one step apart, so the centroid is a quarter step from the center
+        let expected_centroid = positions[0] + 0.25 * (positions[1] - positions[0]);
+        assert!((statistics.centroid - expected_centroid).length() <= 1e-4);
+        assert!((statistics.variance - (3.0 * 0.0625 + 0.5625) / 4.0).abs() <= 1e-4);
+
+        let empty = SignalStatistics::compute(&[0.0, 0.0], &positions);
+        assert_eq!(empty, SignalStatistics::default());
+    }
+
+    #[test]
+    fn samples_are_taken_at_intervals_into_a_ring_buffer() {
+        let (_, _, signal_field) = field();
+        let mut recorder = SignalRecorder::new(2, 3);
+
+        for _ in 0..10 {
+            recorder.record_tick(&signal_field, TICK);
+        }
+
+        let ticks: Vec<u64> = recorder.samples().map(|sample| sample.tick).collect();
+        assert_eq!(ticks, vec![6, 8, 10]);
+        let last = recorder.samples().last().unwrap();
+        assert_eq!(last.time, Duration::from_secs(1));
+        assert_eq!(last.emitters.len(), 1);
+        assert_eq!(last.emitters[0].1.total, 4.0);
+
+        // Recording can be switched off
+        recorder.interval = 0;
+        recorder.clear();
+        recorder.record_tick(&signal_field, TICK);
+        assert_eq!(recorder.samples().count(), 0);
+    }
+
+    #[test]
+    fn sample_times_do_not_wrap_around() {
+        let (_, _, signal_field) = field();
+        let mut recorder = SignalRecorder::new(1, 1);
+        recorder.ticks = u64::from(u32::MAX);
+        recorder.record_tick(&signal_field, TICK);
+
+        let last = recorder.samples().last().unwrap();
+        assert!((last.time.as_secs_f64() - 0.1 * 2f64.powi(32)).abs() <= 1e-3);
+    }
+
+    #[test]
+    fn recordings_can_be_exported() {
+        let (_, _, signal_field) = field();
+        let signal_configs = SignalConfigs::default();
+        let mut recorder = SignalRecorder::new(1, 10);
+        recorder.record_tick(&signal_field, TICK);
+        recorder.record_tick(&signal_field, TICK);
+
+        let csv = recorder.to_csv(&signal_configs);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("tick,time,emitter,total"));
+        assert!(lines[2].starts_with("2,0.2,\"Ant\",4,3,"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&recorder.to_json(&signal_configs).unwrap()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(json[1]["tick"], 2);
+        assert_eq!(json[1]["emitters"][0]["emitter"], "Ant");
+        assert_eq!(json[1]["emitters"][0]["total"], 4.0);
+
+        let path = std::env::temp_dir().join("emergence_signal_recording_test.csv");
+        recorder
+            .export(&signal_configs, &path, RecordingFormat::Csv)
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), csv);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn csv_labels_are_quoted() {
+        let mut signal_configs = SignalConfigs::default();
+        let config = *signal_configs.get(&EMITTER).unwrap();
+        let custom = signal_configs
+            .register_custom_emitter(CustomEmitter {
+                display_name: "Say \"hi\", please".to_string(),
+                ..CustomEmitter::new("greeting", config)
+            })
+            .unwrap();
+        let (center, _, mut signal_field) = field();
+        signal_field.set(custom, &center, 1.0);
+        let mut recorder = SignalRecorder::new(1, 1);
+        recorder.record_tick(&signal_field, TICK);
+
+        let csv = recorder.to_csv(&signal_configs);
+        assert!(csv.contains("0.1,\"Say \"\"hi\"\", please\",1,"));
+    }
+}
@@ -0,0 +1,230 @@
+//! Gives [custom emitters](Emitter::Custom) human-readable names.
+//!
+//! Custom emitters are registered by name through
+//! [`SignalConfigs::register_custom_emitter`](crate::signals::configs::SignalConfigs::register_custom_emitter),
+//! which allocates their id and stores their configuration. The names are used wherever emitters are
+//! shown to players, and are written to save files so that ids stay stable between sessions.
+
+use crate::signals::configs::SignalConfig;
+use crate::signals::emitters::Emitter;
+use bevy::utils::HashMap;
+use indexmap::IndexMap;
+use std::fmt::Display;
+
+/// Everything needed to register a custom emitter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomEmitter {
+    /// The unique name used to refer to the emitter in code, configuration files and saves.
+    pub name: String,
+    /// The name shown to players.
+    pub display_name: String,
+    /// How the signal behaves, and its colour on the map.
+    pub config: SignalConfig,
+}
+
+impl CustomEmitter {
+    /// Describes a custom emitter that is shown to players by its `name`.
+    pub fn new(name: impl Into<String>, config: SignalConfig) -> CustomEmitter {
+        let name = name.into();
+        CustomEmitter {
+            display_name: name.clone(),
+            name,
+            config,
+        }
+    }
+}
+
+/// The names of a registered custom emitter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomEmitterNames {
+    /// The unique name used to refer to the emitter in code, configuration files and saves.
+    pub name: String,
+    /// The name shown to players.
+    pub display_name: String,
+}
+
+/// A custom emitter could not be registered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationError {
+    /// A custom emitter with this name has already been registered.
+    DuplicateName(String),
+    /// The requested id is already used by another emitter.
+    IdTaken {
+        /// The requested id.
+        id: u16,
+        /// The name of the emitter using the id, if it was registered with one.
+        existing: Option<String>,
+    },
+    /// Every custom emitter id is already in use.
+    TooManyCustomEmitters,
+}
+
+impl Display for RegistrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistrationError::DuplicateName(name) => {
+                write!(f, "custom emitter {name:?} has already been registered")
+            }
+            RegistrationError::IdTaken {
+                id,
+                existing: Some(existing),
+            } => write!(f, "custom emitter id {id} is already used by {existing:?}"),
+            RegistrationError::IdTaken { id, existing: None } => {
+                write!(f, "custom emitter id {id} is already in use")
+            }
+            RegistrationError::TooManyCustomEmitters => write!(
+                f,
+                "at most {} custom emitters can be registered",
+                u16::MAX as usize + 1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RegistrationError {}
+
+/// The names of every registered custom emitter, and the ids they were given.
+#[derive(Debug, Clone, Default)]
+pub struct CustomEmitterRegistry {
+    /// The names of each registered emitter, by id, in the order they were registered
+    names: IndexMap<u16, CustomEmitterNames>,
+    /// The id of each registered emitter, by name
+    ids: HashMap<String, u16>,
+}
+
+impl CustomEmitterRegistry {
+    /// The emitter registered with the given `name`, if any
+    pub fn emitter(&self, name: &str) -> Option<Emitter> {
+        self.ids.get(name).map(|id| Emitter::Custom(*id))
+    }
+
+    /// The names of `emitter`, if it is a registered custom emitter
+    pub fn names(&self, emitter: &Emitter) -> Option<&CustomEmitterNames> {
+        match emitter {
+            Emitter::Custom(id) => self.names.get(id),
+            _ => None,
+        }
+    }
+
+    /// Iterate over every registered emitter and its names, in the order they were registered
+    pub fn iter(&self) -> impl Iterator<Item = (Emitter, &CustomEmitterNames)> {
+        self.names
+            .iter()
+            .map(|(id, names)| (Emitter::Custom(*id), names))
+    }
+
+    /// The lowest id for which `is_free` holds
+    pub(super) fn free_id(&self, is_free: impl Fn(u16) -> bool) -> Result<u16, RegistrationError> {
+        (0..=u16::MAX)
+            .find(|id| is_free(*id))
+            .ok_or(RegistrationError::TooManyCustomEmitters)
+    }
+
+    /// Records the names of the emitter with the given `id`.
+    ///
+    /// The caller is responsible for checking that `id` is free.
+    pub(super) fn insert(
+        &mut self,
+        id: u16,
+        names: CustomEmitterNames,
+    ) -> Result<(), RegistrationError> {
+        if self.ids.contains_key(&names.name) {
+            return Err(RegistrationError::DuplicateName(names.name));
+        }
+        if let Some(existing) = self.names.get(&id) {
+            return Err(RegistrationError::IdTaken {
+                id,
+                existing: Some(existing.name.clone()),
+            });
+        }
+
+        self.ids.insert(names.name.clone(), id);
+        self.names.insert(id, names);
+        Ok(())
+    }
+
+    /// Changes the display name of the emitter registered as `name`, if there is one
+    pub(super) fn rename(&mut self, name: &str, display_name: String) {
+        if let Some(id) = self.ids.get(name) {
+            if let Some(names) = self.names.get_mut(id) {
+                names.display_name = display_name;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signals::configs::SignalConfigs;
+    use crate::signals::emitters::StockEmitter;
+
+    /// A custom emitter with the given `name`, configured like the stock ant signal
+    fn custom(name: &str) -> CustomEmitter {
+        let config = *SignalConfigs::default()
+            .get(&Emitter::Stock(StockEmitter::Ant))
+            .unwrap();
+        CustomEmitter::new(name, config)
+    }
+
+    #[test]
+    fn names_are_given_the_lowest_free_id() {
+        let mut signal_configs = SignalConfigs::default();
+        let trail = signal_configs
+            .register_custom_emitter(custom("trail"))
+            .unwrap();
+        let mut alarm = custom("alarm");
+        alarm.display_name = "Alarm pheromone".to_string();
+        let alarm = signal_configs.register_custom_emitter(alarm).unwrap();
+
+        assert_eq!(trail, Emitter::Custom(0));
+        assert_eq!(alarm, Emitter::Custom(1));
+        assert_eq!(signal_configs.custom_emitter("alarm"), Some(alarm));
+        assert!(signal_configs.get(&alarm).is_some());
+        assert_eq!(signal_configs.label(&alarm), "Alarm pheromone");
+        assert_eq!(signal_configs.label(&Emitter::Custom(7)), "Custom 7");
+
+        let names: Vec<&str> = signal_configs
+            .custom_emitters()
+            .iter()
+            .map(|(_, names)| names.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["trail", "alarm"]);
+    }
+
+    #[test]
+    fn duplicate_and_conflicting_registrations_are_rejected() {
+        let mut signal_configs = SignalConfigs::default();
+        signal_configs
+            .register_custom_emitter(custom("trail"))
+            .unwrap();
+
+        assert_eq!(
+            signal_configs.register_custom_emitter(custom("trail")),
+            Err(RegistrationError::DuplicateName("trail".to_string()))
+        );
+        assert_eq!(
+            signal_configs.register_custom_emitter_with_id(0, custom("alarm")),
+            Err(RegistrationError::IdTaken {
+                id: 0,
+                existing: Some("trail".to_string())
+            })
+        );
+
+        // Emitters configured without a name still occupy their id
+        let config = custom("anonymous").config;
+        signal_configs.insert(Emitter::Custom(1), config);
+        assert_eq!(
+            signal_configs.register_custom_emitter_with_id(1, custom("alarm")),
+            Err(RegistrationError::IdTaken {
+                id: 1,
+                existing: None
+            })
+        );
+        assert_eq!(
+            signal_configs.register_custom_emitter(custom("alarm")),
+            Ok(Emitter::Custom(2))
+        );
+        assert_eq!(signal_configs.custom_emitters().iter().count(), 2);
+    }
+}
@@ -0,0 +1,280 @@
+//! Queries that let units and tools sense the [`SignalField`] around a tile.
+//!
+//! Directions are measured in the plane of the map, with one unit of length per step between
+//! adjacent tiles: the `x` axis points along a row of tiles, and the `y` axis points towards
+//! increasing rows.
+
+use crate::enum_iter::IterableEnum;
+use crate::signals::emitters::Emitter;
+use crate::signals::field::SignalField;
+use crate::simulation::map::geometry::hex_distance;
+use crate::simulation::map::hex_patch::HexPatchLocation;
+use crate::simulation::map::MapPositions;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+/// Controls how much tiles further away count towards a [`sample`](SignalField::sample).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Falloff {
+    /// Every tile within the radius counts equally.
+    Uniform,
+    /// Weight falls linearly with distance, from `1.0` at the center to zero one step beyond
+    /// the radius.
+    Linear,
+    /// Weight halves every `half_distance` steps away from the center.
+    ///
+    /// A `half_distance` that is not positive is treated as the limit of a vanishingly small one:
+    /// only the center counts.
+    Exponential {
+        /// The number of steps over which weight halves
+        half_distance: f32,
+    },
+}
+
+impl Falloff {
+    /// The weight of a tile `distance` steps from the center of a sample with the given `radius`
+    pub fn weight(&self, distance: u32, radius: u32) -> f32 {
+        match self {
+            Falloff::Uniform => 1.0,
+            Falloff::Linear => 1.0 - distance as f32 / (radius + 1) as f32,
+            Falloff::Exponential { half_distance } if *half_distance > 0.0 => {
+                0.5f32.powf(distance as f32 / half_distance)
+            }
+            // Dividing by a half-distance of zero would be NaN, and a negative one would favor far tiles
+            Falloff::Exponential { .. } => {
+                if distance == 0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// The location of the center of the tile at `position`, in the plane of the map
+pub fn plane_position(position: &TilePos) -> Vec2 {
+    let q = position.x as f32;
+    let r = position.y as f32;
+    Vec2::new(q + 0.5 * r, r * 3f32.sqrt() / 2.0)
+}
+
+/// The vector from `from` to `to`, in the plane of the map
+fn offset(from: &TilePos, to: &TilePos) -> Vec2 {
+    plane_position(to) - plane_position(from)
+}
+
+impl SignalField {
+    /// The direction and rate at which the signal from `emitter` increases around `position`.
+    ///
+    /// This is the least-squares fit of a plane to the values at `position` and its neighbors,
+    /// so it is well-behaved on the edges of the map, where some neighbors are missing.
+    /// Returns [`None`] if `position` is not on the map.
+    pub fn gradient(&self, emitter: &Emitter, position: &TilePos) -> Option<Vec2> {
+        let center = self.index().get(position)?;
+        let center_value = self.get(emitter, position);
+
+        // Accumulate the normal equations of the least-squares fit
+        let mut moments = Mat2::ZERO;
+        let mut weighted_differences = Vec2::ZERO;
+        for neighbor in self.index().patch(center).iter().filter(|i| **i != center) {
+            let neighbor_position = self.index().position(*neighbor);
+            let direction = offset(position, &neighbor_position);
+            let difference = self.get(emitter, &neighbor_position) - center_value;
+
+            moments += Mat2::from_cols(direction * direction.x, direction * direction.y);
+            weighted_differences += direction * difference;
+        }
+
+        if moments.determinant().abs() <= f32::EPSILON {
+            return Some(Vec2::ZERO);
+        }
+        Some(moments.inverse() * weighted_differences)
+    }
+
+    /// The neighbor of `position` with the strongest signal from `emitter`.
+    ///
+    /// Returns [`None`] if no neighbor has a stronger signal than `position` itself, or if
+    /// `position` is not on the map. Ties are broken in favor of the earliest [`HexPatchLocation`].
+    pub fn strongest_ascent(
+        &self,
+        emitter: &Emitter,
+        position: &TilePos,
+    ) -> Option<HexPatchLocation> {
+        let patch = self.get_patch(emitter, position)?;
+        let mut best = (
+            HexPatchLocation::Center,
+            *patch.get(HexPatchLocation::Center)?,
+        );
+
+        for location in HexPatchLocation::variants() {
+            if let Some(value) = patch.get(location) {
+                if *value > best.1 {
+                    best = (location, *value);
+                }
+            }
+        }
+
+        match best.0 {
+            HexPatchLocation::Center => None,
+            location => Some(location),
+        }
+    }
+
+    /// The weighted average of the signal from `emitter` over every tile within `radius` steps of
+    /// `position`, where each tile is weighted according to `falloff`.
+    ///
+    /// Tiles that are not on the map are left out of the average.
+    /// Returns `0.0` if `position` is not on the map.
+    pub fn sample(
+        &self,
+        emitter: &Emitter,
+        map_positions: &MapPositions,
+        position: &TilePos,
+        radius: u32,
+        falloff: Falloff,
+    ) -> f32 {
+        let mut total = 0.0;
+        let mut total_weight = 0.0;
+        for tile_pos in map_positions.disk(position, radius) {
+            let weight = falloff.weight(hex_distance(position, &tile_pos), radius);
+            total += weight * self.get(emitter, &tile_pos);
+            total_weight += weight;
+        }
+
+        if total_weight > 0.0 {
+            total / total_weight
+        } else {
+            0.0
+        }
+    }
+
+    /// The (up to) `k` emitters with the strongest signal at `position`, strongest first.
+    ///
+    /// Emitters with no signal at `position` are never included.
+    /// Ties are broken in favor of the emitter that was added to the field first.
+    pub fn top_emitters(&self, position: &TilePos, k: usize) -> Vec<(Emitter, f32)> {
+        let mut values = self.tile_values(position);
+        values.retain(|(_, value)| *value > 0.0);
+        // Stable sorting keeps ties in the order the emitters were added
+        values.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        values.truncate(k);
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signals::emitters::StockEmitter;
+    use crate::signals::field::test_utils::{map, EMITTER};
+
+    #[test]
+    fn gradient_points_uphill() {
+        let (map_geometry, map_positions, mut signal_field) = map();
+        let center = map_geometry.center();
+        let slope = Vec2::new(0.3, -0.7);
+
+        // A plane that rises along `slope`, shifted so that it is never negative
+        for position in map_positions.iter_positions() {
+            let value = 10.0 + slope.dot(offset(&center, position));
+            signal_field.set(EMITTER, position, value);
+        }
+
+        // A plane is fit exactly, even on the edge of the map
+        for position in map_positions.iter_positions() {
+            let gradient = signal_field.gradient(&EMITTER, position).unwrap();
+            assert!((gradient - slope).length() <= 1e-4);
+        }
+
+        let flat = Emitter::Stock(StockEmitter::Plant);
+        assert_eq!(signal_field.gradient(&flat, &center), Some(Vec2::ZERO));
+        assert_eq!(
+            signal_field.gradient(&EMITTER, &TilePos { x: 1000, y: 0 }),
+            None
+        );
+    }
+
+    #[test]
+    fn strongest_ascent_climbs_to_the_peak() {
+        let (map_geometry, map_positions, mut signal_field) = map();
+        let peak = map_positions
+            .ring(&map_geometry.center(), 2)
+            .next()
+            .unwrap();
+        for position in map_positions.iter_positions() {
+            let value = 10.0 - hex_distance(&peak, position) as f32;
+            signal_field.set(EMITTER, position, value);
+        }
+
+        // Following the strongest ascent from anywhere leads to the peak
+        for start in map_positions.iter_positions() {
+            let mut position = *start;
+            while let Some(location) = signal_field.strongest_ascent(&EMITTER, &position) {
+                position = *map_positions
+                    .get_patch(&position)
+                    .unwrap()
+                    .get(location)
+                    .unwrap();
+            }
+            assert_eq!(position, peak);
+        }
+    }
+
+    #[test]
+    fn samples_are_weighted_by_distance() {
+        let (map_geometry, map_positions, mut signal_field) = map();
+        let center = map_geometry.center();
+        signal_field.set(EMITTER, &center, 7.0);
+
+        // The center and its 6 neighbors
+        let uniform = signal_field.sample(&EMITTER, &map_positions, &center, 1, Falloff::Uniform);
+        assert!((uniform - 1.0).abs() <= 1e-6);
+
+        // The center has weight 1, and each neighbor has weight 1/2
+        let linear = signal_field.sample(&EMITTER, &map_positions, &center, 1, Falloff::Linear);
+        assert!((linear - 7.0 / 4.0).abs() <= 1e-6);
+
+        let exponential = Falloff::Exponential { half_distance: 1.0 };
+        assert_eq!(exponential.weight(2, 5), 0.25);
+        assert_eq!(
+            signal_field.sample(&EMITTER, &map_positions, &center, 0, exponential),
+            7.0
+        );
+    }
+
+    #[test]
+    fn invalid_half_distances_only_count_the_center() {
+        let (map_geometry, map_positions, mut signal_field) = map();
+        let center = map_geometry.center();
+        signal_field.set(EMITTER, &center, 7.0);
+
+        for half_distance in [0.0, -1.0, f32::NAN] {
+            let falloff = Falloff::Exponential { half_distance };
+            assert_eq!(falloff.weight(0, 2), 1.0);
+            assert_eq!(falloff.weight(1, 2), 0.0);
+            assert_eq!(
+                signal_field.sample(&EMITTER, &map_positions, &center, 2, falloff),
+                7.0
+            );
+        }
+    }
+
+    #[test]
+    fn top_emitters_are_strongest_first() {
+        let (map_geometry, _, mut signal_field) = map();
+        let center = map_geometry.center();
+        let plant = Emitter::Stock(StockEmitter::Plant);
+        let fungus = Emitter::Stock(StockEmitter::Fungus);
+        signal_field.set(EMITTER, &center, 1.0);
+        signal_field.set(plant, &center, 3.0);
+        signal_field.set(fungus, &center, 1.0);
+        signal_field.set(Emitter::Work, &center, 0.0);
+
+        assert_eq!(
+            signal_field.top_emitters(&center, 2),
+            vec![(plant, 3.0), (EMITTER, 1.0)]
+        );
+        assert_eq!(signal_field.top_emitters(&center, 10).len(), 3);
+    }
+}
@@ -0,0 +1,499 @@
+//! Watchers that fire events when a signal crosses a chosen level, so that behaviours,
+//! notifications and structures can react to signals without polling every tile themselves.
+//!
+//! Register a [`ThresholdWatcher`] with the [`SignalThresholds`] resource, then read
+//! [`SignalThresholdCrossed`] events.
+//!
+//! Each watcher tracks whether its condition holds separately at every tile it watches.
+//! A tile is said to be *triggered* once its value crosses the level in the watched direction,
+//! and stays triggered until the value moves back past the level by more than the watcher's
+//! hysteresis. This stops signals that hover around the level from firing an event every tick.
+
+use crate::signals::emitters::Emitter;
+use crate::signals::field::SignalField;
+use crate::simulation::map::index::MapIndex;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use indexmap::IndexMap;
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// The direction in which a signal must cross the level of a [`ThresholdWatcher`] to trigger it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// Triggered when the signal rises above the level.
+    Above,
+    /// Triggered when the signal falls below the level.
+    Below,
+}
+
+impl Comparison {
+    /// Does `value` trigger a watcher with this comparison at `level`?
+    fn triggers(&self, value: f32, level: f32) -> bool {
+        match self {
+            Comparison::Above => value > level,
+            Comparison::Below => value < level,
+        }
+    }
+
+    /// Does `value` release a triggered watcher with this comparison at `level`?
+    fn releases(&self, value: f32, level: f32, hysteresis: f32) -> bool {
+        match self {
+            Comparison::Above => value < level - hysteresis,
+            Comparison::Below => value > level + hysteresis,
+        }
+    }
+}
+
+/// Whether a tile has just become triggered, or just stopped being triggered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    /// The signal crossed the level in the watched direction.
+    Rising,
+    /// The signal moved back past the level, by more than the hysteresis.
+    Falling,
+}
+
+/// Which [`Edge`]s a [`ThresholdWatcher`] fires events for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Edges {
+    /// Only fire when a tile becomes triggered.
+    Rising,
+    /// Only fire when a tile stops being triggered.
+    Falling,
+    /// Fire on both edges.
+    #[default]
+    Both,
+}
+
+impl Edges {
+    /// Should events be fired for `edge`?
+    pub fn includes(&self, edge: Edge) -> bool {
+        matches!(
+            (self, edge),
+            (Edges::Both, _) | (Edges::Rising, Edge::Rising) | (Edges::Falling, Edge::Falling)
+        )
+    }
+}
+
+/// Describes when [`SignalThresholdCrossed`] events should be fired.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdWatcher {
+    /// The emitter whose signal is watched.
+    pub emitter: Emitter,
+    /// The direction in which the signal must cross `level`.
+    pub comparison: Comparison,
+    /// The value of the signal that must be crossed.
+    pub level: f32,
+    /// How far past `level` the signal must move back before a triggered tile is released.
+    ///
+    /// Must not be negative.
+    pub hysteresis: f32,
+    /// The edges that events are fired for.
+    pub edges: Edges,
+    /// The tiles to watch, or [`None`] to watch the whole map.
+    ///
+    /// Tiles that are not on the map are ignored.
+    pub region: Option<Vec<TilePos>>,
+}
+
+impl ThresholdWatcher {
+    /// Watches the whole map for the signal from `emitter` crossing `level`, firing on both edges
+    /// without any hysteresis.
+    pub fn new(emitter: Emitter, comparison: Comparison, level: f32) -> ThresholdWatcher {
+        ThresholdWatcher {
+            emitter,
+            comparison,
+            level,
+            hysteresis: 0.0,
+            edges: Edges::Both,
+            region: None,
+        }
+    }
+}
+
+/// Identifies a [`ThresholdWatcher`] registered with the [`SignalThresholds`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ThresholdWatcherId(u32);
+
+/// A [`ThresholdWatcher`] could not be registered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdWatcherError {
+    /// The level was NaN or infinite.
+    InvalidLevel(f32),
+    /// The hysteresis was negative, NaN or infinite.
+    InvalidHysteresis(f32),
+}
+
+impl Display for ThresholdWatcherError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThresholdWatcherError::InvalidLevel(level) => {
+                write!(f, "threshold level must be finite, but was {level}")
+            }
+            ThresholdWatcherError::InvalidHysteresis(hysteresis) => {
+                write!(
+                    f,
+                    "threshold hysteresis must be finite and non-negative, but was {hysteresis}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ThresholdWatcherError {}
+
+/// Fired when a tile watched by a [`ThresholdWatcher`] becomes triggered, or stops being triggered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalThresholdCrossed {
+    /// The watcher whose threshold was crossed.
+    pub watcher: ThresholdWatcherId,
+    /// The emitter whose signal crossed the threshold.
+    pub emitter: Emitter,
+    /// The tile at which the threshold was crossed.
+    pub position: TilePos,
+    /// Whether the tile became triggered, or stopped being triggered.
+    pub edge: Edge,
+    /// The value of the signal at `position` when the threshold was crossed.
+    pub value: f32,
+}
+
+/// A registered [`ThresholdWatcher`], along with the state of each tile it watches
+#[derive(Debug)]
+struct WatcherState {
+    /// The conditions under which events are fired
+    watcher: ThresholdWatcher,
+    /// The index of each watched tile, resolved the first time the watcher is evaluated against
+    /// the current map
+    tiles: Option<Vec<usize>>,
+    /// Whether each watched tile is currently triggered, in the same order as `tiles`
+    triggered: Vec<bool>,
+}
+
+/// The [`ThresholdWatcher`]s that are checked after each signal update.
+#[derive(Resource, Debug, Default)]
+pub struct SignalThresholds {
+    /// The id that will be given to the next registered watcher
+    next_id: u32,
+    /// The registered watchers, in the order they were registered
+    watchers: IndexMap<ThresholdWatcherId, WatcherState>,
+    /// The index of the map that the watched tiles were resolved against
+    index: Option<Arc<MapIndex>>,
+}
+
+impl SignalThresholds {
+    /// Starts firing events for `watcher`.
+    ///
+    /// Every watched tile starts out untriggered, so tiles that already meet the condition will
+    /// fire a [rising](Edge::Rising) event the next time signals are checked.
+    pub fn register(
+        &mut self,
+        watcher: ThresholdWatcher,
+    ) -> Result<ThresholdWatcherId, ThresholdWatcherError> {
+        if !watcher.level.is_finite() {
+            return Err(ThresholdWatcherError::InvalidLevel(watcher.level));
+        }
+        if !watcher.hysteresis.is_finite() || watcher.hysteresis < 0.0 {
+            return Err(ThresholdWatcherError::InvalidHysteresis(watcher.hysteresis));
+        }
+
+        let id = ThresholdWatcherId(self.next_id);
+        self.next_id += 1;
+        self.watchers.insert(
+            id,
+            WatcherState {
+                watcher,
+                tiles: None,
+                triggered: Vec::new(),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Stops firing events for the watcher with the given `id`, returning it if it was registered.
+    pub fn unregister(&mut self, id: ThresholdWatcherId) -> Option<ThresholdWatcher> {
+        self.watchers
+            .shift_remove(&id)
+            .map(|watcher_state| watcher_state.watcher)
+    }
+
+    /// The watcher registered with the given `id`
+    pub fn get(&self, id: ThresholdWatcherId) -> Option<&ThresholdWatcher> {
+        self.watchers
+            .get(&id)
+            .map(|watcher_state| &watcher_state.watcher)
+    }
+
+    /// Iterate over every registered watcher, in the order they were registered
+    pub fn iter(&self) -> impl Iterator<Item = (ThresholdWatcherId, &ThresholdWatcher)> {
+        self.watchers
+            .iter()
+            .map(|(id, watcher_state)| (*id, &watcher_state.watcher))
+    }
+
+    /// Is `position` currently triggered for the watcher with the given `id`?
+    pub fn is_triggered(
+        &self,
+        id: ThresholdWatcherId,
+        signal_field: &SignalField,
+        position: &TilePos,
+    ) -> bool {
+        if !self.is_resolved_against(signal_field) {
+            return false;
+        }
+
+        let (watcher_state, i) = match (self.watchers.get(&id), signal_field.index().get(position))
+        {
+            (Some(watcher_state), Some(i)) => (watcher_state, i),
+            _ => return false,
+        };
+        match &watcher_state.tiles {
+            Some(tiles) => match tiles.iter().position(|tile| *tile == i) {
+                Some(j) => watcher_state.triggered[j],
+                None => false,
+            },
+            None => false,
+        }
+    }
+
+    /// Were the watched tiles resolved against the map covered by `signal_field`?
+    fn is_resolved_against(&self, signal_field: &SignalField) -> bool {
+        match &self.index {
+            Some(index) => Arc::ptr_eq(index, signal_field.index()),
+            None => false,
+        }
+    }
+
+    /// Checks every watcher against the current values in `signal_field`, returning an event for
+    /// each tile whose state changed.
+    ///
+    /// If `signal_field` covers a different map than last time, for example because a saved game
+    /// was loaded, the watched tiles are resolved again, and every tile starts out untriggered.
+    ///
+    /// Events are ordered by watcher, then by tile.
+    pub fn evaluate(&mut self, signal_field: &SignalField) -> Vec<SignalThresholdCrossed> {
+        let index = signal_field.index();
+        if !self.is_resolved_against(signal_field) {
+            for watcher_state in self.watchers.values_mut() {
+                watcher_state.tiles = None;
+                watcher_state.triggered.clear();
+            }
+            self.index = Some(index.clone());
+        }
+        let mut events = Vec::new();
+
+        for (id, watcher_state) in self.watchers.iter_mut() {
+            let watcher = &watcher_state.watcher;
+            let tiles = watcher_state
+                .tiles
+                .get_or_insert_with(|| match &watcher.region {
+                    Some(region) => region
+                        .iter()
+                        .filter_map(|position| index.get(position))
+                        .collect(),
+                    None => (0..index.len()).collect(),
+                });
+            watcher_state.triggered.resize(tiles.len(), false);
+
+            let values = signal_field
+                .layer(&watcher.emitter)
+                .map(|layer| layer.values());
+
+            for (tile, triggered) in tiles.iter().zip(watcher_state.triggered.iter_mut()) {
+                let value = match values {
+                    Some(values) => values[*tile],
+                    None => 0.0,
+                };
+
+                let edge = if !*triggered && watcher.comparison.triggers(value, watcher.level) {
+                    Edge::Rising
+                } else if *triggered
+                    && watcher
+                        .comparison
+                        .releases(value, watcher.level, watcher.hysteresis)
+                {
+                    Edge::Falling
+                } else {
+                    continue;
+                };
+
+                *triggered = edge == Edge::Rising;
+                if watcher.edges.includes(edge) {
+                    events.push(SignalThresholdCrossed {
+                        watcher: *id,
+                        emitter: watcher.emitter,
+                        position: index.position(*tile),
+                        edge,
+                        value,
+                    });
+                }
+            }
+        }
+
+        events
+    }
+}
+
+/// Fires a [`SignalThresholdCrossed`] event for each tile whose state has changed since the
+/// signals were last checked.
+pub(super) fn fire_threshold_events(
+    signal_field: Res<SignalField>,
+    mut signal_thresholds: ResMut<SignalThresholds>,
+    mut threshold_events: EventWriter<SignalThresholdCrossed>,
+) {
+    if signal_thresholds.watchers.is_empty() {
+        return;
+    }
+
+    threshold_events.send_batch(signal_thresholds.evaluate(&signal_field));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signals::field::test_utils::{map, EMITTER};
+    use crate::simulation::map::{MapGeometry, MapPositions, MapShape};
+
+    #[test]
+    fn rising_and_falling_edges_fire_once() {
+        let (map_geometry, _, mut signal_field) = map();
+        let center = map_geometry.center();
+        let mut thresholds = SignalThresholds::default();
+        let id = thresholds
+            .register(ThresholdWatcher::new(EMITTER, Comparison::Above, 0.5))
+            .unwrap();
+
+        assert!(thresholds.evaluate(&signal_field).is_empty());
+
+        signal_field.set(EMITTER, &center, 0.75);
+        assert_eq!(
+            thresholds.evaluate(&signal_field),
+            vec![SignalThresholdCrossed {
+                watcher: id,
+                emitter: EMITTER,
+                position: center,
+                edge: Edge::Rising,
+                value: 0.75,
+            }]
+        );
+        assert!(thresholds.is_triggered(id, &signal_field, &center));
+
+        // Staying above the level does not fire again
+        signal_field.set(EMITTER, &center, 1.0);
+        assert!(thresholds.evaluate(&signal_field).is_empty());
+
+        signal_field.set(EMITTER, &center, 0.25);
+        let events = thresholds.evaluate(&signal_field);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].edge, Edge::Falling);
+        assert!(!thresholds.is_triggered(id, &signal_field, &center));
+    }
+
+    #[test]
+    fn hysteresis_suppresses_chatter() {
+        let (map_geometry, _, mut signal_field) = map();
+        let center = map_geometry.center();
+        let mut thresholds = SignalThresholds::default();
+        let mut watcher = ThresholdWatcher::new(EMITTER, Comparison::Below, 0.5);
+        watcher.hysteresis = 0.2;
+        watcher.region = Some(vec![center]);
+        thresholds.register(watcher).unwrap();
+
+        signal_field.set(EMITTER, &center, 1.0);
+        assert!(thresholds.evaluate(&signal_field).is_empty());
+
+        let mut edges = Vec::new();
+        for value in [0.45, 0.55, 0.45, 0.65, 0.75, 0.45] {
+            signal_field.set(EMITTER, &center, value);
+            edges.extend(thresholds.evaluate(&signal_field).iter().map(|e| e.edge));
+        }
+        assert_eq!(edges, vec![Edge::Rising, Edge::Falling, Edge::Rising]);
+    }
+
+    #[test]
+    fn only_the_region_and_requested_edges_are_reported() {
+        let (map_geometry, map_positions, mut signal_field) = map();
+        let center = map_geometry.center();
+        let neighbor = map_positions.ring(&center, 1).next().unwrap();
+        let mut thresholds = SignalThresholds::default();
+        let mut watcher = ThresholdWatcher::new(EMITTER, Comparison::Above, 0.5);
+        watcher.edges = Edges::Falling;
+        watcher.region = Some(vec![neighbor, TilePos { x: 1000, y: 0 }]);
+        let id = thresholds.register(watcher).unwrap();
+
+        for position in map_positions.iter_positions() {
+            signal_field.set(EMITTER, position, 1.0);
+        }
+        assert!(thresholds.evaluate(&signal_field).is_empty());
+        assert!(thresholds.is_triggered(id, &signal_field, &neighbor));
+        assert!(!thresholds.is_triggered(id, &signal_field, &center));
+
+        for position in map_positions.iter_positions() {
+            signal_field.set(EMITTER, position, 0.0);
+        }
+        let events = thresholds.evaluate(&signal_field);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].position, neighbor);
+        assert_eq!(events[0].edge, Edge::Falling);
+
+        assert!(thresholds.unregister(id).is_some());
+        assert!(thresholds.get(id).is_none());
+    }
+
+    #[test]
+    fn replacing_the_map_resolves_tiles_again() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 5 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let mut signal_field = SignalField::new(&map_positions);
+        let mut thresholds = SignalThresholds::default();
+        let id = thresholds
+            .register(ThresholdWatcher::new(EMITTER, Comparison::Above, 0.5))
+            .unwrap();
+
+        for position in map_positions.iter_positions() {
+            signal_field.set(EMITTER, position, 1.0);
+        }
+        assert_eq!(
+            thresholds.evaluate(&signal_field).len(),
+            map_positions.n_positions()
+        );
+
+        // Replace the field with one covering a smaller map, as loading a saved game would
+        let (small_geometry, small_positions, mut small_field) = map();
+        let center = small_geometry.center();
+        assert!(!thresholds.is_triggered(id, &small_field, &center));
+        assert!(thresholds.evaluate(&small_field).is_empty());
+
+        small_field.set(EMITTER, &center, 1.0);
+        assert_eq!(
+            thresholds.evaluate(&small_field),
+            vec![SignalThresholdCrossed {
+                watcher: id,
+                emitter: EMITTER,
+                position: center,
+                edge: Edge::Rising,
+                value: 1.0,
+            }]
+        );
+        assert!(thresholds.is_triggered(id, &small_field, &center));
+        assert!(small_positions.n_positions() < map_positions.n_positions());
+    }
+
+    #[test]
+    fn invalid_watchers_are_rejected() {
+        let mut thresholds = SignalThresholds::default();
+        let mut watcher = ThresholdWatcher::new(EMITTER, Comparison::Above, f32::NAN);
+        assert!(matches!(
+            thresholds.register(watcher.clone()),
+            Err(ThresholdWatcherError::InvalidLevel(_))
+        ));
+
+        watcher.level = 0.5;
+        watcher.hysteresis = -0.1;
+        assert_eq!(
+            thresholds.register(watcher),
+            Err(ThresholdWatcherError::InvalidHysteresis(-0.1))
+        );
+        assert_eq!(thresholds.iter().count(), 0);
+    }
+}
@@ -0,0 +1,178 @@
+//! Rules that turn elevation and moisture fields into terrain
+//!
+//! Both fields are normalized to the fraction of the map that lies below each tile,
+//! so thresholds describe how much of the map each biome covers, whatever the noise settings.
+
+use crate::simulation::generation::noise::NoiseConfig;
+use crate::simulation::map::MapPositions;
+use crate::terrain::TerrainType;
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::ops::Range;
+
+/// Assigns `terrain` to tiles whose elevation and moisture both fall within the given ranges
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiomeRule {
+    /// The range of normalized elevations covered by this rule
+    pub elevation: Range<f32>,
+    /// The range of normalized moistures covered by this rule
+    pub moisture: Range<f32>,
+    /// The terrain that tiles matching this rule will have
+    pub terrain: TerrainType,
+}
+
+impl BiomeRule {
+    /// Creates a new [`BiomeRule`] that covers every moisture level
+    pub fn elevation(elevation: Range<f32>, terrain: TerrainType) -> BiomeRule {
+        BiomeRule {
+            elevation,
+            moisture: 0.0..f32::INFINITY,
+            terrain,
+        }
+    }
+
+    /// Does this rule cover the given elevation and moisture?
+    pub fn matches(&self, elevation: f32, moisture: f32) -> bool {
+        self.elevation.contains(&elevation) && self.moisture.contains(&moisture)
+    }
+}
+
+/// An ordered list of [`BiomeRule`]s
+#[derive(Debug, Clone, PartialEq)]
+pub struct BiomeRules {
+    /// The rules to check, in order: the first one that matches is used
+    pub rules: Vec<BiomeRule>,
+    /// The terrain used when no rule matches
+    pub fallback: TerrainType,
+}
+
+impl BiomeRules {
+    /// The terrain of a tile with the given normalized elevation and moisture
+    pub fn terrain(&self, elevation: f32, moisture: f32) -> TerrainType {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(elevation, moisture))
+            .map(|rule| rule.terrain)
+            .unwrap_or(self.fallback)
+    }
+}
+
+/// Ready-made ways to generate terrain, selected by [`GenerationConfig`](super::GenerationConfig)
+#[derive(Debug, Clone, PartialEq)]
+pub enum BiomePreset {
+    /// Chooses each tile's terrain independently, using
+    /// [`GenerationConfig::terrain_weights`](super::GenerationConfig::terrain_weights)
+    Scattered,
+    /// Mostly open plains, broken up by high plateaus, rocky ridges and dry rocky outcrops
+    Temperate,
+    /// Mostly high plateaus, with wide rocky ridges and a few low plains
+    Highlands,
+    /// Open plains with only the occasional hill or rock
+    Lowlands,
+    /// Custom rules
+    Custom(BiomeRules),
+}
+
+impl BiomePreset {
+    /// The rules used by this preset, or `None` if it does not use noise fields
+    pub fn rules(&self) -> Option<BiomeRules> {
+        use TerrainType::*;
+
+        let rules = match self {
+            BiomePreset::Scattered => return None,
+            BiomePreset::Temperate => vec![
+                BiomeRule::elevation(0.86..f32::INFINITY, Rocky),
+                BiomeRule::elevation(0.64..0.86, High),
+                BiomeRule {
+                    elevation: 0.45..0.64,
+                    moisture: 0.0..0.08,
+                    terrain: Rocky,
+                },
+            ],
+            BiomePreset::Highlands => vec![
+                BiomeRule::elevation(0.75..f32::INFINITY, Rocky),
+                BiomeRule::elevation(0.3..0.75, High),
+            ],
+            BiomePreset::Lowlands => vec![
+                BiomeRule::elevation(0.96..f32::INFINITY, Rocky),
+                BiomeRule::elevation(0.88..0.96, High),
+            ],
+            BiomePreset::Custom(rules) => return Some(rules.clone()),
+        };
+
+        Some(BiomeRules {
+            rules,
+            fallback: Plain,
+        })
+    }
+}
+
+/// Samples a noise field at every position on the map, normalized to the fraction of
+/// positions with a lower value.
+///
+/// The result is in the same order as [`MapPositions::iter_positions`], and every value is in `[0, 1)`.
+pub fn normalized_field(map_positions: &MapPositions, config: &NoiseConfig, seed: u64) -> Vec<f32> {
+    let noise = config.build(seed);
+    let raw: Vec<f64> = map_positions
+        .iter_positions()
+        .map(|position| config.sample_tile(&noise, position))
+        .collect();
+
+    // Ties are broken by position, so that the result never depends on sort stability
+    let mut ranked: Vec<usize> = (0..raw.len()).collect();
+    ranked.sort_by(|a, b| raw[*a].total_cmp(&raw[*b]).then(a.cmp(b)));
+
+    let mut normalized = vec![0.0; raw.len()];
+    for (rank, index) in ranked.into_iter().enumerate() {
+        normalized[index] = rank as f32 / raw.len() as f32;
+    }
+
+    normalized
+}
+
+/// Chooses the terrain of every position on the map from elevation and moisture fields
+pub fn biome_terrain(
+    map_positions: &MapPositions,
+    rules: &BiomeRules,
+    elevation: &NoiseConfig,
+    moisture: &NoiseConfig,
+    seeds: (u64, u64),
+) -> Vec<(TilePos, TerrainType)> {
+    let elevation = normalized_field(map_positions, elevation, seeds.0);
+    let moisture = normalized_field(map_positions, moisture, seeds.1);
+
+    map_positions
+        .iter_positions()
+        .zip(elevation.into_iter().zip(moisture))
+        .map(|(position, (elevation, moisture))| (*position, rules.terrain(elevation, moisture)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::{MapGeometry, MapShape};
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = BiomePreset::Temperate.rules().unwrap();
+
+        assert_eq!(rules.terrain(0.9, 0.5), TerrainType::Rocky);
+        assert_eq!(rules.terrain(0.7, 0.5), TerrainType::High);
+        assert_eq!(rules.terrain(0.5, 0.05), TerrainType::Rocky);
+        assert_eq!(rules.terrain(0.5, 0.5), TerrainType::Plain);
+        assert_eq!(BiomePreset::Scattered.rules(), None);
+    }
+
+    #[test]
+    fn normalized_fields_are_uniform() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 10 });
+        let map_positions = MapPositions::new(&map_geometry);
+
+        let field = normalized_field(&map_positions, &NoiseConfig::default(), 3);
+        assert_eq!(field.len(), map_positions.n_positions());
+
+        let above_threshold = field.iter().filter(|value| **value >= 0.75).count();
+        let expected = map_positions.n_positions() / 4;
+        assert!(above_threshold.abs_diff(expected) <= 1);
+    }
+}
@@ -0,0 +1,526 @@
+//! Hand-authored maps, imported from text or PNG images
+//!
+//! Both formats describe a map row by row, using the same layout as [`MapShape::Rectangle`]:
+//! each row is offset from the one before it by half a tile.
+//!
+//! # Text format
+//!
+//! Lines starting with `//` and blank lines are ignored.
+//! The first remaining line gives the `width` and `height` of the map, separated by whitespace.
+//! Each following line is one row of the map, with one glyph per tile.
+//! Whitespace between glyphs is ignored, so rows can be indented to match their offset on screen.
+//! Rows with fewer than `width` glyphs, and missing rows, are left out of the map.
+//!
+//! | Glyph | Tile                       |
+//! |-------|----------------------------|
+//! | `.`   | [`TerrainType::Plain`]     |
+//! | `^`   | [`TerrainType::High`]      |
+//! | `#`   | [`TerrainType::Rocky`]     |
+//! | `-`   | Not part of the map        |
+//! | `a`   | An ant, on plain terrain   |
+//! | `p`   | An acacia, on plain terrain |
+//! | `f`   | A leuco, on plain terrain  |
+//! | `A`   | An ant, on high terrain    |
+//! | `P`   | An acacia, on high terrain |
+//! | `F`   | A leuco, on high terrain   |
+//!
+//! # Image format
+//!
+//! Each pixel of the image is one tile, with its colour looked up in a [`ColourKey`].
+//! Fully transparent pixels are left out of the map.
+
+use crate::simulation::map::MapShape;
+use crate::terrain::TerrainType;
+use bevy::utils::HashMap;
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::fmt::Display;
+use std::path::Path;
+
+/// The species of an organism placed by an imported map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImportedOrganism {
+    /// An [`AntBundle`](crate::organisms::units::AntBundle)
+    Ant,
+    /// An [`AcaciaBundle`](crate::organisms::sessile::plants::AcaciaBundle)
+    Acacia,
+    /// A [`LeucoBundle`](crate::organisms::sessile::fungi::LeucoBundle)
+    Leuco,
+}
+
+/// The contents of a single tile of an imported map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImportedTile {
+    /// The terrain of this tile
+    pub terrain: TerrainType,
+    /// The organism placed on this tile, if any
+    pub organism: Option<ImportedOrganism>,
+}
+
+impl ImportedTile {
+    /// A tile with the given terrain, and nothing on it
+    pub fn terrain(terrain: TerrainType) -> ImportedTile {
+        ImportedTile {
+            terrain,
+            organism: None,
+        }
+    }
+
+    /// A tile with the given terrain, with an organism on it
+    pub fn organism(terrain: TerrainType, organism: ImportedOrganism) -> ImportedTile {
+        ImportedTile {
+            terrain,
+            organism: Some(organism),
+        }
+    }
+}
+
+/// The tile that `glyph` stands for in the text format,
+/// where `Some(None)` marks tiles that are not part of the map
+fn parse_glyph(glyph: char) -> Option<Option<ImportedTile>> {
+    use ImportedOrganism::*;
+    use TerrainType::*;
+
+    let tile = match glyph {
+        '.' => ImportedTile::terrain(Plain),
+        '^' => ImportedTile::terrain(High),
+        '#' => ImportedTile::terrain(Rocky),
+        'a' => ImportedTile::organism(Plain, Ant),
+        'p' => ImportedTile::organism(Plain, Acacia),
+        'f' => ImportedTile::organism(Plain, Leuco),
+        'A' => ImportedTile::organism(High, Ant),
+        'P' => ImportedTile::organism(High, Acacia),
+        'F' => ImportedTile::organism(High, Leuco),
+        '-' => return Some(None),
+        _ => return None,
+    };
+
+    Some(Some(tile))
+}
+
+/// Maps the colours of an image onto tiles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColourKey {
+    /// The tile that each RGB colour stands for, where `None` marks tiles that are not part of the map
+    colours: HashMap<[u8; 3], Option<ImportedTile>>,
+}
+
+impl ColourKey {
+    /// Creates an empty [`ColourKey`]: only fully transparent pixels can be imported
+    pub fn empty() -> ColourKey {
+        ColourKey {
+            colours: HashMap::new(),
+        }
+    }
+
+    /// Sets the tile that `colour` stands for, or marks it as not part of the map if `tile` is `None`
+    pub fn insert(&mut self, colour: [u8; 3], tile: Option<ImportedTile>) {
+        self.colours.insert(colour, tile);
+    }
+
+    /// The tile that `colour` stands for, or `None` if the colour is not in the key
+    pub fn get(&self, colour: &[u8; 3]) -> Option<Option<ImportedTile>> {
+        self.colours.get(colour).copied()
+    }
+}
+
+impl Default for ColourKey {
+    fn default() -> Self {
+        use ImportedOrganism::*;
+        use TerrainType::*;
+
+        let mut key = ColourKey::empty();
+        key.insert([255, 0, 255], None);
+        key.insert([120, 180, 70], Some(ImportedTile::terrain(Plain)));
+        key.insert([160, 120, 80], Some(ImportedTile::terrain(High)));
+        key.insert([128, 128, 128], Some(ImportedTile::terrain(Rocky)));
+        key.insert([0, 0, 0], Some(ImportedTile::organism(Plain, Ant)));
+        key.insert([0, 100, 0], Some(ImportedTile::organism(Plain, Acacia)));
+        key.insert([255, 255, 255], Some(ImportedTile::organism(Plain, Leuco)));
+        key
+    }
+}
+
+/// The largest number of tiles that a map in the text format may have
+const MAX_TILES: u32 = 1 << 24;
+
+/// Failed to import a map.
+#[derive(Debug)]
+pub enum ImportError {
+    /// The map file could not be read.
+    Io(std::io::Error),
+    /// The image could not be decoded.
+    Png(png::DecodingError),
+    /// The text does not start with the size of the map.
+    MissingSize,
+    /// The size of the map could not be parsed, or covers more than [`MAX_TILES`] tiles.
+    InvalidSize {
+        /// The line containing the size, starting from 1.
+        line: usize,
+    },
+    /// The text contains a glyph that does not stand for any tile.
+    UnknownGlyph {
+        /// The unknown glyph.
+        glyph: char,
+        /// The line containing the glyph, starting from 1.
+        line: usize,
+        /// The column of the glyph within its line, starting from 1.
+        column: usize,
+    },
+    /// The text places a tile beyond the width or height of the map.
+    OutOfBounds {
+        /// The line containing the tile, starting from 1.
+        line: usize,
+        /// The column of the tile within its line, starting from 1.
+        column: usize,
+    },
+    /// The image contains a colour that is not in the [`ColourKey`].
+    UnknownColour {
+        /// The unknown colour.
+        colour: [u8; 3],
+        /// The column of the pixel, starting from 0.
+        x: u32,
+        /// The row of the pixel, starting from 0.
+        y: u32,
+    },
+    /// The map does not contain any tiles.
+    Empty,
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Io(error) => write!(f, "could not read map file: {error}"),
+            ImportError::Png(error) => write!(f, "could not decode map image: {error}"),
+            ImportError::MissingSize => {
+                write!(f, "the map does not start with its width and height")
+            }
+            ImportError::InvalidSize { line } => {
+                write!(
+                    f,
+                    "line {line}: expected the width and height of a map with at most {MAX_TILES} tiles"
+                )
+            }
+            ImportError::UnknownGlyph {
+                glyph,
+                line,
+                column,
+            } => write!(f, "line {line}, column {column}: unknown glyph {glyph:?}"),
+            ImportError::OutOfBounds { line, column } => write!(
+                f,
+                "line {line}, column {column}: tile lies outside of the map's width or height"
+            ),
+            ImportError::UnknownColour { colour, x, y } => write!(
+                f,
+                "pixel ({x}, {y}): colour {colour:?} is not in the colour key"
+            ),
+            ImportError::Empty => write!(f, "the map does not contain any tiles"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<std::io::Error> for ImportError {
+    fn from(error: std::io::Error) -> Self {
+        ImportError::Io(error)
+    }
+}
+
+impl From<png::DecodingError> for ImportError {
+    fn from(error: png::DecodingError) -> Self {
+        ImportError::Png(error)
+    }
+}
+
+/// A hand-authored map, which replaces generated terrain and organisms
+///
+/// Set [`GenerationConfig::map_import`](super::GenerationConfig::map_import) to use it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedMap {
+    /// The number of tiles in each row
+    width: u32,
+    /// The number of rows
+    height: u32,
+    /// The contents of each tile, stored row by row, where `None` marks tiles that are not part of the map
+    cells: Vec<Option<ImportedTile>>,
+}
+
+impl ImportedMap {
+    /// Creates a new [`ImportedMap`] from its cells, stored row by row
+    fn new(width: u32, height: u32, cells: Vec<Option<ImportedTile>>) -> Result<Self, ImportError> {
+        if cells.iter().all(Option::is_none) {
+            return Err(ImportError::Empty);
+        }
+
+        Ok(ImportedMap {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    /// Reads a map from a file, as an image if it has a `png` extension, or as text otherwise
+    pub fn load(path: impl AsRef<Path>, colour_key: &ColourKey) -> Result<Self, ImportError> {
+        let path = path.as_ref();
+        let is_png = path
+            .extension()
+            .map(|extension| extension.eq_ignore_ascii_case("png"))
+            .unwrap_or_default();
+
+        if is_png {
+            ImportedMap::from_png(&std::fs::read(path)?, colour_key)
+        } else {
+            ImportedMap::from_text(&std::fs::read_to_string(path)?)
+        }
+    }
+
+    /// Parses a map from the text format described in the [module documentation](self)
+    pub fn from_text(text: &str) -> Result<Self, ImportError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line))
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with("//"));
+
+        let (size_line, size) = lines.next().ok_or(ImportError::MissingSize)?;
+        let size: Vec<u32> = size
+            .split_whitespace()
+            .map(|value| value.parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| ImportError::InvalidSize { line: size_line })?;
+        let (width, height) = match size[..] {
+            [width, height] => (width, height),
+            _ => return Err(ImportError::InvalidSize { line: size_line }),
+        };
+
+        // Reject sizes that would overflow, or allocate far more tiles than any map could use
+        let n_tiles = width
+            .checked_mul(height)
+            .filter(|n_tiles| *n_tiles <= MAX_TILES)
+            .ok_or(ImportError::InvalidSize { line: size_line })?;
+
+        let mut cells = vec![None; n_tiles as usize];
+        for (row, (line_number, line)) in lines.enumerate() {
+            let glyphs = line
+                .chars()
+                .enumerate()
+                .filter(|(_, glyph)| !glyph.is_whitespace());
+
+            for (column, (char_index, glyph)) in glyphs.enumerate() {
+                let position = (line_number, char_index + 1);
+                let tile = parse_glyph(glyph).ok_or(ImportError::UnknownGlyph {
+                    glyph,
+                    line: position.0,
+                    column: position.1,
+                })?;
+
+                if row >= height as usize || column >= width as usize {
+                    return Err(ImportError::OutOfBounds {
+                        line: position.0,
+                        column: position.1,
+                    });
+                }
+                cells[row * width as usize + column] = tile;
+            }
+        }
+
+        ImportedMap::new(width, height, cells)
+    }
+
+    /// Decodes a map from the bytes of a PNG image, looking up the colour of each pixel in `colour_key`
+    pub fn from_png(bytes: &[u8], colour_key: &ColourKey) -> Result<Self, ImportError> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buffer)?;
+
+        // Expanded images only contain 8-bit grayscale or RGB channels, with or without alpha
+        let to_rgba = |pixel: &[u8]| -> [u8; 4] {
+            match frame.color_type {
+                png::ColorType::Grayscale => [pixel[0], pixel[0], pixel[0], 255],
+                png::ColorType::GrayscaleAlpha => [pixel[0], pixel[0], pixel[0], pixel[1]],
+                png::ColorType::Rgb => [pixel[0], pixel[1], pixel[2], 255],
+                _ => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            }
+        };
+        let channels = frame.color_type.samples();
+
+        let mut cells = Vec::with_capacity(frame.width as usize * frame.height as usize);
+        for y in 0..frame.height {
+            let row_start = y as usize * frame.line_size;
+            let row = &buffer[row_start..row_start + frame.line_size];
+
+            for (x, pixel) in (0..frame.width).zip(row.chunks_exact(channels)) {
+                let [r, g, b, alpha] = to_rgba(pixel);
+                if alpha == 0 {
+                    cells.push(None);
+                    continue;
+                }
+
+                let colour = [r, g, b];
+                let tile =
+                    colour_key
+                        .get(&colour)
+                        .ok_or(ImportError::UnknownColour { colour, x, y })?;
+                cells.push(tile);
+            }
+        }
+
+        ImportedMap::new(frame.width, frame.height, cells)
+    }
+
+    /// The shape of the imported map
+    pub fn shape(&self) -> MapShape {
+        if self.cells.iter().all(Option::is_some) {
+            MapShape::Rectangle {
+                width: self.width,
+                height: self.height,
+            }
+        } else {
+            MapShape::Mask {
+                width: self.width,
+                height: self.height,
+                cells: self.cells.iter().map(Option::is_some).collect(),
+            }
+        }
+    }
+
+    /// Iterate over the position and contents of every tile of the map
+    pub fn tiles(&self) -> impl Iterator<Item = (TilePos, &ImportedTile)> + '_ {
+        self.cells.iter().enumerate().filter_map(|(index, tile)| {
+            let column = index as u32 % self.width;
+            let row = index as u32 / self.width;
+            let position = MapShape::rectangle_tile_pos(column, row, self.height);
+            tile.as_ref().map(|tile| (position, tile))
+        })
+    }
+
+    /// The terrain of every tile of the map
+    pub fn terrain(&self) -> impl Iterator<Item = (TilePos, TerrainType)> + '_ {
+        self.tiles()
+            .map(|(position, tile)| (position, tile.terrain))
+    }
+
+    /// The organisms placed on the map, and their positions
+    pub fn organisms(&self) -> impl Iterator<Item = (TilePos, ImportedOrganism)> + '_ {
+        self.tiles()
+            .filter_map(|(position, tile)| tile.organism.map(|organism| (position, organism)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::{MapGeometry, MapPositions};
+
+    /// A small map, with a hole in its top left corner
+    const SCENARIO: &str = "
+        // A rocky ridge, with some ants
+        4 3
+        - . # .
+         a ^ # F
+        . p . f
+    ";
+
+    #[test]
+    fn text_maps_are_imported() {
+        let imported = ImportedMap::from_text(SCENARIO).unwrap();
+        let map_geometry = MapGeometry::new(imported.shape());
+        let map_positions = MapPositions::new(&map_geometry);
+
+        assert_eq!(map_positions.n_positions(), 11);
+        assert_eq!(imported.terrain().count(), 11);
+        assert!(imported
+            .terrain()
+            .all(|(position, _)| map_geometry.check_inclusion(&position)));
+
+        let organisms: Vec<ImportedOrganism> =
+            imported.organisms().map(|(_, organism)| organism).collect();
+        use ImportedOrganism::*;
+        assert_eq!(organisms, [Ant, Leuco, Acacia, Leuco]);
+
+        let rocky = imported
+            .terrain()
+            .filter(|(_, terrain)| *terrain == TerrainType::Rocky)
+            .count();
+        assert_eq!(rocky, 2);
+    }
+
+    #[test]
+    fn text_errors_are_located() {
+        assert!(matches!(
+            ImportedMap::from_text("2 2\n..\n.x"),
+            Err(ImportError::UnknownGlyph {
+                glyph: 'x',
+                line: 3,
+                column: 2
+            })
+        ));
+        assert!(matches!(
+            ImportedMap::from_text("2 2\n. . .\n.."),
+            Err(ImportError::OutOfBounds { line: 2, column: 5 })
+        ));
+        assert!(matches!(
+            ImportedMap::from_text("2 1\n..\n.."),
+            Err(ImportError::OutOfBounds { line: 3, column: 1 })
+        ));
+        assert!(matches!(
+            ImportedMap::from_text("two by two"),
+            Err(ImportError::InvalidSize { line: 1 })
+        ));
+        assert!(matches!(
+            ImportedMap::from_text("4294967295 2\n.."),
+            Err(ImportError::InvalidSize { line: 1 })
+        ));
+        assert!(matches!(
+            ImportedMap::from_text("// Far too big\n65536 65536\n.."),
+            Err(ImportError::InvalidSize { line: 2 })
+        ));
+        assert!(matches!(
+            ImportedMap::from_text("// Nothing here"),
+            Err(ImportError::MissingSize)
+        ));
+        assert!(matches!(
+            ImportedMap::from_text("2 2\n--"),
+            Err(ImportError::Empty)
+        ));
+    }
+
+    /// Encodes RGBA `pixels` as a PNG image with the given width and height
+    fn encode_png(width: u32, height: u32, pixels: &[[u8; 4]]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&pixels.concat()).unwrap();
+        writer.finish().unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn images_are_imported() {
+        let plain = [120, 180, 70, 255];
+        let rocky = [128, 128, 128, 255];
+        let ant = [0, 0, 0, 255];
+        let transparent = [0, 0, 0, 0];
+        let colour_key = ColourKey::default();
+
+        let image = encode_png(3, 2, &[plain, rocky, transparent, ant, plain, plain]);
+        let imported = ImportedMap::from_png(&image, &colour_key).unwrap();
+        let text = ImportedMap::from_text("3 2\n.#-\na..").unwrap();
+        assert_eq!(imported, text);
+
+        let image = encode_png(1, 1, &[[1, 2, 3, 255]]);
+        assert!(matches!(
+            ImportedMap::from_png(&image, &colour_key),
+            Err(ImportError::UnknownColour {
+                colour: [1, 2, 3],
+                x: 0,
+                y: 0
+            })
+        ));
+    }
+}
@@ -0,0 +1,409 @@
+//! Generating starting terrain and organisms
+use crate::enum_iter::IterableEnum;
+use crate::organisms::sessile::fungi::LeucoBundle;
+use crate::organisms::sessile::plants::AcaciaBundle;
+use crate::organisms::units::AntBundle;
+use crate::simulation::generation::biomes::{biome_terrain, BiomePreset};
+use crate::simulation::generation::import::{ImportedMap, ImportedOrganism};
+use crate::simulation::generation::noise::{NoiseConfig, NoiseKind};
+use crate::simulation::generation::placement::{place_organisms, PlacementRules};
+use crate::simulation::map::resources::MapResource;
+use crate::simulation::map::{
+    configure_map_geometry, create_map_positions, MapPositions, MapShape,
+};
+use crate::simulation::pathfinding::Impassable;
+use crate::simulation::rng::{RngStream, SimulationRng};
+use crate::terrain::entity_map::TerrainEntityMap;
+use crate::terrain::TerrainType;
+use bevy::app::{App, Plugin, StartupStage};
+use bevy::ecs::prelude::*;
+use bevy::log::{error, info};
+use bevy::utils::HashMap;
+use bevy_ecs_tilemap::tiles::TilePos;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::fmt::Display;
+
+pub mod biomes;
+pub mod import;
+pub mod noise;
+pub mod placement;
+
+/// Controls world generation strategy
+#[derive(Resource, Clone)]
+pub struct GenerationConfig {
+    /// Seed for the [`SimulationRng`].
+    ///
+    /// The same seed (and the same config) will always produce the same world.
+    pub seed: u64,
+    /// Shape and size of the map.
+    pub map_shape: MapShape,
+    /// Initial number of ants.
+    pub n_ant: usize,
+    /// Initial number of plants.
+    pub n_plant: usize,
+    /// Initial number of fungi.
+    pub n_fungi: usize,
+    /// Relative probability of generating tiles of each terrain type.
+    ///
+    /// Only used by [`BiomePreset::Scattered`].
+    pub terrain_weights: HashMap<TerrainType, f32>,
+    /// How terrain is chosen from the elevation and moisture fields.
+    pub biomes: BiomePreset,
+    /// Settings for the elevation field.
+    pub elevation_noise: NoiseConfig,
+    /// Settings for the moisture field.
+    pub moisture_noise: NoiseConfig,
+    /// A hand-authored map to use instead of generating one.
+    ///
+    /// When set, the imported map determines the shape of the map, its terrain and its starting
+    /// organisms: the other settings are ignored, except for the seed.
+    pub map_import: Option<ImportedMap>,
+    /// Constraints on where starting organisms are placed.
+    pub placement: PlacementRules,
+}
+
+impl GenerationConfig {
+    /// The seed used by the default generation config
+    pub const SEED: u64 = 0x0E3E_26E2_CE00;
+
+    /// The number of tiles from the center of the map to the edge, in the default generation config
+    pub const MAP_RADIUS: u32 = 20;
+
+    /// The number of ants in the default generation config
+    pub const N_ANT: usize = 5;
+    /// The number of plants in the default generation config
+    pub const N_PLANT: usize = 7;
+    /// The number of fungi in the default generation config
+    pub const N_FUNGI: usize = 4;
+
+    /// The choice weight for plain terrain in default generation config
+    pub const TERRAIN_WEIGHT_PLAIN: f32 = 1.0;
+    /// The choice weight for high terrain in default generation config
+    pub const TERRAIN_WEIGHT_HIGH: f32 = 0.3;
+    /// The choice weight for impassable terrain in default generation config
+    pub const TERRAIN_WEIGHT_ROCKY: f32 = 0.2;
+
+    /// The moisture field used by the default generation config
+    pub fn default_moisture_noise() -> NoiseConfig {
+        NoiseConfig {
+            kind: NoiseKind::Value,
+            scale: 18.0,
+            octaves: 3,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for GenerationConfig {
+    fn default() -> GenerationConfig {
+        let mut terrain_weights: HashMap<TerrainType, f32> = HashMap::new();
+        terrain_weights.insert(TerrainType::Plain, GenerationConfig::TERRAIN_WEIGHT_PLAIN);
+        terrain_weights.insert(TerrainType::High, GenerationConfig::TERRAIN_WEIGHT_HIGH);
+        terrain_weights.insert(TerrainType::Rocky, GenerationConfig::TERRAIN_WEIGHT_ROCKY);
+
+        GenerationConfig {
+            seed: GenerationConfig::SEED,
+            map_shape: MapShape::Hexagon {
+                radius: GenerationConfig::MAP_RADIUS,
+            },
+            n_ant: GenerationConfig::N_ANT,
+            n_plant: GenerationConfig::N_PLANT,
+            n_fungi: GenerationConfig::N_FUNGI,
+            terrain_weights,
+            biomes: BiomePreset::Temperate,
+            elevation_noise: NoiseConfig::default(),
+            moisture_noise: GenerationConfig::default_moisture_noise(),
+            map_import: None,
+            placement: PlacementRules::default(),
+        }
+    }
+}
+
+/// Generate the world.
+pub struct GenerationPlugin {
+    /// Configuration settings for world generation
+    pub config: GenerationConfig,
+}
+
+/// Stage labels required to organize our startup systems.
+///
+/// We must use stage labels, as we need commands to be flushed between each stage.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub enum GenerationStage {
+    /// Creates and inserts the [`MapGeometry`](crate::simulation::map::MapGeometry) resource based on the [`GenerationConfig`] resource
+    ///
+    /// Systems:
+    /// * [`configure_map_geometry`]
+    Configuration,
+    /// Creates and inserts the [`MapPositions`] resource.
+    ///
+    /// Systems:
+    /// * [`create_map_positions`]
+    PositionCaching,
+    /// Generates and inserts terrain entities based on the [`GenerationConfig`] resource
+    ///
+    /// Systems:
+    /// * [`generate_terrain`]
+    TerrainGeneration,
+    /// Generates starting organisms, based on [`GenerationConfig`] resource, with random positions
+    ///
+    /// Systems:
+    /// * [`generate_organisms`]
+    OrganismGeneration,
+}
+
+impl Plugin for GenerationPlugin {
+    fn build(&self, app: &mut App) {
+        info!("Building Generation plugin...");
+        app.insert_resource(self.config.clone())
+            .insert_resource(SimulationRng::new(self.config.seed))
+            .add_startup_stage_before(
+                StartupStage::Startup,
+                GenerationStage::OrganismGeneration,
+                SystemStage::parallel(),
+            )
+            .add_startup_stage_before(
+                GenerationStage::OrganismGeneration,
+                GenerationStage::TerrainGeneration,
+                SystemStage::parallel(),
+            )
+            .add_startup_stage_before(
+                GenerationStage::TerrainGeneration,
+                GenerationStage::PositionCaching,
+                SystemStage::parallel(),
+            )
+            .add_startup_stage_before(
+                GenerationStage::PositionCaching,
+                GenerationStage::Configuration,
+                SystemStage::parallel(),
+            )
+            .add_startup_system_to_stage(GenerationStage::Configuration, configure_map_geometry)
+            .add_startup_system_to_stage(GenerationStage::PositionCaching, create_map_positions)
+            .add_startup_system_to_stage(GenerationStage::TerrainGeneration, generate_terrain)
+            .add_startup_system_to_stage(
+                GenerationStage::OrganismGeneration,
+                generate_organisms.pipe(report_generation_errors),
+            );
+    }
+}
+
+/// Chooses the terrain of every position on the map according to [`GenerationConfig`].
+///
+/// Positions are returned in the same order as [`MapPositions::iter_positions`].
+pub fn choose_terrain<R: Rng + ?Sized>(
+    config: &GenerationConfig,
+    map_positions: &MapPositions,
+    rng: &mut R,
+) -> Vec<(TilePos, TerrainType)> {
+    if let Some(imported) = &config.map_import {
+        return imported.terrain().collect();
+    }
+
+    match config.biomes.rules() {
+        Some(rules) => biome_terrain(
+            map_positions,
+            &rules,
+            &config.elevation_noise,
+            &config.moisture_noise,
+            (rng.gen(), rng.gen()),
+        ),
+        None => {
+            let terrain_variants = TerrainType::variants().collect::<Vec<TerrainType>>();
+            let terrain_weights = &config.terrain_weights;
+
+            map_positions
+                .iter_positions()
+                .map(|position| {
+                    let terrain: TerrainType = terrain_variants
+                        .choose_weighted(&mut *rng, |terrain_type| {
+                            terrain_weights
+                                .get(terrain_type)
+                                .copied()
+                                .unwrap_or_default()
+                        })
+                        .copied()
+                        .unwrap();
+                    (*position, terrain)
+                })
+                .collect()
+        }
+    }
+}
+
+/// Creates the world according to [`GenerationConfig`].
+pub fn generate_terrain(
+    mut commands: Commands,
+    config: Res<GenerationConfig>,
+    map_positions: Res<MapPositions>,
+    mut rng: ResMut<SimulationRng>,
+) {
+    info!("Generating terrain...");
+    let rng = rng.stream(RngStream::Terrain);
+
+    let entity_data = choose_terrain(&config, &map_positions, rng)
+        .into_iter()
+        .map(|(position, terrain)| (position, terrain.instantiate(&mut commands, &position)));
+
+    let terrain_entities = TerrainEntityMap {
+        inner: MapResource::new(&map_positions, entity_data),
+    };
+    commands.insert_resource(terrain_entities)
+}
+
+/// The world could not be generated from the [`GenerationConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GenerationError {
+    /// There are fewer tiles that organisms may start on than there are starting organisms.
+    NotEnoughTiles {
+        /// The number of starting organisms.
+        needed: usize,
+        /// The number of tiles that organisms may start on.
+        available: usize,
+    },
+    /// The starting organisms could not all be placed without breaking the [`PlacementRules`].
+    Unsatisfiable {
+        /// The kind of organism that could not be placed.
+        organism: &'static str,
+        /// The number of organisms of this kind that were placed before getting stuck.
+        placed: usize,
+        /// The number of organisms of this kind requested.
+        requested: usize,
+    },
+}
+
+impl Display for GenerationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenerationError::NotEnoughTiles { needed, available } => write!(
+                f,
+                "{needed} starting organisms were requested, but only {available} tiles are available"
+            ),
+            GenerationError::Unsatisfiable {
+                organism,
+                placed,
+                requested,
+            } => write!(
+                f,
+                "could only place {placed} of {requested} {organism} without breaking the placement rules"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GenerationError {}
+
+/// Records that the world could not be generated.
+///
+/// Inserted as a resource when [`generate_organisms`] fails.
+#[derive(Resource, Debug, Clone)]
+pub struct GenerationFailed(pub GenerationError);
+
+/// Create starting organisms according to [`GenerationConfig`], placing them on passable tiles
+/// according to its [`PlacementRules`].
+///
+/// If a map was imported, its organisms are placed instead.
+pub fn generate_organisms(
+    mut commands: Commands,
+    config: Res<GenerationConfig>,
+    map_positions: Res<MapPositions>,
+    passable_tiles: Query<(&TilePos, &TerrainType), Without<Impassable>>,
+    mut rng: ResMut<SimulationRng>,
+) -> Result<(), GenerationError> {
+    info!("Generating organisms...");
+    if let Some(imported) = &config.map_import {
+        for (position, organism) in imported.organisms() {
+            match organism {
+                ImportedOrganism::Ant => commands.spawn(AntBundle::new(position)),
+                ImportedOrganism::Acacia => commands.spawn(AcaciaBundle::new(position)),
+                ImportedOrganism::Leuco => commands.spawn(LeucoBundle::new(position)),
+            };
+        }
+        return Ok(());
+    }
+
+    let passable: Vec<(TilePos, TerrainType)> = passable_tiles
+        .iter()
+        .map(|(position, terrain)| (*position, *terrain))
+        .collect();
+    let positions = place_organisms(
+        &config,
+        &map_positions,
+        &passable,
+        rng.stream(RngStream::Organisms),
+    )?;
+
+    commands.spawn_batch(positions.ants.into_iter().map(AntBundle::new));
+    commands.spawn_batch(positions.plants.into_iter().map(AcaciaBundle::new));
+    commands.spawn_batch(positions.fungi.into_iter().map(LeucoBundle::new));
+
+    Ok(())
+}
+
+/// Logs any error returned by [`generate_organisms`], and records it in the [`GenerationFailed`] resource.
+pub fn report_generation_errors(
+    In(result): In<Result<(), GenerationError>>,
+    mut commands: Commands,
+) {
+    if let Err(generation_error) = result {
+        error!("Could not generate starting organisms: {generation_error}");
+        commands.insert_resource(GenerationFailed(generation_error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::MapGeometry;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// The fraction of pairs of neighboring tiles that have the same terrain
+    fn coherence(config: &GenerationConfig) -> f32 {
+        let map_geometry = MapGeometry::new(config.map_shape.clone());
+        let map_positions = MapPositions::new(&map_geometry);
+        let terrain = MapResource::new(
+            &map_positions,
+            choose_terrain(
+                config,
+                &map_positions,
+                &mut StdRng::seed_from_u64(config.seed),
+            )
+            .into_iter(),
+        );
+
+        let mut pairs = 0;
+        let mut matching = 0;
+        for position in map_positions.iter_positions() {
+            for neighbor in map_positions.ring(position, 1) {
+                pairs += 1;
+                if terrain.get(position) == terrain.get(&neighbor) {
+                    matching += 1;
+                }
+            }
+        }
+
+        matching as f32 / pairs as f32
+    }
+
+    #[test]
+    fn biomes_are_more_coherent_than_scattered_terrain() {
+        let scattered = coherence(&GenerationConfig {
+            biomes: BiomePreset::Scattered,
+            ..Default::default()
+        });
+
+        for biomes in [
+            BiomePreset::Temperate,
+            BiomePreset::Highlands,
+            BiomePreset::Lowlands,
+        ] {
+            let coherent = coherence(&GenerationConfig {
+                biomes,
+                ..Default::default()
+            });
+            assert!(coherent > 0.7);
+            assert!(coherent > scattered);
+        }
+    }
+}
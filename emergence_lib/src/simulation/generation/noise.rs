@@ -0,0 +1,321 @@
+//! Coherent noise, used to generate fields that vary smoothly across the map
+//!
+//! Every noise source is fully determined by its seed, so that generation stays reproducible.
+
+use bevy_ecs_tilemap::tiles::TilePos;
+
+/// A source of coherent noise over the plane
+pub trait Noise2d {
+    /// Samples the noise at the point `(x, y)`, returning a value in `[-1, 1]`
+    fn sample(&self, x: f64, y: f64) -> f64;
+}
+
+/// Hashes a pair of lattice coordinates and a seed into a pseudo-random number
+fn hash(x: i64, y: i64, seed: u64) -> u64 {
+    // The finalizer of SplitMix64, which mixes every input bit into every output bit
+    let mut z = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Smoothly eases `t` from 0 to 1, with zero first and second derivatives at both ends
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Noise made by smoothly interpolating between random values at each integer lattice point
+#[derive(Debug, Clone)]
+pub struct ValueNoise {
+    /// Determines the value at each lattice point
+    seed: u64,
+}
+
+impl ValueNoise {
+    /// Creates a new [`ValueNoise`] source with the given `seed`
+    pub fn new(seed: u64) -> ValueNoise {
+        ValueNoise { seed }
+    }
+
+    /// The random value at the lattice point `(x, y)`, in `[-1, 1]`
+    fn lattice_value(&self, x: i64, y: i64) -> f64 {
+        (hash(x, y, self.seed) >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
+
+impl Noise2d for ValueNoise {
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        let x_floor = x.floor();
+        let y_floor = y.floor();
+        let (x0, y0) = (x_floor as i64, y_floor as i64);
+        let t_x = fade(x - x_floor);
+        let t_y = fade(y - y_floor);
+
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let bottom = lerp(
+            self.lattice_value(x0, y0),
+            self.lattice_value(x0 + 1, y0),
+            t_x,
+        );
+        let top = lerp(
+            self.lattice_value(x0, y0 + 1),
+            self.lattice_value(x0 + 1, y0 + 1),
+            t_x,
+        );
+
+        lerp(bottom, top, t_y)
+    }
+}
+
+/// Two-dimensional simplex noise, which has fewer directional artifacts than [`ValueNoise`]
+#[derive(Debug, Clone)]
+pub struct SimplexNoise {
+    /// Determines the gradient at each lattice point
+    seed: u64,
+}
+
+impl SimplexNoise {
+    /// Skews the plane onto the simplex lattice
+    const SKEW: f64 = 0.366_025_403_784_438_6;
+    /// Unskews the simplex lattice back onto the plane
+    const UNSKEW: f64 = 0.211_324_865_405_187_1;
+    /// Scales the sum of the corner contributions to roughly `[-1, 1]`
+    const SCALE: f64 = 70.0;
+
+    /// Creates a new [`SimplexNoise`] source with the given `seed`
+    pub fn new(seed: u64) -> SimplexNoise {
+        SimplexNoise { seed }
+    }
+
+    /// The contribution of the lattice point `(i, j)` to a point offset from it by `(x, y)`
+    fn corner(&self, i: i64, j: i64, x: f64, y: f64) -> f64 {
+        let t = 0.5 - x * x - y * y;
+        if t <= 0.0 {
+            return 0.0;
+        }
+
+        let diagonal = std::f64::consts::FRAC_1_SQRT_2;
+        let (g_x, g_y) = match hash(i, j, self.seed) % 8 {
+            0 => (1.0, 0.0),
+            1 => (-1.0, 0.0),
+            2 => (0.0, 1.0),
+            3 => (0.0, -1.0),
+            4 => (diagonal, diagonal),
+            5 => (-diagonal, diagonal),
+            6 => (diagonal, -diagonal),
+            _ => (-diagonal, -diagonal),
+        };
+
+        t * t * t * t * (g_x * x + g_y * y)
+    }
+}
+
+impl Noise2d for SimplexNoise {
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        let skew = (x + y) * SimplexNoise::SKEW;
+        let i = (x + skew).floor();
+        let j = (y + skew).floor();
+        let unskew = (i + j) * SimplexNoise::UNSKEW;
+        let x0 = x - (i - unskew);
+        let y0 = y - (j - unskew);
+        let (i, j) = (i as i64, j as i64);
+
+        // Which of the two triangles in the skewed cell the point lies in
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let x1 = x0 - i1 as f64 + SimplexNoise::UNSKEW;
+        let y1 = y0 - j1 as f64 + SimplexNoise::UNSKEW;
+        let x2 = x0 - 1.0 + 2.0 * SimplexNoise::UNSKEW;
+        let y2 = y0 - 1.0 + 2.0 * SimplexNoise::UNSKEW;
+
+        let sum = self.corner(i, j, x0, y0)
+            + self.corner(i + i1, j + j1, x1, y1)
+            + self.corner(i + 1, j + 1, x2, y2);
+
+        (SimplexNoise::SCALE * sum).clamp(-1.0, 1.0)
+    }
+}
+
+/// The kinds of noise that can be used to generate a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseKind {
+    /// See [`ValueNoise`]
+    Value,
+    /// See [`SimplexNoise`]
+    Simplex,
+}
+
+/// A basic noise source, chosen at runtime
+#[derive(Debug, Clone)]
+pub enum NoiseSource {
+    /// See [`ValueNoise`]
+    Value(ValueNoise),
+    /// See [`SimplexNoise`]
+    Simplex(SimplexNoise),
+}
+
+impl Noise2d for NoiseSource {
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        match self {
+            NoiseSource::Value(noise) => noise.sample(x, y),
+            NoiseSource::Simplex(noise) => noise.sample(x, y),
+        }
+    }
+}
+
+/// Fractal Brownian motion: sums several octaves of a noise source,
+/// each with a higher frequency and a lower amplitude than the last.
+#[derive(Debug, Clone)]
+pub struct Fbm<N: Noise2d> {
+    /// The noise sampled by each octave
+    source: N,
+    /// The number of octaves to sum
+    octaves: u32,
+    /// How much the frequency is multiplied by in each octave
+    lacunarity: f64,
+    /// How much the amplitude is multiplied by in each octave
+    persistence: f64,
+}
+
+impl<N: Noise2d> Fbm<N> {
+    /// Offsets each octave, so that their lattices do not line up at the origin
+    const OCTAVE_OFFSET: f64 = 19.19;
+
+    /// Creates a new [`Fbm`] from the given noise `source`
+    ///
+    /// At least one octave is always sampled.
+    pub fn new(source: N, octaves: u32, lacunarity: f64, persistence: f64) -> Fbm<N> {
+        Fbm {
+            source,
+            octaves: octaves.max(1),
+            lacunarity,
+            persistence,
+        }
+    }
+}
+
+impl<N: Noise2d> Noise2d for Fbm<N> {
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+
+        for octave in 0..self.octaves {
+            let offset = octave as f64 * Fbm::<N>::OCTAVE_OFFSET;
+            sum += amplitude
+                * self
+                    .source
+                    .sample(x * frequency + offset, y * frequency + offset);
+            total_amplitude += amplitude;
+            amplitude *= self.persistence;
+            frequency *= self.lacunarity;
+        }
+
+        sum / total_amplitude
+    }
+}
+
+/// Settings for generating a noise field across the map
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseConfig {
+    /// The kind of noise sampled by each octave
+    pub kind: NoiseKind,
+    /// The approximate width of features in the field, in tiles
+    pub scale: f64,
+    /// The number of octaves of [`Fbm`] to sum
+    pub octaves: u32,
+    /// How much the frequency is multiplied by in each octave
+    pub lacunarity: f64,
+    /// How much the amplitude is multiplied by in each octave
+    pub persistence: f64,
+}
+
+impl NoiseConfig {
+    /// Creates the noise described by this config, using the given `seed`
+    pub fn build(&self, seed: u64) -> Fbm<NoiseSource> {
+        let source = match self.kind {
+            NoiseKind::Value => NoiseSource::Value(ValueNoise::new(seed)),
+            NoiseKind::Simplex => NoiseSource::Simplex(SimplexNoise::new(seed)),
+        };
+
+        Fbm::new(source, self.octaves, self.lacunarity, self.persistence)
+    }
+
+    /// Samples `noise` (built from this config) at the center of the given tile
+    ///
+    /// Tiles are laid out on the plane as hexes of unit width, so that features have the same
+    /// size in every direction.
+    pub fn sample_tile(&self, noise: &impl Noise2d, tile_pos: &TilePos) -> f64 {
+        let (q, r) = (tile_pos.x as f64, tile_pos.y as f64);
+        let x = q + r / 2.0;
+        let y = r * 3f64.sqrt() / 2.0;
+
+        noise.sample(x / self.scale, y / self.scale)
+    }
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        NoiseConfig {
+            kind: NoiseKind::Simplex,
+            scale: 16.0,
+            octaves: 3,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples `noise` on a grid of points
+    fn grid_samples(noise: &impl Noise2d) -> Vec<f64> {
+        (0..40)
+            .flat_map(|i| (0..40).map(move |j| (i as f64 * 0.37, j as f64 * 0.23)))
+            .map(|(x, y)| noise.sample(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn noise_is_bounded_and_seeded() {
+        let sources = [
+            (
+                NoiseSource::Value(ValueNoise::new(1)),
+                NoiseSource::Value(ValueNoise::new(2)),
+            ),
+            (
+                NoiseSource::Simplex(SimplexNoise::new(1)),
+                NoiseSource::Simplex(SimplexNoise::new(2)),
+            ),
+        ];
+
+        for (noise, other_noise) in sources {
+            let samples = grid_samples(&noise);
+            assert!(samples.iter().all(|value| (-1.0..=1.0).contains(value)));
+            assert_eq!(samples, grid_samples(&noise.clone()));
+            assert_ne!(samples, grid_samples(&other_noise));
+        }
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        for kind in [NoiseKind::Value, NoiseKind::Simplex] {
+            let config = NoiseConfig {
+                kind,
+                ..Default::default()
+            };
+            let noise = config.build(7);
+
+            for i in 0..200 {
+                let x = i as f64 * 0.05;
+                let y = i as f64 * 0.031;
+                let step = noise.sample(x, y) - noise.sample(x + 0.001, y + 0.001);
+                assert!(step.abs() < 0.05, "{kind:?} noise jumped by {step}");
+            }
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Rules for where starting organisms may be placed
+//!
+//! Organisms are placed greedily, one at a time, on a random tile that satisfies every rule.
+//! If this gets stuck, placement is retried a few times before giving up with a [`GenerationError`].
+
+use crate::simulation::generation::{GenerationConfig, GenerationError};
+use crate::simulation::map::geometry::hex_distance;
+use crate::simulation::map::MapPositions;
+use crate::terrain::TerrainType;
+use bevy::utils::HashSet;
+use bevy_ecs_tilemap::tiles::TilePos;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Constraints on where starting organisms may be placed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlacementRules {
+    /// The minimum number of steps between any two starting organisms.
+    ///
+    /// Values below 1 are treated as 1: organisms never share a tile.
+    pub min_spacing: u32,
+    /// If set, every ant starts within this many steps of a fungus.
+    ///
+    /// Ignored if there are no fungi.
+    pub ant_cluster_radius: Option<u32>,
+    /// The types of terrain that organisms may start on.
+    pub allowed_terrain: Vec<TerrainType>,
+    /// Should every organism start in the same connected region of passable tiles?
+    ///
+    /// If so, the region with the most tiles that organisms may start on is used.
+    pub connected_start: bool,
+}
+
+impl Default for PlacementRules {
+    fn default() -> Self {
+        PlacementRules {
+            min_spacing: 1,
+            ant_cluster_radius: None,
+            allowed_terrain: vec![TerrainType::Plain, TerrainType::High],
+            connected_start: true,
+        }
+    }
+}
+
+/// The positions chosen for each kind of starting organism
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StartingPositions {
+    /// The starting position of each ant
+    pub ants: Vec<TilePos>,
+    /// The starting position of each plant
+    pub plants: Vec<TilePos>,
+    /// The starting position of each fungus
+    pub fungi: Vec<TilePos>,
+}
+
+/// The number of times that placement is attempted before giving up
+const PLACEMENT_ATTEMPTS: usize = 16;
+
+/// Chooses a starting position for every organism in `config`, following its [`PlacementRules`].
+///
+/// `passable` contains every passable tile on the map, along with its terrain.
+pub fn place_organisms<R: Rng + ?Sized>(
+    config: &GenerationConfig,
+    map_positions: &MapPositions,
+    passable: &[(TilePos, TerrainType)],
+    rng: &mut R,
+) -> Result<StartingPositions, GenerationError> {
+    let rules = &config.placement;
+
+    // Sort the candidates, so that placement never depends on query iteration order
+    let mut candidates: Vec<TilePos> = passable
+        .iter()
+        .filter(|(_, terrain)| rules.allowed_terrain.contains(terrain))
+        .map(|(position, _)| *position)
+        .collect();
+    candidates.sort_by_key(|position| (position.y, position.x));
+
+    if rules.connected_start {
+        let passable: HashSet<TilePos> = passable.iter().map(|(position, _)| *position).collect();
+        candidates = largest_region(map_positions, &passable, &candidates);
+    }
+
+    let needed = config.n_ant + config.n_plant + config.n_fungi;
+    if candidates.len() < needed {
+        return Err(GenerationError::NotEnoughTiles {
+            needed,
+            available: candidates.len(),
+        });
+    }
+
+    let mut last_error = None;
+    for _ in 0..PLACEMENT_ATTEMPTS {
+        match try_placement(config, &candidates, rng) {
+            Ok(positions) => return Ok(positions),
+            Err(error) => last_error = Some(error),
+        }
+    }
+
+    Err(last_error.unwrap())
+}
+
+/// The `candidates` that lie in the connected region of `passable` tiles which contains the
+/// most candidates
+fn largest_region(
+    map_positions: &MapPositions,
+    passable: &HashSet<TilePos>,
+    candidates: &[TilePos],
+) -> Vec<TilePos> {
+    let mut visited: HashSet<TilePos> = HashSet::new();
+    let mut largest: Vec<TilePos> = Vec::new();
+
+    for start in candidates {
+        if visited.contains(start) {
+            continue;
+        }
+
+        let region: HashSet<TilePos> = map_positions
+            .flood_fill(start, u32::MAX, |position| passable.contains(position))
+            .collect();
+        let region_candidates: Vec<TilePos> = candidates
+            .iter()
+            .filter(|position| region.contains(position))
+            .copied()
+            .collect();
+
+        if region_candidates.len() > largest.len() {
+            largest = region_candidates;
+        }
+        visited.extend(region);
+    }
+
+    largest
+}
+
+/// Makes a single attempt at placing every organism, fungi first so that ants can cluster around them
+fn try_placement<R: Rng + ?Sized>(
+    config: &GenerationConfig,
+    candidates: &[TilePos],
+    rng: &mut R,
+) -> Result<StartingPositions, GenerationError> {
+    let rules = &config.placement;
+    let min_spacing = rules.min_spacing.max(1);
+    let mut placed: Vec<TilePos> = Vec::new();
+    let mut positions = StartingPositions::default();
+
+    let mut place = |name: &'static str,
+                     requested: usize,
+                     near: Option<(&[TilePos], u32)>,
+                     rng: &mut R|
+     -> Result<Vec<TilePos>, GenerationError> {
+        let mut chosen = Vec::with_capacity(requested);
+        for _ in 0..requested {
+            let valid: Vec<TilePos> = candidates
+                .iter()
+                .filter(|candidate| {
+                    placed
+                        .iter()
+                        .all(|other| hex_distance(candidate, other) >= min_spacing)
+                })
+                .filter(|candidate| match near {
+                    Some((anchors, radius)) if !anchors.is_empty() => anchors
+                        .iter()
+                        .any(|anchor| hex_distance(candidate, anchor) <= radius),
+                    _ => true,
+                })
+                .copied()
+                .collect();
+
+            let position = *valid.choose(rng).ok_or(GenerationError::Unsatisfiable {
+                organism: name,
+                placed: chosen.len(),
+                requested,
+            })?;
+            placed.push(position);
+            chosen.push(position);
+        }
+
+        Ok(chosen)
+    };
+
+    positions.fungi = place("fungi", config.n_fungi, None, rng)?;
+    positions.plants = place("plants", config.n_plant, None, rng)?;
+    let near_fungi = rules
+        .ant_cluster_radius
+        .map(|radius| (positions.fungi.as_slice(), radius));
+    positions.ants = place("ants", config.n_ant, near_fungi, rng)?;
+
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::{MapGeometry, MapShape};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Creates a small map where every tile is passable plain terrain, except for a wall of rocks
+    /// that cuts off one corner
+    fn map() -> (MapPositions, Vec<(TilePos, TerrainType)>) {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 4 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let corner = map_positions
+            .ring(&map_geometry.center(), 4)
+            .next()
+            .unwrap();
+        let wall: HashSet<TilePos> = map_positions.ring(&corner, 1).collect();
+
+        let passable = map_positions
+            .iter_positions()
+            .filter(|position| !wall.contains(position))
+            .map(|position| (*position, TerrainType::Plain))
+            .collect();
+        (map_positions, passable)
+    }
+
+    #[test]
+    fn rules_are_followed() {
+        let (map_positions, passable) = map();
+        let config = GenerationConfig {
+            n_ant: 6,
+            n_plant: 3,
+            n_fungi: 2,
+            placement: PlacementRules {
+                min_spacing: 2,
+                ant_cluster_radius: Some(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let positions = place_organisms(
+            &config,
+            &map_positions,
+            &passable,
+            &mut StdRng::seed_from_u64(0),
+        )
+        .unwrap();
+        let all: Vec<TilePos> = [&positions.ants, &positions.plants, &positions.fungi]
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect();
+
+        assert_eq!(all.len(), 11);
+        for (i, a) in all.iter().enumerate() {
+            for b in &all[i + 1..] {
+                assert!(hex_distance(a, b) >= 2);
+            }
+        }
+        for ant in &positions.ants {
+            assert!(positions
+                .fungi
+                .iter()
+                .any(|fungus| hex_distance(ant, fungus) <= 3));
+        }
+
+        // The corner cut off by the wall is not part of the largest connected region
+        let region: HashSet<TilePos> = map_positions
+            .flood_fill(&all[0], u32::MAX, |position| {
+                passable.iter().any(|(passable, _)| passable == position)
+            })
+            .collect();
+        assert!(all.iter().all(|position| region.contains(position)));
+    }
+
+    #[test]
+    fn unsatisfiable_rules_are_errors() {
+        let (map_positions, passable) = map();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let too_many = GenerationConfig {
+            n_ant: 100,
+            ..Default::default()
+        };
+        assert!(matches!(
+            place_organisms(&too_many, &map_positions, &passable, &mut rng),
+            Err(GenerationError::NotEnoughTiles { needed: 111, .. })
+        ));
+
+        let too_spread_out = GenerationConfig {
+            placement: PlacementRules {
+                min_spacing: 5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            place_organisms(&too_spread_out, &map_positions, &passable, &mut rng),
+            Err(GenerationError::Unsatisfiable { .. })
+        ));
+
+        let no_terrain = GenerationConfig {
+            placement: PlacementRules {
+                allowed_terrain: vec![TerrainType::High],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            place_organisms(&no_terrain, &map_positions, &passable, &mut rng),
+            Err(GenerationError::NotEnoughTiles { available: 0, .. })
+        ));
+    }
+}
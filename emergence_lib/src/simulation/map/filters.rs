@@ -10,28 +10,21 @@ pub type MapFilter = MapResource<bool>;
 impl MapFilter {
     /// Create new from an underlying [`MapPositions`] template.
     ///
-    /// This allocates capacity and initializes patches based on the [`MapPositions`] template
-    /// provided, and the specified default value for data.
+    /// Every position in the [`MapPositions`] template starts with the specified default value,
+    /// which is then overwritten by `data`.
     pub fn new_with_default(
         default: bool,
         template: &MapPositions,
         data: impl Iterator<Item = (TilePos, bool)>,
     ) -> MapFilter {
-        let mut storage = MapResource::generate_storage(
+        let mut filter = MapResource::new(
             template,
             template
                 .iter_positions()
                 .map(|position| (*position, default)),
         );
+        filter.update(data);
 
-        let patches = MapResource::generate_patches(&storage, template);
-
-        for (position, value) in data {
-            if let Some(tile_value) = storage.get_mut(&position) {
-                *(tile_value.get_mut()) = value;
-            }
-        }
-
-        MapResource { storage, patches }
+        filter
     }
 }
@@ -0,0 +1,319 @@
+//! Hex-grid geometry of arbitrary size: distances, rings, disks, lines, spirals and flood fills
+//!
+//! When using `HexCoordSystem::Row`, [`TilePos`] is the same as `AxialPos`,
+//! so all of the math here is done directly on axial coordinates.
+//! Every query is bounded by the positions that actually exist in [`MapPositions`].
+
+use crate::simulation::map::MapPositions;
+use bevy::utils::HashSet;
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::collections::VecDeque;
+
+/// The axial offsets `(q, r)` from a hex to each of its six neighbors, in counter-clockwise order.
+const AXIAL_DIRECTIONS: [(i64, i64); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+/// Axial coordinates that may lie outside of the range representable by [`TilePos`].
+type Axial = (i64, i64);
+
+/// Converts a [`TilePos`] into signed axial coordinates.
+#[inline]
+fn to_axial(tile_pos: &TilePos) -> Axial {
+    (tile_pos.x as i64, tile_pos.y as i64)
+}
+
+/// Converts signed axial coordinates into a [`TilePos`], if they are representable as one.
+#[inline]
+fn to_tile_pos((q, r): Axial) -> Option<TilePos> {
+    Some(TilePos {
+        x: u32::try_from(q).ok()?,
+        y: u32::try_from(r).ok()?,
+    })
+}
+
+/// The number of steps between two hexes, ignoring the contents of the map.
+pub fn hex_distance(a: &TilePos, b: &TilePos) -> u32 {
+    let (a_q, a_r) = to_axial(a);
+    let (b_q, b_r) = to_axial(b);
+    let d_q = a_q - b_q;
+    let d_r = a_r - b_r;
+
+    ((d_q.abs() + d_r.abs() + (d_q + d_r).abs()) / 2) as u32
+}
+
+/// The hexes exactly `radius` steps away from `center`, ignoring the bounds of the map.
+///
+/// The ring starts in the [`AXIAL_DIRECTIONS`]`[4]` direction and proceeds counter-clockwise.
+fn unbounded_ring(center: Axial, radius: u32) -> impl Iterator<Item = Axial> {
+    let radius = radius as i64;
+    let (start_q, start_r) = AXIAL_DIRECTIONS[4];
+    let start = (center.0 + start_q * radius, center.1 + start_r * radius);
+
+    // A ring of radius zero is just the center: the walk below would produce nothing
+    let center_only = (radius == 0).then_some(center);
+
+    let walk = (0..6).flat_map(move |side| {
+        // Each side begins at the corner where the previous one ended
+        let corner = AXIAL_DIRECTIONS[..side]
+            .iter()
+            .fold(start, |(q, r), (d_q, d_r)| {
+                (q + d_q * radius, r + d_r * radius)
+            });
+        let (step_q, step_r) = AXIAL_DIRECTIONS[side];
+
+        (0..radius).map(move |step| (corner.0 + step_q * step, corner.1 + step_r * step))
+    });
+
+    center_only.into_iter().chain(walk)
+}
+
+/// Rounds fractional cube coordinates to the nearest hex, returned in axial coordinates.
+fn cube_round(q: f64, r: f64, s: f64) -> Axial {
+    let mut rounded_q = q.round();
+    let mut rounded_r = r.round();
+    let rounded_s = s.round();
+
+    let d_q = (rounded_q - q).abs();
+    let d_r = (rounded_r - r).abs();
+    let d_s = (rounded_s - s).abs();
+
+    if d_q > d_r && d_q > d_s {
+        rounded_q = -rounded_r - rounded_s;
+    } else if d_r > d_s {
+        rounded_r = -rounded_q - rounded_s;
+    }
+
+    (rounded_q as i64, rounded_r as i64)
+}
+
+impl MapPositions {
+    /// Does the given position lie on the map?
+    #[inline]
+    pub fn contains(&self, tile_pos: &TilePos) -> bool {
+        self.index().get(tile_pos).is_some()
+    }
+
+    /// The positions on the map that are exactly `radius` steps away from `center`.
+    ///
+    /// A radius of zero produces only `center`.
+    pub fn ring<'a>(&'a self, center: &TilePos, radius: u32) -> impl Iterator<Item = TilePos> + 'a {
+        unbounded_ring(to_axial(center), radius)
+            .filter_map(to_tile_pos)
+            .filter(|tile_pos| self.contains(tile_pos))
+    }
+
+    /// The positions on the map that are at most `radius` steps away from `center`, row by row.
+    pub fn disk<'a>(&'a self, center: &TilePos, radius: u32) -> impl Iterator<Item = TilePos> + 'a {
+        let (center_q, center_r) = to_axial(center);
+        let radius = radius as i64;
+
+        (-radius..=radius)
+            .flat_map(move |d_r| {
+                let min_d_q = (-radius).max(-d_r - radius);
+                let max_d_q = radius.min(-d_r + radius);
+                (min_d_q..=max_d_q).map(move |d_q| (center_q + d_q, center_r + d_r))
+            })
+            .filter_map(to_tile_pos)
+            .filter(|tile_pos| self.contains(tile_pos))
+    }
+
+    /// The positions on the map that are at most `radius` steps away from `center`,
+    /// spiralling outwards one ring at a time.
+    pub fn spiral<'a>(
+        &'a self,
+        center: &TilePos,
+        radius: u32,
+    ) -> impl Iterator<Item = TilePos> + 'a {
+        let center = *center;
+        (0..=radius).flat_map(move |ring_radius| self.ring(&center, ring_radius))
+    }
+
+    /// The positions on the map along the straight line from `start` to `end`, inclusive.
+    ///
+    /// Consecutive positions along the line are always adjacent, although portions of the line
+    /// which leave the map are skipped.
+    pub fn line<'a>(
+        &'a self,
+        start: &TilePos,
+        end: &TilePos,
+    ) -> impl Iterator<Item = TilePos> + 'a {
+        let n_steps = hex_distance(start, end);
+        let (start_q, start_r) = to_axial(start);
+        let (end_q, end_r) = to_axial(end);
+
+        // Nudging the endpoints keeps points that fall exactly between two hexes
+        // from being rounded inconsistently
+        let start_cube = (
+            start_q as f64 + 1e-6,
+            start_r as f64 + 1e-6,
+            (-start_q - start_r) as f64 - 2e-6,
+        );
+        let end_cube = (
+            end_q as f64 + 1e-6,
+            end_r as f64 + 1e-6,
+            (-end_q - end_r) as f64 - 2e-6,
+        );
+
+        (0..=n_steps)
+            .map(move |step| {
+                let t = if n_steps == 0 {
+                    0.0
+                } else {
+                    step as f64 / n_steps as f64
+                };
+                let lerp = |a: f64, b: f64| a + (b - a) * t;
+
+                cube_round(
+                    lerp(start_cube.0, end_cube.0),
+                    lerp(start_cube.1, end_cube.1),
+                    lerp(start_cube.2, end_cube.2),
+                )
+            })
+            .filter_map(to_tile_pos)
+            .filter(|tile_pos| self.contains(tile_pos))
+    }
+
+    /// The positions that can be reached from `start` in at most `max_steps` steps,
+    /// only moving through positions for which `passable` returns `true`.
+    ///
+    /// Positions are returned in order of the number of steps needed to reach them.
+    /// `start` itself is always included, as long as it lies on the map.
+    pub fn flood_fill<F>(
+        &self,
+        start: &TilePos,
+        max_steps: u32,
+        passable: F,
+    ) -> impl Iterator<Item = TilePos>
+    where
+        F: Fn(&TilePos) -> bool,
+    {
+        let mut reached = Vec::new();
+        let mut visited = HashSet::new();
+        let mut frontier = VecDeque::new();
+
+        if self.contains(start) {
+            visited.insert(*start);
+            frontier.push_back((*start, 0));
+        }
+
+        while let Some((position, steps)) = frontier.pop_front() {
+            reached.push(position);
+            if steps == max_steps {
+                continue;
+            }
+
+            for neighbor in self.get_patch(&position).unwrap().iter() {
+                if passable(neighbor) && visited.insert(*neighbor) {
+                    frontier.push_back((*neighbor, steps + 1));
+                }
+            }
+        }
+
+        reached.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::{MapGeometry, MapShape};
+
+    /// Creates the positions of a map with the given radius
+    fn map(radius: u32) -> (MapGeometry, MapPositions) {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius });
+        let map_positions = MapPositions::new(&map_geometry);
+        (map_geometry, map_positions)
+    }
+
+    #[test]
+    fn neighbors_are_one_step_away() {
+        let (map_geometry, map_positions) = map(3);
+        let center = map_geometry.center();
+
+        for neighbor in map_positions.get_patch(&center).unwrap().iter() {
+            let expected = if *neighbor == center { 0 } else { 1 };
+            assert_eq!(hex_distance(&center, neighbor), expected);
+            assert_eq!(hex_distance(neighbor, &center), expected);
+        }
+    }
+
+    #[test]
+    fn rings_have_six_hexes_per_step() {
+        let (map_geometry, map_positions) = map(5);
+        let center = map_geometry.center();
+
+        assert_eq!(map_positions.ring(&center, 0).collect::<Vec<_>>(), [center]);
+        for radius in 1..=5 {
+            let ring: HashSet<TilePos> = map_positions.ring(&center, radius).collect();
+            assert_eq!(ring.len(), 6 * radius as usize);
+            assert!(ring
+                .iter()
+                .all(|tile_pos| hex_distance(&center, tile_pos) == radius));
+        }
+    }
+
+    #[test]
+    fn disks_and_spirals_agree() {
+        let (map_geometry, map_positions) = map(5);
+        let center = map_geometry.center();
+
+        for radius in 0..=5 {
+            let disk: Vec<TilePos> = map_positions.disk(&center, radius).collect();
+            let spiral: HashSet<TilePos> = map_positions.spiral(&center, radius).collect();
+
+            assert_eq!(disk.len(), 1 + 3 * (radius * (radius + 1)) as usize);
+            assert_eq!(HashSet::from_iter(disk), spiral);
+        }
+    }
+
+    #[test]
+    fn queries_respect_map_bounds() {
+        let (map_geometry, map_positions) = map(3);
+        let center = map_geometry.center();
+
+        // Every position on the map is within 3 steps of the center
+        assert_eq!(map_positions.ring(&center, 4).count(), 0);
+        assert_eq!(
+            map_positions.disk(&center, 10).count(),
+            map_positions.n_positions()
+        );
+
+        let edge = map_positions.ring(&center, 3).next().unwrap();
+        assert!(map_positions.disk(&edge, 1).count() < 7);
+        assert!(map_positions
+            .disk(&edge, 2)
+            .all(|tile_pos| map_positions.contains(&tile_pos)));
+    }
+
+    #[test]
+    fn lines_are_connected() {
+        let (map_geometry, map_positions) = map(5);
+        let center = map_geometry.center();
+
+        for end in map_positions.ring(&center, 5) {
+            let line: Vec<TilePos> = map_positions.line(&center, &end).collect();
+
+            assert_eq!(line.len(), 6);
+            assert_eq!(line.first(), Some(&center));
+            assert_eq!(line.last(), Some(&end));
+            for pair in line.windows(2) {
+                assert_eq!(hex_distance(&pair[0], &pair[1]), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn flood_fill_is_limited_by_steps_and_passability() {
+        let (map_geometry, map_positions) = map(4);
+        let center = map_geometry.center();
+
+        let unblocked: Vec<TilePos> = map_positions.flood_fill(&center, 2, |_| true).collect();
+        assert_eq!(unblocked.len(), 19);
+
+        // Surround the center with a wall: nothing beyond it can be reached
+        let wall: HashSet<TilePos> = map_positions.ring(&center, 1).collect();
+        let walled: Vec<TilePos> = map_positions
+            .flood_fill(&center, 4, |tile_pos| !wall.contains(tile_pos))
+            .collect();
+        assert_eq!(walled, [center]);
+    }
+}
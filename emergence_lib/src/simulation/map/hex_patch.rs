@@ -2,7 +2,6 @@
 
 use crate as emergence_lib;
 use crate::enum_iter::IterableEnum;
-use crate::simulation::map::resources::MapData;
 use crate::simulation::map::MapGeometry;
 use bevy_ecs_tilemap::helpers::hex_grid::axial::AxialPos;
 use bevy_ecs_tilemap::helpers::hex_grid::neighbors::HexRowDirection;
@@ -13,7 +12,7 @@ use rand::Rng;
 use std::fmt::Debug;
 
 /// Enumerates the positions in a 7-tile hex patch (central tile + 6 neighbors)
-#[derive(Debug, Clone, Copy, Hash, IterableEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IterableEnum)]
 pub enum HexPatchLocation {
     /// The central position
     Center,
@@ -230,15 +229,11 @@ impl<T> HexPatch<T> {
     /// `default_none` specifies what boolean value should be associated with the filter if it has
     /// `None` in a given direction. If `default_none` is `true`, then whatever value `self` contains
     /// in that direction will be returned, else `None` will be returned.
-    pub fn apply_filter(
-        &self,
-        filter_patch: &HexPatch<MapData<bool>>,
-        default_none: bool,
-    ) -> HexPatch<&T> {
+    pub fn apply_filter(&self, filter_patch: &HexPatch<&bool>, default_none: bool) -> HexPatch<&T> {
         HexPatch::from_locational_closure(|location| {
             if filter_patch
                 .get(location)
-                .map_or(default_none, |filter_data| *filter_data.read())
+                .map_or(default_none, |filter_data| **filter_data)
             {
                 self.get(location)
             } else {
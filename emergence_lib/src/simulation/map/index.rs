@@ -0,0 +1,174 @@
+//! Dense indexing of the positions that make up the map
+
+use crate::simulation::map::hex_patch::HexPatch;
+use crate::simulation::map::MapGeometry;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+/// Assigns each position on the map a unique index in `0..len`.
+///
+/// This allows data that is tied to the map to be stored in a contiguous [`Vec`], rather than
+/// being looked up through a hash map. Converting a [`TilePos`] into an index is a single lookup
+/// into a table covering the bounding box of the map, and the indices of each position's
+/// [`HexPatch`] are computed once up front.
+#[derive(Debug, Default)]
+pub struct MapIndex {
+    /// The smallest `x` coordinate of any position on the map
+    min_x: u32,
+    /// The smallest `y` coordinate of any position on the map
+    min_y: u32,
+    /// The width of the bounding box of the map
+    width: u32,
+    /// The height of the bounding box of the map
+    height: u32,
+    /// The index of the position at each cell of the bounding box, stored row by row
+    ///
+    /// Cells of the bounding box that are not part of the map are `None`.
+    lookup: Vec<Option<usize>>,
+    /// The position associated with each index
+    positions: Vec<TilePos>,
+    /// The indices of the [`HexPatch`] centered at each index
+    patches: Vec<HexPatch<usize>>,
+}
+
+impl MapIndex {
+    /// Creates the index for the map described by `map_geometry`
+    ///
+    /// Positions are indexed row by row.
+    pub fn new(map_geometry: &MapGeometry) -> MapIndex {
+        MapIndex::from_positions(map_geometry.iter_positions().collect(), map_geometry)
+    }
+
+    /// Creates an index over exactly the supplied `positions`, in the order they were given
+    fn from_positions(positions: Vec<TilePos>, map_geometry: &MapGeometry) -> MapIndex {
+        let (mut min_x, mut min_y) = (u32::MAX, u32::MAX);
+        let (mut max_x, mut max_y) = (0, 0);
+        for position in &positions {
+            min_x = min_x.min(position.x);
+            min_y = min_y.min(position.y);
+            max_x = max_x.max(position.x);
+            max_y = max_y.max(position.y);
+        }
+        // An empty map has an empty bounding box
+        min_x = min_x.min(max_x);
+        min_y = min_y.min(max_y);
+
+        let mut index = MapIndex {
+            min_x,
+            min_y,
+            width: max_x - min_x + 1,
+            height: max_y - min_y + 1,
+            lookup: Vec::new(),
+            patches: Vec::with_capacity(positions.len()),
+            positions,
+        };
+
+        index.lookup = vec![None; (index.width * index.height) as usize];
+        for (i, position) in index.positions.iter().enumerate() {
+            let cell = index.cell(position).unwrap();
+            index.lookup[cell] = Some(i);
+        }
+
+        for position in index.positions.iter() {
+            let patch = HexPatch::generate(position, map_geometry)
+                .and_then(|neighbor| index.get(&neighbor));
+            index.patches.push(patch);
+        }
+
+        index
+    }
+
+    /// The cell of the bounding box that contains `position`, if any
+    #[inline]
+    fn cell(&self, position: &TilePos) -> Option<usize> {
+        let x = position.x.checked_sub(self.min_x)?;
+        let y = position.y.checked_sub(self.min_y)?;
+        (x < self.width && y < self.height).then(|| (y * self.width + x) as usize)
+    }
+
+    /// The index of `position`, or `None` if it does not lie on the map
+    #[inline]
+    pub fn get(&self, position: &TilePos) -> Option<usize> {
+        self.lookup[self.cell(position)?]
+    }
+
+    /// The position associated with `index`
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn position(&self, index: usize) -> TilePos {
+        self.positions[index]
+    }
+
+    /// The position associated with each index, in order
+    #[inline]
+    pub fn positions(&self) -> &[TilePos] {
+        &self.positions
+    }
+
+    /// The indices of the [`HexPatch`] centered at `index`
+    ///
+    /// Neighbors that do not lie on the map are `None`.
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn patch(&self, index: usize) -> &HexPatch<usize> {
+        &self.patches[index]
+    }
+
+    /// The number of positions on the map
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Does this map have no positions at all?
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::hex_patch::HexPatchLocation;
+    use crate::simulation::map::MapShape;
+
+    #[test]
+    fn indices_are_contiguous_and_unique() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 3 });
+        let index = MapIndex::new(&map_geometry);
+
+        assert_eq!(index.len(), map_geometry.n_positions());
+        for (i, position) in index.positions().iter().enumerate() {
+            assert_eq!(index.get(position), Some(i));
+            assert_eq!(index.position(i), *position);
+        }
+    }
+
+    #[test]
+    fn positions_off_the_map_have_no_index() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 2 });
+        let index = MapIndex::new(&map_geometry);
+
+        assert_eq!(index.get(&TilePos { x: 0, y: 0 }), None);
+        assert_eq!(index.get(&TilePos { x: 1000, y: 1000 }), None);
+    }
+
+    #[test]
+    fn patches_point_at_neighbors() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 3 });
+        let index = MapIndex::new(&map_geometry);
+        let center = index.get(&map_geometry.center()).unwrap();
+
+        let patch = index.patch(center);
+        assert_eq!(patch.get(HexPatchLocation::Center), Some(&center));
+        // The central tile is far enough from the edge that all of its neighbors exist
+        assert_eq!(patch.count(), 7);
+
+        for neighbor in patch.iter() {
+            let neighbor_patch = index.patch(*neighbor);
+            assert!(neighbor_patch.iter().any(|i| *i == center));
+        }
+    }
+}
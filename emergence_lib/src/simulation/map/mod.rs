@@ -1,25 +1,101 @@
 //! Manages the game world's grid and data tied to that grid
 
 pub mod filters;
+pub mod geometry;
 pub mod hex_patch;
+pub mod index;
 pub mod resources;
 
 use crate::simulation::generation::GenerationConfig;
+use crate::simulation::map::geometry::hex_distance;
 use crate::simulation::map::hex_patch::HexPatch;
+use crate::simulation::map::index::MapIndex;
 use bevy::ecs::system::Resource;
 use bevy::log::info;
 use bevy::prelude::{Commands, Res};
-use bevy::utils::HashMap;
 use bevy_ecs_tilemap::map::TilemapSize;
-use bevy_ecs_tilemap::prelude::axial::AxialPos;
-use bevy_ecs_tilemap::prelude::generate_hexagon;
 use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// The shape of the game map, and the tiles that make it up.
+///
+/// The rows of [`Rectangle`](MapShape::Rectangle) and [`Mask`](MapShape::Mask) maps are offset
+/// from each other by half a tile, so that they appear rectangular on screen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapShape {
+    /// Every tile within `radius` steps of the central tile.
+    Hexagon {
+        /// The number of tiles from the center of the map to the edge
+        radius: u32,
+    },
+    /// `height` rows of `width` tiles.
+    Rectangle {
+        /// The number of tiles in each row
+        width: u32,
+        /// The number of rows
+        height: u32,
+    },
+    /// `height` rows of `width` tiles, with each row shifted half a tile from the one before it.
+    Parallelogram {
+        /// The number of tiles in each row
+        width: u32,
+        /// The number of rows
+        height: u32,
+    },
+    /// An arbitrary subset of a [`Rectangle`](MapShape::Rectangle).
+    Mask {
+        /// The number of tiles in each row of the enclosing rectangle
+        width: u32,
+        /// The number of rows of the enclosing rectangle
+        height: u32,
+        /// Whether each tile of the enclosing rectangle is part of the map, stored row by row
+        ///
+        /// Missing entries are treated as `false`.
+        cells: Vec<bool>,
+    },
+}
+
+impl Default for MapShape {
+    fn default() -> Self {
+        MapShape::Hexagon {
+            radius: GenerationConfig::MAP_RADIUS,
+        }
+    }
+}
+
+impl MapShape {
+    /// The number of tiles that the rows of rectangular maps are shifted by,
+    /// so that every tile has a non-negative `x` coordinate.
+    const fn row_shift(height: u32) -> u32 {
+        height.saturating_sub(1) / 2
+    }
+
+    /// Converts the `column` and `row` of a rectangular map into a [`TilePos`].
+    pub const fn rectangle_tile_pos(column: u32, row: u32, height: u32) -> TilePos {
+        TilePos {
+            x: MapShape::row_shift(height) + column - row / 2,
+            y: row,
+        }
+    }
+
+    /// Converts a [`TilePos`] into the `(column, row)` of a rectangular map, if it lies within
+    /// the map's bounding box.
+    fn rectangle_cell(tile_pos: &TilePos, width: u32, height: u32) -> Option<(u32, u32)> {
+        let row = tile_pos.y;
+        let column = tile_pos
+            .x
+            .checked_add(row / 2)?
+            .checked_sub(MapShape::row_shift(height))?;
+        (row < height && column < width).then_some((column, row))
+    }
+}
 
 /// Resource that stores information regarding the size of the game map.
-#[derive(Resource, Debug)]
+#[derive(Resource, Debug, Clone)]
 pub struct MapGeometry {
-    /// The radius, in tiles, of the map
-    radius: u32,
+    /// The shape of the map
+    shape: MapShape,
     /// The location of the central tile
     center: TilePos,
     /// The [`TilemapSize`] of the map
@@ -28,29 +104,82 @@ pub struct MapGeometry {
 
 impl Default for MapGeometry {
     fn default() -> Self {
-        MapGeometry::new(GenerationConfig::MAP_RADIUS)
+        MapGeometry::new(MapShape::default())
     }
 }
 
 impl MapGeometry {
-    /// Constructs a new [`MapGeometry`] for a `radius`.
-    pub const fn new(radius: u32) -> Self {
-        MapGeometry {
-            radius,
-            center: TilePos {
-                x: radius + 1,
-                y: radius + 1,
-            },
-            size: TilemapSize {
-                x: 2 * radius + 1,
-                y: 2 * radius + 1,
-            },
+    /// Constructs a new [`MapGeometry`] for the given `shape`.
+    pub fn new(shape: MapShape) -> Self {
+        let (center, size) = match shape {
+            MapShape::Hexagon { radius } => (
+                TilePos {
+                    x: radius,
+                    y: radius,
+                },
+                TilemapSize {
+                    x: 2 * radius + 1,
+                    y: 2 * radius + 1,
+                },
+            ),
+            MapShape::Rectangle { width, height } | MapShape::Mask { width, height, .. } => (
+                MapShape::rectangle_tile_pos(width / 2, height / 2, height),
+                TilemapSize {
+                    x: MapShape::row_shift(height) + width,
+                    y: height,
+                },
+            ),
+            MapShape::Parallelogram { width, height } => (
+                TilePos {
+                    x: width / 2,
+                    y: height / 2,
+                },
+                TilemapSize {
+                    x: width,
+                    y: height,
+                },
+            ),
+        };
+
+        let mut map_geometry = MapGeometry {
+            shape,
+            center,
+            size,
+        };
+
+        // The middle of a mask's bounding box may be a hole, so use the nearest included tile instead
+        if let MapShape::Mask { .. } = map_geometry.shape {
+            if let Some(nearest) = map_geometry
+                .iter_positions()
+                .min_by_key(|tile_pos| hex_distance(&center, tile_pos))
+            {
+                map_geometry.center = nearest;
+            }
         }
+
+        map_geometry
     }
 
     /// Computes the number of positions that exist in this map
-    pub const fn n_positions(&self) -> usize {
-        1 + 6 * ((self.radius * (self.radius + 1)) / 2) as usize
+    pub fn n_positions(&self) -> usize {
+        match &self.shape {
+            MapShape::Hexagon { radius } => {
+                let radius = *radius as usize;
+                1 + 3 * radius * (radius + 1)
+            }
+            MapShape::Rectangle { width, height } | MapShape::Parallelogram { width, height } => {
+                *width as usize * *height as usize
+            }
+            MapShape::Mask {
+                width,
+                height,
+                cells,
+            } => cells
+                .iter()
+                .take(*width as usize * *height as usize)
+                .filter(|included| **included)
+                .count(),
+        }
     }
 
     /// Computes the total diameter from end-to-end of the game world
@@ -60,6 +189,8 @@ impl MapGeometry {
     }
 
     /// Computes the [`TilemapSize`] of the game world
+    ///
+    /// Every position on the map lies within this size.
     #[inline]
     pub const fn size(&self) -> TilemapSize {
         self.size
@@ -68,34 +199,57 @@ impl MapGeometry {
     /// Computes the [`TilePos`] of the tile at the center of this map.
     ///
     /// This is not (0,0) as `bevy_ecs_tilemap` works with `u32` coordinates.
+    /// For [`MapShape::Mask`], this is the included tile closest to the middle of the mask.
     #[inline]
     pub const fn center(&self) -> TilePos {
         self.center
     }
 
-    /// Gets the radius of the map
+    /// Gets the shape of the map
     #[inline]
-    pub const fn radius(&self) -> u32 {
-        self.radius
+    pub const fn shape(&self) -> &MapShape {
+        &self.shape
     }
 
     /// Checks to see if the given tile position lies within the map
-    #[inline]
-    pub const fn check_inclusion(&self, tile_pos: &TilePos) -> bool {
-        let delta_x = (tile_pos.x as isize - self.center.x as isize).unsigned_abs() as u32;
-        if delta_x < self.radius + 1 {
-            let delta_y = (tile_pos.y as isize - self.center.y as isize).unsigned_abs() as u32;
-            delta_y < self.radius + 1
-        } else {
-            false
+    pub fn check_inclusion(&self, tile_pos: &TilePos) -> bool {
+        match &self.shape {
+            MapShape::Hexagon { radius } => hex_distance(&self.center, tile_pos) <= *radius,
+            MapShape::Rectangle { width, height } => {
+                MapShape::rectangle_cell(tile_pos, *width, *height).is_some()
+            }
+            MapShape::Parallelogram { width, height } => {
+                tile_pos.x < *width && tile_pos.y < *height
+            }
+            MapShape::Mask {
+                width,
+                height,
+                cells,
+            } => MapShape::rectangle_cell(tile_pos, *width, *height)
+                .and_then(|(column, row)| {
+                    cells.get(row as usize * *width as usize + column as usize)
+                })
+                .copied()
+                .unwrap_or(false),
         }
     }
+
+    /// Iterates over every position on the map, row by row.
+    pub fn iter_positions(&self) -> impl Iterator<Item = TilePos> + '_ {
+        (0..self.size.y)
+            .flat_map(move |y| (0..self.size.x).map(move |x| TilePos { x, y }))
+            .filter(|tile_pos| self.check_inclusion(tile_pos))
+    }
 }
 
 /// Initialize the [`MapGeometry`] resource according to [`GenerationConfig`].
 pub fn configure_map_geometry(mut commands: Commands, config: Res<GenerationConfig>) {
     info!("Configuring map geometry...");
-    let map_geometry: MapGeometry = MapGeometry::new(config.map_radius);
+    let map_shape = match &config.map_import {
+        Some(imported) => imported.shape(),
+        None => config.map_shape.clone(),
+    };
+    let map_geometry: MapGeometry = MapGeometry::new(map_shape);
 
     commands.insert_resource(map_geometry);
 }
@@ -103,71 +257,56 @@ pub fn configure_map_geometry(mut commands: Commands, config: Res<GenerationConf
 /// Resource caching tile positions for a fixed map size
 #[derive(Resource, Default)]
 pub struct MapPositions {
-    /// Number of positions that are expected given the size of teh hexagonal map
-    n_positions: usize,
-    /// Vector of positions that exist in the map
-    positions: Vec<TilePos>,
-    /// [`HashMap`] caching the [`HexPatch`] centered at each position
-    patches: HashMap<TilePos, HexPatch<TilePos>>,
-    /// [`HashMap`] caching the number of hex neighbors at each position
-    patch_sizes: HashMap<TilePos, usize>,
+    /// The dense index of every position on the map, shared with each [`MapResource`](resources::MapResource)
+    index: Arc<MapIndex>,
+    /// The [`HexPatch`] centered at each position, in the same order as `index`
+    patches: Vec<HexPatch<TilePos>>,
 }
 
 impl MapPositions {
-    /// Creates map positions for a hexagonal map specified by the given [`MapGeometry`]
+    /// Creates map positions for the map specified by the given [`MapGeometry`]
     pub fn new(map_geometry: &MapGeometry) -> MapPositions {
-        let n_positions = map_geometry.n_positions();
-
-        let mut map_positions = MapPositions {
-            n_positions,
-            positions: Vec::with_capacity(n_positions),
-            patches: HashMap::with_capacity(n_positions),
-            patch_sizes: HashMap::with_capacity(n_positions),
-        };
+        let index = MapIndex::new(map_geometry);
+        let patches = (0..index.len())
+            .map(|i| index.patch(i).map_ref(|neighbor| index.position(*neighbor)))
+            .collect();
 
-        let center = map_geometry.center();
-        let radius = map_geometry.radius();
-        // When using HexCoordSystem::Row, TilePos is the same as AxialPos, so we can get away with
-        // unchecked/fast conversions between AxialPos and TilePos
-        for position in generate_hexagon(AxialPos::from(&center), radius)
-            .into_iter()
-            .map(|axial_pos| axial_pos.as_tile_pos_unchecked())
-        {
-            let patch = HexPatch::generate(&position, map_geometry);
-            let patch_size = patch.count();
-            map_positions.positions.push(position);
-            map_positions.patches.insert(position, patch);
-            map_positions.patch_sizes.insert(position, patch_size);
+        MapPositions {
+            index: Arc::new(index),
+            patches,
         }
-
-        map_positions
     }
 
     /// Get an iterator over tile positions
     pub fn iter_positions(&self) -> impl Iterator<Item = &TilePos> + '_ {
-        self.positions.iter()
+        self.index.positions().iter()
     }
 
     /// Get an iterator over neighbors
     pub fn iter_neighbors(&self) -> impl Iterator<Item = &HexPatch<TilePos>> + '_ {
-        self.patches.values()
+        self.patches.iter()
     }
 
     /// Get neighbors associated with a given tile position, if it exists in the cache
     pub fn get_patch(&self, tile_pos: &TilePos) -> Option<&HexPatch<TilePos>> {
-        self.patches.get(tile_pos)
+        Some(&self.patches[self.index.get(tile_pos)?])
     }
 
     /// Get number of positions in the given position's [`HexPatch`], if it exists in the cache
     ///
     /// Usually, missing positions indicate map edges
     pub fn get_patch_count(&self, tile_pos: &TilePos) -> Option<usize> {
-        self.patch_sizes.get(tile_pos).copied()
+        Some(self.get_patch(tile_pos)?.count())
     }
 
     /// Get the number of positions that are managed by this structure
-    pub const fn n_positions(&self) -> usize {
-        self.n_positions
+    pub fn n_positions(&self) -> usize {
+        self.index.len()
+    }
+
+    /// The dense index of the positions on this map
+    pub fn index(&self) -> &Arc<MapIndex> {
+        &self.index
     }
 }
 
@@ -178,3 +317,116 @@ pub fn create_map_positions(mut commands: Commands, map_geometry: Res<MapGeometr
 
     commands.insert_resource(map_positions);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One of each kind of map shape
+    fn shapes() -> Vec<MapShape> {
+        vec![
+            MapShape::Hexagon { radius: 0 },
+            MapShape::Hexagon { radius: 4 },
+            MapShape::Rectangle {
+                width: 5,
+                height: 4,
+            },
+            MapShape::Rectangle {
+                width: 3,
+                height: 7,
+            },
+            MapShape::Parallelogram {
+                width: 6,
+                height: 2,
+            },
+            MapShape::Mask {
+                width: 3,
+                height: 3,
+                cells: vec![true, false, true, false, true, false, true, true],
+            },
+            MapShape::Mask {
+                width: 3,
+                height: 3,
+                cells: vec![true, true, true, true, false, true, true, true, true],
+            },
+        ]
+    }
+
+    #[test]
+    fn positions_match_shape() {
+        for shape in shapes() {
+            let map_geometry = MapGeometry::new(shape.clone());
+            let positions: Vec<TilePos> = map_geometry.iter_positions().collect();
+            let size = map_geometry.size();
+
+            assert_eq!(positions.len(), map_geometry.n_positions(), "{shape:?}");
+            assert!(map_geometry.check_inclusion(&map_geometry.center()));
+            assert!(positions
+                .iter()
+                .all(|tile_pos| tile_pos.x < size.x && tile_pos.y < size.y));
+            assert_eq!(
+                MapPositions::new(&map_geometry).n_positions(),
+                positions.len()
+            );
+        }
+    }
+
+    #[test]
+    fn hexagons_exclude_corners() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 2 });
+
+        assert!(map_geometry.check_inclusion(&TilePos { x: 2, y: 0 }));
+        assert!(map_geometry.check_inclusion(&TilePos { x: 4, y: 0 }));
+        assert!(!map_geometry.check_inclusion(&TilePos { x: 0, y: 0 }));
+        assert!(!map_geometry.check_inclusion(&TilePos { x: 4, y: 4 }));
+    }
+
+    #[test]
+    fn rectangle_rows_are_offset() {
+        let map_geometry = MapGeometry::new(MapShape::Rectangle {
+            width: 3,
+            height: 4,
+        });
+
+        // Each pair of rows starts one tile further to the left
+        let row_starts: Vec<u32> = (0..4)
+            .map(|y| {
+                map_geometry
+                    .iter_positions()
+                    .find(|tile_pos| tile_pos.y == y)
+                    .unwrap()
+                    .x
+            })
+            .collect();
+        assert_eq!(row_starts, [1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn masks_only_include_set_cells() {
+        let map_geometry = MapGeometry::new(MapShape::Mask {
+            width: 2,
+            height: 1,
+            cells: vec![false, true],
+        });
+
+        assert_eq!(
+            map_geometry.iter_positions().collect::<Vec<_>>(),
+            [TilePos { x: 1, y: 0 }]
+        );
+    }
+
+    #[test]
+    fn mask_centers_avoid_holes() {
+        let shape = MapShape::Mask {
+            width: 3,
+            height: 3,
+            cells: vec![true, true, true, true, false, true, true, true, true],
+        };
+        let middle = MapShape::rectangle_tile_pos(1, 1, 3);
+        let map_geometry = MapGeometry::new(shape);
+
+        assert!(!map_geometry.check_inclusion(&middle));
+        assert!(map_geometry.check_inclusion(&map_geometry.center()));
+        assert_eq!(hex_distance(&middle, &map_geometry.center()), 1);
+    }
+}
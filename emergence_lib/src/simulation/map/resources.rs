@@ -1,73 +1,28 @@
 //! Code for managing data that is deeply tied to the map
 
 use crate::simulation::map::hex_patch::HexPatch;
+use crate::simulation::map::index::MapIndex;
 use crate::simulation::map::MapPositions;
 use bevy::prelude::Resource;
-use bevy::utils::HashMap;
 use bevy_ecs_tilemap::tiles::TilePos;
 use std::fmt::Debug;
-use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
-
-/// Spatial data for use with the [`MapResource`] struct.
-// We cannot derive Clone on this, because:https://stackoverflow.com/questions/39415052/deriving-a-trait-results-in-unexpected-compiler-error-but-the-manual-implementa
-#[derive(Debug)]
-pub struct MapData<T> {
-    /// The `Arc` allows for multiple references to the data, the `RwLock` allows for
-    /// multiple readers/single-writer manipulation of the data.
-    pub(crate) inner: Arc<RwLock<T>>,
-}
-
-impl<T> Clone for MapData<T> {
-    fn clone(&self) -> Self {
-        MapData {
-            inner: self.inner.clone(),
-        }
-    }
-}
-
-impl<T> MapData<T> {
-    /// Create from data
-    pub fn new(data: T) -> Self {
-        Self {
-            inner: Arc::new(RwLock::new(data)),
-        }
-    }
-
-    /// Immutably borrow the inner data (read access)
-    pub fn read(&self) -> RwLockReadGuard<'_, T> {
-        self.inner.as_ref().read().unwrap()
-    }
-
-    /// Get mutable access to the data
-    ///
-    /// Will panic if write-access cannot be obtained
-    /// TODO: flesh out under what conditions the panic will occur.
-    pub fn get_mut(&mut self) -> RwLockWriteGuard<'_, T> {
-        self.inner.write().unwrap()
-    }
-
-    /// Replace internal data
-    pub fn replace(&mut self, new_data: T) {
-        *self.get_mut() = new_data;
-    }
-}
+use std::sync::Arc;
 
 /// A helper for managing game resources that are naturally tied to a fixed specific position on
 /// the map
 ///
-/// It can give you [`MapData<T>`](MapData) at a given tile position, or it can give you
-/// [`Hexpatches<MapData<T>>`](HexPatch) for the given position.
+/// It can give you the data at a given tile position, or it can give you a
+/// [`HexPatch`] of references to the data surrounding the given position.
 ///
-/// Internally, [`MapData`] is stored in a [`HashMap`] for each position, in the `storage` field,
-/// and this same data is then referenced to by the `patches` field.
-#[derive(Resource)]
+/// Internally, the data is stored contiguously in a [`Vec`], in the order given by the
+/// [`MapIndex`] shared with the [`MapPositions`] this resource was created from.
+/// Patches are borrowed from this storage on demand, rather than being cached.
+#[derive(Resource, Debug)]
 pub struct MapResource<T> {
-    /// Primary internal storage of data associated with each position
-    pub(crate) storage: HashMap<TilePos, MapData<T>>,
-    /// [`HexPatch`] of data centered at each position
-    ///
-    /// It is based off of references to data in `storage`.
-    pub(crate) patches: HashMap<TilePos, HexPatch<MapData<T>>>,
+    /// The index used to convert tile positions into offsets into `data`
+    index: Arc<MapIndex>,
+    /// The data associated with each position, in index order
+    data: Vec<T>,
 }
 
 impl<T> MapResource<T>
@@ -76,115 +31,196 @@ where
 {
     /// Create new from an underlying [`MapPositions`] template
     ///
-    /// This allocates capacity and initializes patches based on the template provided.
-    ///
     /// This requires that that there is a `Default` impl for the underlying data type
     pub fn default_from_template(template: &MapPositions) -> MapResource<T> {
-        let storage = MapResource::generate_storage(
-            template,
-            template
-                .iter_positions()
-                .map(|position| (*position, T::default())),
-        );
-
-        let patches = MapResource::generate_patches(&storage, template);
-
-        MapResource { storage, patches }
+        MapResource {
+            index: template.index().clone(),
+            data: (0..template.n_positions()).map(|_| T::default()).collect(),
+        }
     }
 }
 
 impl<T> MapResource<T> {
-    /// Generate the storage [`HashMap`]
-    pub fn generate_storage(
-        template: &MapPositions,
-        data: impl Iterator<Item = (TilePos, T)>,
-    ) -> HashMap<TilePos, MapData<T>> {
-        let mut storage = HashMap::with_capacity(template.n_positions());
-        storage.extend(data.map(|(tile_pos, t)| (tile_pos, MapData::new(t))));
-        storage
-    }
-
-    /// Generate patches for the given storage
-    /// TODO: improve doc string
-    pub fn generate_patches(
-        storage: &HashMap<TilePos, MapData<T>>,
-        template: &MapPositions,
-    ) -> HashMap<TilePos, HexPatch<MapData<T>>> {
-        let mut patches = HashMap::with_capacity(template.n_positions());
-        patches.extend(template.iter_positions().filter_map(|position| {
-            let tile_patch = template.get_patch(position)?;
-            let data_patch = tile_patch.and_then_ref(|position| {
-                let map_data = storage.get(position)?;
-                let map_data_clone: MapData<T> = map_data.clone();
-                Some(map_data_clone)
-            });
-            Some((*position, data_patch))
-        }));
-        patches
-    }
-
     /// Create new from an underlying [`MapPositions`] template.
     ///
-    /// This allocates capacity and initializes patches based on the template provided.
+    /// Data for positions that do not lie on the map is ignored.
     ///
     /// If your underlying data implements [`Default`], you could use
     /// [`default_from_template`](MapResource::default_from_template) to also initialize data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` does not contain a value for every position on the map.
     pub fn new(
         template: &MapPositions,
         data: impl Iterator<Item = (TilePos, T)>,
     ) -> MapResource<T> {
-        let storage = MapResource::generate_storage(template, data);
-        let patches = MapResource::generate_patches(&storage, template);
+        let index = template.index().clone();
+
+        let mut slots: Vec<Option<T>> = (0..index.len()).map(|_| None).collect();
+        for (position, value) in data {
+            if let Some(i) = index.get(&position) {
+                slots[i] = Some(value);
+            }
+        }
+
+        let data = slots
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                value.unwrap_or_else(|| panic!("No data provided for {:?}", index.position(i)))
+            })
+            .collect();
 
-        MapResource { storage, patches }
+        MapResource { index, data }
     }
 
     /// Update data for given tile positions
     pub fn update(&mut self, new_data: impl Iterator<Item = (TilePos, T)>) {
         new_data.for_each(|(position, data)| {
-            if let Some(map_data) = self.storage.get_mut(&position) {
-                map_data.replace(data);
+            if let Some(map_data) = self.get_mut(&position) {
+                *map_data = data;
             }
         });
     }
 
     /// Replace data at the specified position
     pub fn replace(&mut self, position: &TilePos, replace_with: T) {
-        *(self.storage.get_mut(position).unwrap().get_mut()) = replace_with;
+        *(self.get_mut(position).unwrap()) = replace_with;
     }
 
     /// Get data stored at given position
-    pub fn get(&self, position: &TilePos) -> Option<MapData<T>> {
-        self.storage.get(position).cloned()
+    #[inline]
+    pub fn get(&self, position: &TilePos) -> Option<&T> {
+        Some(&self.data[self.index.get(position)?])
     }
 
     /// Get mutable access to data stored at given position
-    pub fn get_mut(&mut self, position: &TilePos) -> Option<&mut MapData<T>> {
-        self.storage.get_mut(position)
-    }
-
-    /// Get immutable access to [`HexPatch`] of data for given position
-    pub fn get_patch(&self, position: &TilePos) -> Option<&HexPatch<MapData<T>>> {
-        self.patches.get(position)
+    #[inline]
+    pub fn get_mut(&mut self, position: &TilePos) -> Option<&mut T> {
+        Some(&mut self.data[self.index.get(position)?])
     }
 
-    /// Get mutable access to [`HexPatch`] of data for given position
-    pub fn get_patch_mut(&mut self, position: &TilePos) -> Option<&mut HexPatch<MapData<T>>> {
-        self.patches.get_mut(position)
+    /// Get a [`HexPatch`] borrowing the data surrounding the given position
+    pub fn get_patch(&self, position: &TilePos) -> Option<HexPatch<&T>> {
+        let center = self.index.get(position)?;
+        Some(
+            self.index
+                .patch(center)
+                .map_ref(|neighbor| &self.data[*neighbor]),
+        )
     }
 
     /// Iterate over the positions managed by this resource
     pub fn positions(&self) -> impl Iterator<Item = &TilePos> {
-        self.storage.keys()
+        self.index.positions().iter()
     }
 
     /// Iterate over the data at all positions
-    pub fn values(&self) -> impl Iterator<Item = &MapData<T>> {
-        self.storage.values()
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.data.iter()
     }
 
     /// Iterate over the data at all positions, returning a mutable reference
-    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut MapData<T>> {
-        self.storage.values_mut()
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.data.iter_mut()
+    }
+
+    /// Iterate over each position, paired with its data
+    pub fn iter(&self) -> impl Iterator<Item = (&TilePos, &T)> {
+        self.positions().zip(self.data.iter())
+    }
+
+    /// The [`MapIndex`] that determines the order of [`as_slice`](MapResource::as_slice)
+    pub fn index(&self) -> &MapIndex {
+        &self.index
+    }
+
+    /// The data at every position, in the order given by [`index`](MapResource::index)
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Mutable access to the data at every position, in the order given by [`index`](MapResource::index)
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    /// The [`MapIndex`], along with mutable access to the data at every position
+    ///
+    /// Useful when updating each position requires access to the data at its neighbors.
+    pub fn index_and_mut_slice(&mut self) -> (&MapIndex, &mut [T]) {
+        (&self.index, &mut self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::hex_patch::HexPatchLocation;
+    use crate::simulation::map::{MapGeometry, MapShape};
+
+    /// Creates the positions of a small map
+    fn template() -> MapPositions {
+        MapPositions::new(&MapGeometry::new(MapShape::Hexagon { radius: 2 }))
+    }
+
+    #[test]
+    fn data_is_stored_per_position() {
+        let template = template();
+        let map_resource = MapResource::new(
+            &template,
+            template.iter_positions().map(|p| (*p, p.x * p.y)),
+        );
+
+        for position in template.iter_positions() {
+            assert_eq!(map_resource.get(position), Some(&(position.x * position.y)));
+        }
+        assert_eq!(map_resource.get(&TilePos { x: 0, y: 0 }), None);
+    }
+
+    #[test]
+    fn updates_only_change_given_positions() {
+        let template = template();
+        let mut map_resource = MapResource::<u32>::default_from_template(&template);
+        let center = MapGeometry::new(MapShape::Hexagon { radius: 2 }).center();
+
+        map_resource.update([(center, 3), (TilePos { x: 1000, y: 0 }, 5)].into_iter());
+        map_resource.replace(&center, 4);
+
+        assert_eq!(map_resource.values().sum::<u32>(), 4);
+        assert_eq!(map_resource.get(&center), Some(&4));
+    }
+
+    #[test]
+    fn patches_borrow_neighboring_data() {
+        let template = template();
+        let center = MapGeometry::new(MapShape::Hexagon { radius: 2 }).center();
+        let mut map_resource = MapResource::<u32>::default_from_template(&template);
+
+        let north = *template
+            .get_patch(&center)
+            .unwrap()
+            .get(HexPatchLocation::North)
+            .unwrap();
+        *map_resource.get_mut(&north).unwrap() = 7;
+
+        let patch = map_resource.get_patch(&center).unwrap();
+        assert_eq!(patch.get(HexPatchLocation::North), Some(&&7));
+        assert_eq!(patch.get(HexPatchLocation::Center), Some(&&0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn incomplete_data_panics() {
+        let template = template();
+        MapResource::new(
+            &template,
+            [(
+                MapGeometry::new(MapShape::Hexagon { radius: 2 }).center(),
+                0,
+            )]
+            .into_iter(),
+        );
     }
 }
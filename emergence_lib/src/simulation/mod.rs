@@ -3,19 +3,23 @@
 //! All plugins in this module should work without rendering.
 
 use crate::organisms::OrganismPlugin;
+use crate::save::SavePlugin;
 use crate::signals::SignalsPlugin;
 use crate::simulation::generation::{GenerationConfig, GenerationPlugin};
 use crate::simulation::map::MapPositions;
-use crate::simulation::pathfinding::{Impassable, PassabilityCache};
+use crate::simulation::occupancy::OccupancyPlugin;
+use crate::simulation::pathfinding::{Impassable, PassabilityCache, PathfindingPlugin};
 use crate::structures::StructuresPlugin;
 use bevy::app::{App, CoreStage, Plugin, StartupStage};
 use bevy::log::info;
-use bevy::prelude::{Commands, Query, Res, ResMut, With};
+use bevy::prelude::{Changed, Commands, Entity, Or, Query, RemovedComponents, Res, ResMut};
 use bevy_ecs_tilemap::tiles::TilePos;
 
 pub mod generation;
 pub mod map;
+pub mod occupancy;
 pub mod pathfinding;
+pub mod rng;
 
 /// All of the code needed to make the simulation run
 pub struct SimulationPlugin {
@@ -32,8 +36,12 @@ impl Plugin for SimulationPlugin {
         .add_plugin(StructuresPlugin)
         .add_plugin(OrganismPlugin)
         .add_plugin(SignalsPlugin)
+        .add_plugin(SavePlugin)
+        .add_plugin(PathfindingPlugin)
+        .add_plugin(OccupancyPlugin)
         .add_startup_system_to_stage(StartupStage::PostStartup, initialize_passable_filter)
-        .add_system_to_stage(CoreStage::PreUpdate, update_passable_filter);
+        .add_system_to_stage(CoreStage::PreUpdate, update_passable_filter)
+        .add_system_to_stage(CoreStage::Last, remove_impassable_from_filter);
     }
 }
 
@@ -42,10 +50,33 @@ pub fn initialize_passable_filter(mut commands: Commands, map_positions: Res<Map
     commands.insert_resource(PassabilityCache::new(&map_positions));
 }
 
-/// Update the [`PassabilityCache`] resource
+/// Update the [`PassabilityCache`] resource, as [`Impassable`] entities are added, moved or removed
 pub fn update_passable_filter(
-    impassable: Query<&TilePos, With<Impassable>>,
+    changed_impassable: Query<
+        (Entity, &TilePos, &Impassable),
+        Or<(Changed<Impassable>, Changed<TilePos>)>,
+    >,
+    removed_impassable: RemovedComponents<Impassable>,
     mut passable_filters: ResMut<PassabilityCache>,
 ) {
-    passable_filters.update_from_impassable_query(&impassable)
+    for entity in removed_impassable.iter() {
+        passable_filters.remove_blocker(entity);
+    }
+
+    for (entity, position, impassable) in changed_impassable.iter() {
+        passable_filters.insert_blocker(entity, *position, *impassable);
+    }
+}
+
+/// Remove [`Impassable`] entities from the [`PassabilityCache`] resource
+///
+/// Removed components are only tracked until the end of the frame,
+/// so removals made after [`update_passable_filter`] runs must be handled before then.
+fn remove_impassable_from_filter(
+    removed_impassable: RemovedComponents<Impassable>,
+    mut passable_filters: ResMut<PassabilityCache>,
+) {
+    for entity in removed_impassable.iter() {
+        passable_filters.remove_blocker(entity);
+    }
 }
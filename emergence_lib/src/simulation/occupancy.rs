@@ -0,0 +1,364 @@
+//! Tracks which units stand on each tile, and how many can fit there.
+//!
+//! Units claim the tile they want to move onto with a reservation before anyone moves,
+//! so that two units can never take the last free slot on the same tile.
+
+use crate::organisms::units::Unit;
+use crate::simulation::map::resources::MapResource;
+use crate::simulation::map::MapPositions;
+use crate::structures::Structure;
+use crate::terrain::TerrainType;
+use bevy::app::{App, CoreStage, Plugin, StartupStage};
+use bevy::prelude::{
+    Changed, Commands, Entity, Or, Query, RemovedComponents, Res, ResMut, Resource, With,
+};
+use bevy::utils::HashMap;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+/// Keeps the [`TileOccupancy`] resource in sync with the units, terrain and structures on the map
+pub struct OccupancyPlugin;
+
+impl Plugin for OccupancyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OccupancyLimits>()
+            .add_event::<MoveBlocked>()
+            .add_startup_system_to_stage(StartupStage::PostStartup, initialize_tile_occupancy)
+            .add_system_to_stage(CoreStage::PreUpdate, update_tile_occupancy)
+            .add_system_to_stage(CoreStage::Last, remove_from_tile_occupancy);
+    }
+}
+
+/// Sent when a unit could not move onto a tile, because every slot there was taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveBlocked {
+    /// The unit that could not move
+    pub unit: Entity,
+    /// The tile that the unit stayed on
+    pub from: TilePos,
+    /// The tile that the unit tried to move onto
+    pub to: TilePos,
+}
+
+/// The maximum number of units that can stand on a tile
+#[derive(Resource, Debug, Clone)]
+pub struct OccupancyLimits {
+    /// The maximum number of units that can stand on each type of terrain
+    terrain: HashMap<TerrainType, u32>,
+    /// The maximum number of units that can stand on a tile with a structure
+    structure: u32,
+}
+
+impl OccupancyLimits {
+    /// The maximum number of units that can stand on a tile of the given `terrain_type`
+    pub fn terrain(&self, terrain_type: &TerrainType) -> u32 {
+        self.terrain.get(terrain_type).copied().unwrap_or(1)
+    }
+
+    /// Sets the maximum number of units that can stand on a tile of the given `terrain_type`
+    pub fn set_terrain(&mut self, terrain_type: TerrainType, limit: u32) {
+        self.terrain.insert(terrain_type, limit);
+    }
+
+    /// The maximum number of units that can stand on a tile with a structure
+    pub fn structure(&self) -> u32 {
+        self.structure
+    }
+
+    /// Sets the maximum number of units that can stand on a tile with a structure
+    pub fn set_structure(&mut self, limit: u32) {
+        self.structure = limit;
+    }
+}
+
+impl Default for OccupancyLimits {
+    fn default() -> Self {
+        let mut terrain = HashMap::new();
+        terrain.insert(TerrainType::Plain, 2);
+        terrain.insert(TerrainType::High, 1);
+        terrain.insert(TerrainType::Rocky, 1);
+
+        OccupancyLimits {
+            terrain,
+            structure: 0,
+        }
+    }
+}
+
+/// Which units stand on each tile, which tiles they have reserved, and how many units fit on each tile
+#[derive(Resource)]
+pub struct TileOccupancy {
+    /// The units standing on each tile
+    occupants: MapResource<Vec<Entity>>,
+    /// The tile that each unit is standing on
+    unit_positions: HashMap<Entity, TilePos>,
+    /// The units that have reserved a slot on each tile
+    reservations: MapResource<Vec<Entity>>,
+    /// The tile that each unit has reserved, if any
+    reserved_positions: HashMap<Entity, TilePos>,
+    /// The maximum number of units that can stand on each tile, according to its terrain
+    terrain_limits: MapResource<u32>,
+    /// The number of structures on each tile
+    structure_counts: MapResource<u32>,
+    /// The tile that each structure is on
+    structure_positions: HashMap<Entity, TilePos>,
+}
+
+impl TileOccupancy {
+    /// Creates a new, empty [`TileOccupancy`], where every tile can hold a single unit
+    pub fn new(template: &MapPositions) -> TileOccupancy {
+        TileOccupancy {
+            occupants: MapResource::default_from_template(template),
+            unit_positions: HashMap::new(),
+            reservations: MapResource::default_from_template(template),
+            reserved_positions: HashMap::new(),
+            terrain_limits: MapResource::new(
+                template,
+                template.iter_positions().map(|position| (*position, 1)),
+            ),
+            structure_counts: MapResource::default_from_template(template),
+            structure_positions: HashMap::new(),
+        }
+    }
+
+    /// The units standing on `tile_pos`
+    pub fn occupants(&self, tile_pos: &TilePos) -> &[Entity] {
+        self.occupants
+            .get(tile_pos)
+            .map(|occupants| occupants.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The tile that `unit` is standing on, if it is known
+    pub fn position(&self, unit: Entity) -> Option<TilePos> {
+        self.unit_positions.get(&unit).copied()
+    }
+
+    /// The maximum number of units that can stand on `tile_pos`
+    ///
+    /// Tiles off the map cannot hold any units.
+    pub fn capacity(&self, tile_pos: &TilePos, limits: &OccupancyLimits) -> u32 {
+        match (
+            self.terrain_limits.get(tile_pos),
+            self.structure_counts.get(tile_pos),
+        ) {
+            (Some(terrain_limit), Some(0)) => *terrain_limit,
+            (Some(terrain_limit), Some(_)) => (*terrain_limit).min(limits.structure()),
+            _ => 0,
+        }
+    }
+
+    /// The number of slots on `tile_pos` that are neither occupied nor reserved
+    ///
+    /// Units that are about to leave a tile still occupy it until they have moved.
+    pub fn free_slots(&self, tile_pos: &TilePos, limits: &OccupancyLimits) -> u32 {
+        let taken = self.occupants(tile_pos).len()
+            + self
+                .reservations
+                .get(tile_pos)
+                .map(|reservations| reservations.len())
+                .unwrap_or_default();
+
+        self.capacity(tile_pos, limits).saturating_sub(taken as u32)
+    }
+
+    /// Attempts to reserve a slot on `tile_pos` for `unit`, replacing any previous reservation.
+    ///
+    /// Returns `true` if the reservation was made.
+    pub fn reserve(&mut self, unit: Entity, tile_pos: TilePos, limits: &OccupancyLimits) -> bool {
+        self.cancel_reservation(unit);
+        if self.free_slots(&tile_pos, limits) == 0 {
+            return false;
+        }
+
+        if let Some(reservations) = self.reservations.get_mut(&tile_pos) {
+            reservations.push(unit);
+        }
+        self.reserved_positions.insert(unit, tile_pos);
+        true
+    }
+
+    /// The tile that `unit` has reserved, if any
+    pub fn reservation(&self, unit: Entity) -> Option<TilePos> {
+        self.reserved_positions.get(&unit).copied()
+    }
+
+    /// Removes the reservation held by `unit`, if any
+    pub fn cancel_reservation(&mut self, unit: Entity) {
+        if let Some(tile_pos) = self.reserved_positions.remove(&unit) {
+            if let Some(reservations) = self.reservations.get_mut(&tile_pos) {
+                reservations.retain(|reserved_by| *reserved_by != unit);
+            }
+        }
+    }
+
+    /// Records that `unit` is standing on `tile_pos`, consuming its reservation there if it has one
+    pub fn move_unit(&mut self, unit: Entity, tile_pos: TilePos) {
+        if self.reservation(unit) == Some(tile_pos) {
+            self.cancel_reservation(unit);
+        }
+        if self.position(unit) == Some(tile_pos) {
+            return;
+        }
+
+        self.remove_unit(unit);
+        if let Some(occupants) = self.occupants.get_mut(&tile_pos) {
+            occupants.push(unit);
+            self.unit_positions.insert(unit, tile_pos);
+        }
+    }
+
+    /// Forgets about `unit`, freeing its slot and any reservation it holds
+    pub fn remove_unit(&mut self, unit: Entity) {
+        self.cancel_reservation(unit);
+        if let Some(tile_pos) = self.unit_positions.remove(&unit) {
+            if let Some(occupants) = self.occupants.get_mut(&tile_pos) {
+                occupants.retain(|occupant| *occupant != unit);
+            }
+        }
+    }
+
+    /// Sets the maximum number of units that can stand on `tile_pos`, based on its terrain
+    pub fn set_terrain_limit(&mut self, tile_pos: &TilePos, limit: u32) {
+        if let Some(terrain_limit) = self.terrain_limits.get_mut(tile_pos) {
+            *terrain_limit = limit;
+        }
+    }
+
+    /// Records that `structure` is on `tile_pos`
+    pub fn insert_structure(&mut self, structure: Entity, tile_pos: TilePos) {
+        if self.structure_positions.get(&structure) == Some(&tile_pos) {
+            return;
+        }
+
+        self.remove_structure(structure);
+        if let Some(count) = self.structure_counts.get_mut(&tile_pos) {
+            *count += 1;
+            self.structure_positions.insert(structure, tile_pos);
+        }
+    }
+
+    /// Forgets about `structure`
+    pub fn remove_structure(&mut self, structure: Entity) {
+        if let Some(tile_pos) = self.structure_positions.remove(&structure) {
+            if let Some(count) = self.structure_counts.get_mut(&tile_pos) {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Create the [`TileOccupancy`] resource
+fn initialize_tile_occupancy(mut commands: Commands, map_positions: Res<MapPositions>) {
+    commands.insert_resource(TileOccupancy::new(&map_positions));
+}
+
+/// Update the [`TileOccupancy`] resource, as units, terrain and structures change
+#[allow(clippy::too_many_arguments)]
+fn update_tile_occupancy(
+    changed_units: Query<(Entity, &TilePos), (With<Unit>, Changed<TilePos>)>,
+    removed_units: RemovedComponents<Unit>,
+    changed_terrain: Query<(&TilePos, &TerrainType), Changed<TerrainType>>,
+    all_terrain: Query<(&TilePos, &TerrainType)>,
+    changed_structures: Query<
+        (Entity, &TilePos),
+        (With<Structure>, Or<(Changed<Structure>, Changed<TilePos>)>),
+    >,
+    removed_structures: RemovedComponents<Structure>,
+    limits: Res<OccupancyLimits>,
+    mut occupancy: ResMut<TileOccupancy>,
+) {
+    for unit in removed_units.iter() {
+        occupancy.remove_unit(unit);
+    }
+    for (unit, position) in changed_units.iter() {
+        occupancy.move_unit(unit, *position);
+    }
+
+    let terrain: Vec<(&TilePos, &TerrainType)> = if limits.is_changed() {
+        all_terrain.iter().collect()
+    } else {
+        changed_terrain.iter().collect()
+    };
+    for (position, terrain_type) in terrain {
+        occupancy.set_terrain_limit(position, limits.terrain(terrain_type));
+    }
+
+    for structure in removed_structures.iter() {
+        occupancy.remove_structure(structure);
+    }
+    for (structure, position) in changed_structures.iter() {
+        occupancy.insert_structure(structure, *position);
+    }
+}
+
+/// Remove units and structures from the [`TileOccupancy`] resource
+///
+/// Removed components are only tracked until the end of the frame,
+/// so removals made after [`update_tile_occupancy`] runs must be handled before then.
+fn remove_from_tile_occupancy(
+    removed_units: RemovedComponents<Unit>,
+    removed_structures: RemovedComponents<Structure>,
+    mut occupancy: ResMut<TileOccupancy>,
+) {
+    for unit in removed_units.iter() {
+        occupancy.remove_unit(unit);
+    }
+    for structure in removed_structures.iter() {
+        occupancy.remove_structure(structure);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::{MapGeometry, MapShape};
+
+    /// Creates an empty [`TileOccupancy`] for a small map, along with its center
+    fn occupancy() -> (TileOccupancy, TilePos) {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 2 });
+        let map_positions = MapPositions::new(&map_geometry);
+
+        (TileOccupancy::new(&map_positions), map_geometry.center())
+    }
+
+    #[test]
+    fn reservations_respect_capacity() {
+        let (mut occupancy, center) = occupancy();
+        let limits = OccupancyLimits::default();
+        occupancy.set_terrain_limit(&center, 2);
+
+        let [first, second, third] = [0, 1, 2].map(Entity::from_raw);
+        occupancy.move_unit(first, center);
+        assert_eq!(occupancy.free_slots(&center, &limits), 1);
+
+        assert!(occupancy.reserve(second, center, &limits));
+        assert!(!occupancy.reserve(third, center, &limits));
+        assert_eq!(occupancy.reservation(third), None);
+
+        // Once the first unit leaves, its slot can be claimed
+        occupancy.remove_unit(first);
+        assert!(occupancy.reserve(third, center, &limits));
+
+        occupancy.move_unit(second, center);
+        occupancy.move_unit(third, center);
+        assert_eq!(occupancy.occupants(&center), [second, third]);
+        assert_eq!(occupancy.reservation(second), None);
+        assert_eq!(occupancy.free_slots(&center, &limits), 0);
+    }
+
+    #[test]
+    fn structures_limit_capacity() {
+        let (mut occupancy, center) = occupancy();
+        let limits = OccupancyLimits::default();
+        occupancy.set_terrain_limit(&center, 2);
+
+        let plant = Entity::from_raw(0);
+        occupancy.insert_structure(plant, center);
+        assert_eq!(occupancy.capacity(&center, &limits), 0);
+        assert!(!occupancy.reserve(Entity::from_raw(1), center, &limits));
+
+        occupancy.remove_structure(plant);
+        assert_eq!(occupancy.capacity(&center, &limits), 2);
+        assert_eq!(occupancy.capacity(&TilePos { x: 100, y: 100 }, &limits), 0);
+    }
+}
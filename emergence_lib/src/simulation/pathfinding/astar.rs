@@ -0,0 +1,435 @@
+//! Finding the cheapest path between two tiles, using A* search
+
+use crate::enum_iter::IterableEnum;
+use crate::simulation::map::geometry::hex_distance;
+use crate::simulation::map::hex_patch::HexPatchLocation;
+use crate::simulation::map::index::MapIndex;
+use crate::simulation::map::MapPositions;
+use crate::simulation::pathfinding::costs::MovementCostCache;
+use crate::simulation::pathfinding::path::Path;
+use crate::simulation::pathfinding::{MovementClass, PassabilityCache};
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+/// An entry in the open set of an A* search: `(f, h, index)`.
+///
+/// Ties in `f` are broken in favor of nodes closer to the goal, then by index,
+/// so that searches are fully deterministic.
+type OpenNode = Reverse<(u32, u32, usize)>;
+
+/// Marks a node that has not been reached yet.
+const UNREACHED: u32 = u32::MAX;
+
+/// Marks a node without a parent.
+const NO_PARENT: usize = usize::MAX;
+
+/// A read-only view of everything needed to search for paths across the map.
+///
+/// The [`PassabilityCache`] and [`MovementCostCache`] must have been created from the same
+/// [`MapPositions`].
+pub struct PathfindingGrid<'a> {
+    /// The positions on the map, and their neighbors
+    index: &'a MapIndex,
+    /// Whether each position is passable, in index order
+    passable: &'a [bool],
+    /// The cost of moving onto each position, in index order
+    costs: &'a [u32],
+    /// The smallest cost of moving onto any position
+    min_cost: u32,
+    /// The versions of the passability and cost caches
+    versions: (u64, u64),
+}
+
+impl<'a> PathfindingGrid<'a> {
+    /// Creates a new [`PathfindingGrid`]
+    pub fn new(
+        map_positions: &'a MapPositions,
+        passability: &'a PassabilityCache,
+        costs: &'a MovementCostCache,
+    ) -> PathfindingGrid<'a> {
+        let index = map_positions.index();
+        let passable = passability.filter(MovementClass::Walking).as_slice();
+        let cost_slice = costs.as_slice();
+        debug_assert_eq!(index.len(), passable.len());
+        debug_assert_eq!(index.len(), cost_slice.len());
+
+        PathfindingGrid {
+            index,
+            passable,
+            costs: cost_slice,
+            min_cost: costs.min_cost(),
+            versions: (passability.version(), costs.version()),
+        }
+    }
+
+    /// The versions of the [`PassabilityCache`] and [`MovementCostCache`] this grid was built from
+    pub(super) fn versions(&self) -> (u64, u64) {
+        self.versions
+    }
+
+    /// Whether each position is passable, in index order
+    pub(super) fn passable(&self) -> &'a [bool] {
+        self.passable
+    }
+
+    /// The cost of moving onto each position, in index order
+    pub(super) fn costs(&self) -> &'a [u32] {
+        self.costs
+    }
+
+    /// The indices of the neighbors of `index`, not including `index` itself
+    pub(super) fn neighbors(&self, index: usize) -> impl Iterator<Item = usize> + 'a {
+        let patch = self.index.patch(index);
+        HexPatchLocation::variants()
+            .filter(|location| !matches!(location, HexPatchLocation::Center))
+            .filter_map(|location| patch.get(location).copied())
+    }
+
+    /// A lower bound on the cost of moving from `from` to `to`
+    ///
+    /// This is consistent, as every step costs at least `min_cost` and reduces the distance by at most 1.
+    fn heuristic(&self, from: usize, to: usize) -> u32 {
+        hex_distance(&self.index.position(from), &self.index.position(to)) * self.min_cost
+    }
+
+    /// Converts a chain of indices into a [`Path`], dropping the first (starting) index
+    fn to_path(&self, indices: impl Iterator<Item = usize>, cost: u32) -> Path {
+        let steps: VecDeque<TilePos> = indices
+            .skip(1)
+            .map(|index| self.index.position(index))
+            .collect();
+        Path::new(steps, cost)
+    }
+
+    /// Follows the `parents` of `from`, until a node without a parent is reached
+    fn walk(parents: &[usize], from: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(Some(from), |node| {
+            let parent = parents[*node];
+            (parent != NO_PARENT).then_some(parent)
+        })
+    }
+
+    /// Finds the indices of `start` and `goal`, if a search between them could succeed
+    fn endpoints(&self, start: &TilePos, goal: &TilePos) -> Option<(usize, usize)> {
+        let start = self.index.get(start)?;
+        let goal = self.index.get(goal)?;
+        self.passable[goal].then_some((start, goal))
+    }
+
+    /// Removes entries from the top of `open` that have since been reached more cheaply
+    fn discard_stale(open: &mut BinaryHeap<OpenNode>, g: &[u32]) {
+        while let Some(Reverse((f, h, index))) = open.peek() {
+            if f - h > g[*index] {
+                open.pop();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Finds the cheapest path from `start` to `goal` using A* search.
+    ///
+    /// The starting tile does not need to be passable, but every other tile along the path does.
+    /// Returns `None` if no such path exists.
+    pub fn find_path(&self, start: &TilePos, goal: &TilePos) -> Option<Path> {
+        let (start, goal) = self.endpoints(start, goal)?;
+
+        let mut g = vec![UNREACHED; self.index.len()];
+        let mut parents = vec![NO_PARENT; self.index.len()];
+        let mut open = BinaryHeap::new();
+
+        g[start] = 0;
+        let h = self.heuristic(start, goal);
+        open.push(Reverse((h, h, start)));
+
+        while let Some(Reverse((f, h, current))) = open.pop() {
+            if current == goal {
+                let mut indices: Vec<usize> = Self::walk(&parents, goal).collect();
+                indices.reverse();
+                return Some(self.to_path(indices.into_iter(), g[goal]));
+            }
+            if f - h > g[current] {
+                continue;
+            }
+
+            for neighbor in self.neighbors(current) {
+                if !self.passable[neighbor] {
+                    continue;
+                }
+
+                let tentative = g[current] + self.costs[neighbor];
+                if tentative < g[neighbor] {
+                    g[neighbor] = tentative;
+                    parents[neighbor] = current;
+                    let h = self.heuristic(neighbor, goal);
+                    open.push(Reverse((tentative + h, h, neighbor)));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Finds the cheapest path from `start` to `goal`, searching from both ends at once.
+    ///
+    /// This returns a path with the same cost as [`find_path`](Self::find_path),
+    /// but typically expands far fewer tiles when the path is long.
+    pub fn find_path_bidirectional(&self, start: &TilePos, goal: &TilePos) -> Option<Path> {
+        let (start, goal) = self.endpoints(start, goal)?;
+        if start == goal {
+            return Some(Path::new(VecDeque::new(), 0));
+        }
+
+        let n = self.index.len();
+        let mut g_forward = vec![UNREACHED; n];
+        let mut g_backward = vec![UNREACHED; n];
+        let mut parents_forward = vec![NO_PARENT; n];
+        let mut parents_backward = vec![NO_PARENT; n];
+        let mut open_forward = BinaryHeap::new();
+        let mut open_backward = BinaryHeap::new();
+
+        g_forward[start] = 0;
+        g_backward[goal] = 0;
+        let h = self.heuristic(start, goal);
+        open_forward.push(Reverse((h, h, start)));
+        open_backward.push(Reverse((h, h, goal)));
+
+        // The cost of the cheapest path found so far, and the node at which the two searches met
+        let mut best_cost = UNREACHED;
+        let mut meeting_point = None;
+
+        loop {
+            Self::discard_stale(&mut open_forward, &g_forward);
+            Self::discard_stale(&mut open_backward, &g_backward);
+
+            let (f_forward, f_backward) = match (open_forward.peek(), open_backward.peek()) {
+                (Some(Reverse((f_forward, ..))), Some(Reverse((f_backward, ..)))) => {
+                    (*f_forward, *f_backward)
+                }
+                // One of the searches has run out of tiles to explore
+                _ => break,
+            };
+
+            // Every path that has not been found yet costs at least this much
+            if f_forward.max(f_backward) >= best_cost {
+                break;
+            }
+
+            if open_forward.len() <= open_backward.len() {
+                let Reverse((_, _, current)) = open_forward.pop().unwrap();
+
+                for neighbor in self.neighbors(current) {
+                    if !self.passable[neighbor] {
+                        continue;
+                    }
+
+                    let tentative = g_forward[current] + self.costs[neighbor];
+                    if tentative < g_forward[neighbor] {
+                        g_forward[neighbor] = tentative;
+                        parents_forward[neighbor] = current;
+                        let h = self.heuristic(neighbor, goal);
+                        open_forward.push(Reverse((tentative + h, h, neighbor)));
+
+                        if g_backward[neighbor] != UNREACHED
+                            && tentative + g_backward[neighbor] < best_cost
+                        {
+                            best_cost = tentative + g_backward[neighbor];
+                            meeting_point = Some(neighbor);
+                        }
+                    }
+                }
+            } else {
+                let Reverse((_, _, current)) = open_backward.pop().unwrap();
+
+                // Moving from `neighbor` onto `current` costs as much as entering `current`
+                for neighbor in self.neighbors(current) {
+                    if !self.passable[neighbor] && neighbor != start {
+                        continue;
+                    }
+
+                    let tentative = g_backward[current] + self.costs[current];
+                    if tentative < g_backward[neighbor] {
+                        g_backward[neighbor] = tentative;
+                        parents_backward[neighbor] = current;
+                        let h = self.heuristic(neighbor, start);
+                        open_backward.push(Reverse((tentative + h, h, neighbor)));
+
+                        if g_forward[neighbor] != UNREACHED
+                            && g_forward[neighbor] + tentative < best_cost
+                        {
+                            best_cost = g_forward[neighbor] + tentative;
+                            meeting_point = Some(neighbor);
+                        }
+                    }
+                }
+            }
+        }
+
+        let meeting_point = meeting_point?;
+        let mut indices: Vec<usize> = Self::walk(&parents_forward, meeting_point).collect();
+        indices.reverse();
+        indices.extend(Self::walk(&parents_backward, meeting_point).skip(1));
+
+        Some(self.to_path(indices.into_iter(), best_cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::{MapGeometry, MapShape};
+    use crate::simulation::pathfinding::costs::TerrainCosts;
+    use crate::terrain::TerrainType;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// The map, passability and costs needed to build a [`PathfindingGrid`]
+    struct TestMap {
+        /// The shape of the map
+        map_geometry: MapGeometry,
+        /// The positions on the map
+        map_positions: MapPositions,
+        /// Which positions are passable
+        passability: PassabilityCache,
+        /// The cost of moving onto each position
+        costs: MovementCostCache,
+    }
+
+    impl TestMap {
+        /// Creates an open hexagonal map of plain terrain
+        fn new(radius: u32) -> TestMap {
+            let map_geometry = MapGeometry::new(MapShape::Hexagon { radius });
+            let map_positions = MapPositions::new(&map_geometry);
+            let passability = PassabilityCache::new(&map_positions);
+            let costs = MovementCostCache::new(&map_positions, &TerrainCosts::default());
+
+            TestMap {
+                map_geometry,
+                map_positions,
+                passability,
+                costs,
+            }
+        }
+
+        /// Creates a [`PathfindingGrid`] over this map
+        fn grid(&self) -> PathfindingGrid<'_> {
+            PathfindingGrid::new(&self.map_positions, &self.passability, &self.costs)
+        }
+
+        /// Checks that `path` is a valid route from `start`, and returns its cost
+        fn check_path(&self, start: &TilePos, path: &Path) -> u32 {
+            let mut previous = *start;
+            let mut cost = 0;
+            for step in path.steps() {
+                assert_eq!(hex_distance(&previous, step), 1);
+                assert!(self.passability.is_passable(MovementClass::Walking, step));
+                cost += self.costs.get(step).unwrap();
+                previous = *step;
+            }
+            assert_eq!(cost, path.cost());
+            cost
+        }
+    }
+
+    #[test]
+    fn open_paths_are_straight() {
+        let test_map = TestMap::new(5);
+        let start = test_map.map_geometry.center();
+        let goal = test_map.map_positions.ring(&start, 5).next().unwrap();
+
+        let path = test_map.grid().find_path(&start, &goal).unwrap();
+
+        assert_eq!(path.len(), 5);
+        assert_eq!(path.destination(), Some(&goal));
+        assert_eq!(test_map.check_path(&start, &path), 5 * TerrainCosts::PLAIN);
+    }
+
+    #[test]
+    fn trivial_and_impossible_paths() {
+        let mut test_map = TestMap::new(3);
+        let center = test_map.map_geometry.center();
+        let wall: Vec<TilePos> = test_map.map_positions.ring(&center, 1).collect();
+        test_map
+            .passability
+            .rebuild_from_positions(wall.iter().copied());
+        let grid = test_map.grid();
+
+        let trivial = grid.find_path_bidirectional(&center, &center).unwrap();
+        assert!(trivial.is_empty());
+
+        let outside = test_map.map_positions.ring(&center, 3).next().unwrap();
+        assert_eq!(grid.find_path(&center, &outside), None);
+        assert_eq!(grid.find_path_bidirectional(&center, &outside), None);
+        assert_eq!(grid.find_path(&outside, &wall[0]), None);
+    }
+
+    #[test]
+    fn expensive_terrain_is_avoided() {
+        let mut test_map = TestMap::new(4);
+        let start = test_map.map_geometry.center();
+        let goal = test_map.map_positions.ring(&start, 2).next().unwrap();
+
+        // Make the direct route very expensive
+        let mut terrain_costs = TerrainCosts::default();
+        terrain_costs.set(TerrainType::High, 100);
+        let direct: Vec<TilePos> = test_map.map_positions.line(&start, &goal).collect();
+        test_map.costs.update(
+            direct[1..2]
+                .iter()
+                .map(|tile_pos| (*tile_pos, TerrainType::High)),
+            &terrain_costs,
+        );
+
+        let path = test_map.grid().find_path(&start, &goal).unwrap();
+        assert!(!path.steps().any(|step| *step == direct[1]));
+        // The goal lies in a straight line from the start, so going around takes one extra step
+        assert_eq!(test_map.check_path(&start, &path), 3 * TerrainCosts::PLAIN);
+    }
+
+    #[test]
+    fn bidirectional_search_matches_astar() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let terrain_types: Vec<TerrainType> = TerrainType::variants().collect();
+
+        for _ in 0..20 {
+            let mut test_map = TestMap::new(6);
+            let positions: Vec<TilePos> =
+                test_map.map_positions.iter_positions().copied().collect();
+
+            let impassable: Vec<TilePos> = positions
+                .iter()
+                .filter(|_| rng.gen_bool(0.25))
+                .copied()
+                .collect();
+            test_map
+                .passability
+                .rebuild_from_positions(impassable.into_iter());
+            let terrain: Vec<(TilePos, TerrainType)> = positions
+                .iter()
+                .map(|tile_pos| (*tile_pos, terrain_types[rng.gen_range(0..3)]))
+                .collect();
+            test_map
+                .costs
+                .update(terrain.into_iter(), &TerrainCosts::default());
+
+            let grid = test_map.grid();
+            for _ in 0..20 {
+                let start = positions[rng.gen_range(0..positions.len())];
+                let goal = positions[rng.gen_range(0..positions.len())];
+
+                let unidirectional = grid.find_path(&start, &goal);
+                let bidirectional = grid.find_path_bidirectional(&start, &goal);
+
+                assert_eq!(
+                    unidirectional.as_ref().map(Path::cost),
+                    bidirectional.as_ref().map(Path::cost)
+                );
+                if let Some(path) = bidirectional {
+                    test_map.check_path(&start, &path);
+                    assert_eq!(path.destination().copied().unwrap_or(start), goal);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! The cost of moving across each type of terrain
+
+use crate::enum_iter::IterableEnum;
+use crate::simulation::map::resources::MapResource;
+use crate::simulation::map::MapPositions;
+use crate::terrain::TerrainType;
+use bevy::prelude::Resource;
+use bevy::utils::HashMap;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+/// The cost of moving onto a tile of each [`TerrainType`].
+///
+/// Costs are always at least 1, so that longer paths are never cheaper than shorter ones
+/// across the same terrain.
+#[derive(Resource, Debug, Clone)]
+pub struct TerrainCosts {
+    /// The cost of moving onto each type of terrain
+    costs: HashMap<TerrainType, u32>,
+}
+
+impl TerrainCosts {
+    /// The default cost of moving onto [`TerrainType::Plain`]
+    pub const PLAIN: u32 = 10;
+    /// The default cost of moving onto [`TerrainType::High`]
+    pub const HIGH: u32 = 25;
+    /// The default cost of moving onto [`TerrainType::Rocky`], if it is not [`Impassable`](super::Impassable)
+    pub const ROCKY: u32 = 40;
+
+    /// The cost of moving onto a tile of the given `terrain_type`
+    pub fn get(&self, terrain_type: &TerrainType) -> u32 {
+        self.costs
+            .get(terrain_type)
+            .copied()
+            .unwrap_or(TerrainCosts::PLAIN)
+    }
+
+    /// Sets the cost of moving onto a tile of the given `terrain_type`
+    ///
+    /// Costs of zero are raised to 1.
+    pub fn set(&mut self, terrain_type: TerrainType, cost: u32) {
+        self.costs.insert(terrain_type, cost.max(1));
+    }
+
+    /// The smallest cost of moving onto any type of terrain
+    pub fn min_cost(&self) -> u32 {
+        TerrainType::variants()
+            .map(|terrain_type| self.get(&terrain_type))
+            .min()
+            .unwrap_or(TerrainCosts::PLAIN)
+    }
+}
+
+impl Default for TerrainCosts {
+    fn default() -> Self {
+        let mut costs = HashMap::new();
+        costs.insert(TerrainType::Plain, TerrainCosts::PLAIN);
+        costs.insert(TerrainType::High, TerrainCosts::HIGH);
+        costs.insert(TerrainType::Rocky, TerrainCosts::ROCKY);
+
+        TerrainCosts { costs }
+    }
+}
+
+/// Caches the cost of moving onto each position of the map, according to [`TerrainCosts`].
+#[derive(Resource)]
+pub struct MovementCostCache {
+    /// The cost of moving onto each position
+    inner: MapResource<u32>,
+    /// The smallest cost of moving onto any position
+    ///
+    /// Used to scale the pathfinding heuristic, so that it never overestimates.
+    min_cost: u32,
+    /// Incremented every time the cost of any position changes
+    version: u64,
+}
+
+impl MovementCostCache {
+    /// Creates a new [`MovementCostCache`], where every position has the cost of [`TerrainType::Plain`]
+    pub fn new(template: &MapPositions, terrain_costs: &TerrainCosts) -> MovementCostCache {
+        let plain_cost = terrain_costs.get(&TerrainType::Plain);
+
+        MovementCostCache {
+            inner: MapResource::new(
+                template,
+                template
+                    .iter_positions()
+                    .map(|position| (*position, plain_cost)),
+            ),
+            min_cost: terrain_costs.min_cost(),
+            version: 0,
+        }
+    }
+
+    /// Updates the cost of the given positions, based on their new terrain types
+    pub fn update(
+        &mut self,
+        terrain: impl Iterator<Item = (TilePos, TerrainType)>,
+        terrain_costs: &TerrainCosts,
+    ) {
+        self.min_cost = terrain_costs.min_cost();
+
+        let mut changed = false;
+        for (position, terrain_type) in terrain {
+            let cost = terrain_costs.get(&terrain_type);
+            if let Some(current_cost) = self.inner.get_mut(&position) {
+                changed |= *current_cost != cost;
+                *current_cost = cost;
+            }
+        }
+
+        if changed {
+            self.version += 1;
+        }
+    }
+
+    /// The cost of moving onto the given position, if it is on the map
+    pub fn get(&self, tile_pos: &TilePos) -> Option<u32> {
+        self.inner.get(tile_pos).copied()
+    }
+
+    /// The smallest cost of moving onto any position
+    pub fn min_cost(&self) -> u32 {
+        self.min_cost
+    }
+
+    /// The cost of moving onto every position, in [`MapIndex`](crate::simulation::map::index::MapIndex) order
+    pub fn as_slice(&self) -> &[u32] {
+        self.inner.as_slice()
+    }
+
+    /// A counter which changes every time the cost of any position changes
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+}
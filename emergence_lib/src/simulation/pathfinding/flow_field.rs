@@ -0,0 +1,386 @@
+//! Flow fields, which guide any number of units towards the nearest of a set of goals
+//!
+//! Rather than searching for a path for each unit, a [`FlowField`] stores the cost of reaching the
+//! nearest goal from every tile on the map, computed with a multi-source Dijkstra search.
+//! Units simply step in the [`best_direction`](FlowField::best_direction) of the tile they are on.
+
+use crate::enum_iter::IterableEnum;
+use crate::simulation::map::hex_patch::HexPatchLocation;
+use crate::simulation::map::resources::MapResource;
+use crate::simulation::map::MapPositions;
+use crate::simulation::pathfinding::astar::PathfindingGrid;
+use crate::simulation::pathfinding::costs::MovementCostCache;
+use crate::simulation::pathfinding::PassabilityCache;
+use crate::simulation::update_passable_filter;
+use bevy::app::{App, CoreStage, Plugin, StartupStage};
+use bevy::prelude::{
+    Commands, Component, IntoSystemDescriptor, Query, Res, ResMut, Resource, With,
+};
+use bevy::utils::HashSet;
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::marker::PhantomData;
+
+/// Marks a tile from which no goal can be reached.
+const UNREACHABLE: u32 = u32::MAX;
+
+/// Marks a tile without a next step, either because it is a goal or because it is unreachable.
+const NO_NEXT_STEP: usize = usize::MAX;
+
+/// The cost of reaching the nearest tile with a `T` component, from every tile on the map.
+///
+/// Moving onto a tile costs as much as it does for [`PathfindingGrid`],
+/// and only passable tiles can be moved through.
+/// The goals themselves may be impassable (as structures are): they can still be approached,
+/// but units should stop once their [`best_direction`](FlowField::best_direction) points at one.
+#[derive(Resource)]
+pub struct FlowField<T: Component> {
+    /// The cost of reaching the nearest goal from each tile
+    distances: MapResource<u32>,
+    /// The index of the neighboring tile to move onto next, from each tile
+    next_steps: Vec<usize>,
+    /// The indices of the goal tiles
+    sources: HashSet<usize>,
+    /// The passability of each tile when the field was last updated
+    passable: Vec<bool>,
+    /// The cost of moving onto each tile when the field was last updated
+    costs: Vec<u32>,
+    /// The [`PassabilityCache`] and [`MovementCostCache`] versions when the field was last updated
+    versions: Option<(u64, u64)>,
+    /// The type of component that marks goals
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: Component> FlowField<T> {
+    /// Creates a new, empty [`FlowField`]: no goals can be reached from any tile
+    pub fn new(map_positions: &MapPositions) -> FlowField<T> {
+        let n_positions = map_positions.n_positions();
+
+        FlowField {
+            distances: MapResource::new(
+                map_positions,
+                map_positions
+                    .iter_positions()
+                    .map(|position| (*position, UNREACHABLE)),
+            ),
+            next_steps: vec![NO_NEXT_STEP; n_positions],
+            sources: HashSet::new(),
+            passable: vec![false; n_positions],
+            costs: vec![0; n_positions],
+            versions: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The cost of reaching the nearest goal from `tile_pos`
+    ///
+    /// Returns `None` if no goal can be reached, or if the tile is not on the map.
+    pub fn distance(&self, tile_pos: &TilePos) -> Option<u32> {
+        self.distances
+            .get(tile_pos)
+            .copied()
+            .filter(|distance| *distance != UNREACHABLE)
+    }
+
+    /// The neighboring tile to move onto from `tile_pos`, in order to reach the nearest goal
+    ///
+    /// Returns `None` if `tile_pos` is itself a goal, or if no goal can be reached.
+    pub fn next_step(&self, tile_pos: &TilePos) -> Option<TilePos> {
+        let index = self.distances.index().get(tile_pos)?;
+        let next_step = self.next_steps[index];
+        (next_step != NO_NEXT_STEP).then(|| self.distances.index().position(next_step))
+    }
+
+    /// The direction to move in from `tile_pos`, in order to reach the nearest goal
+    ///
+    /// This is [`HexPatchLocation::Center`] if `tile_pos` is a goal,
+    /// and `None` if no goal can be reached.
+    pub fn best_direction(&self, tile_pos: &TilePos) -> Option<HexPatchLocation> {
+        let index = self.distances.index().get(tile_pos)?;
+        if self.sources.contains(&index) {
+            return Some(HexPatchLocation::Center);
+        }
+
+        let next_step = self.next_steps[index];
+        let patch = self.distances.index().patch(index);
+        HexPatchLocation::variants().find(|location| patch.get(*location) == Some(&next_step))
+    }
+
+    /// Iterate over the goal tiles
+    pub fn sources(&self) -> impl Iterator<Item = TilePos> + '_ {
+        self.sources
+            .iter()
+            .map(|index| self.distances.index().position(*index))
+    }
+
+    /// Updates the field to reflect the current passability and costs in `grid`, and the
+    /// current set of goal tiles.
+    ///
+    /// Only tiles whose distance could have changed are recomputed.
+    pub fn update(&mut self, grid: &PathfindingGrid, sources: impl Iterator<Item = TilePos>) {
+        let index = self.distances.index();
+        let sources: HashSet<usize> = sources.filter_map(|source| index.get(&source)).collect();
+
+        // Find every tile whose passability, cost or goal status has changed
+        let mut changed: Vec<usize> = self
+            .sources
+            .symmetric_difference(&sources)
+            .copied()
+            .collect();
+        if self.versions != Some(grid.versions()) {
+            changed.extend((0..index.len()).filter(|i| {
+                self.passable[*i] != grid.passable()[*i] || self.costs[*i] != grid.costs()[*i]
+            }));
+            self.passable.copy_from_slice(grid.passable());
+            self.costs.copy_from_slice(grid.costs());
+            self.versions = Some(grid.versions());
+        }
+        self.sources = sources;
+
+        if changed.is_empty() {
+            return;
+        }
+
+        // Every tile whose route passes through a changed tile must be recomputed
+        let mut affected: HashSet<usize> = changed.iter().copied().collect();
+        let mut frontier = changed.clone();
+        while let Some(current) = frontier.pop() {
+            for neighbor in grid.neighbors(current) {
+                if self.next_steps[neighbor] == current && affected.insert(neighbor) {
+                    frontier.push(neighbor);
+                }
+            }
+        }
+
+        let distances = self.distances.as_mut_slice();
+        for tile in affected.iter() {
+            distances[*tile] = UNREACHABLE;
+            self.next_steps[*tile] = NO_NEXT_STEP;
+        }
+
+        // Seed the search with the best known route from each affected tile,
+        // along with every changed tile, since they may offer new routes to their neighbors
+        let mut open = BinaryHeap::new();
+        for tile in affected.iter().chain(changed.iter()) {
+            if self.sources.contains(tile) {
+                distances[*tile] = 0;
+            } else {
+                for neighbor in grid.neighbors(*tile) {
+                    let enterable = self.passable[neighbor] || self.sources.contains(&neighbor);
+                    if enterable && distances[neighbor] != UNREACHABLE {
+                        let distance = distances[neighbor] + self.costs[neighbor];
+                        if distance < distances[*tile] {
+                            distances[*tile] = distance;
+                            self.next_steps[*tile] = neighbor;
+                        }
+                    }
+                }
+            }
+
+            if distances[*tile] != UNREACHABLE {
+                open.push(Reverse((distances[*tile], *tile)));
+            }
+        }
+
+        while let Some(Reverse((distance, current))) = open.pop() {
+            if distance > distances[current] {
+                continue;
+            }
+            // Routes may only lead onto tiles that can be entered
+            if !self.passable[current] && !self.sources.contains(&current) {
+                continue;
+            }
+
+            let through_current = distance + self.costs[current];
+            for neighbor in grid.neighbors(current) {
+                if through_current < distances[neighbor] && !self.sources.contains(&neighbor) {
+                    distances[neighbor] = through_current;
+                    self.next_steps[neighbor] = current;
+                    open.push(Reverse((through_current, neighbor)));
+                }
+            }
+        }
+    }
+}
+
+/// Maintains a [`FlowField`] leading to every tile with a `T` component
+pub struct FlowFieldPlugin<T: Component> {
+    /// The type of component that marks goals
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T: Component> Default for FlowFieldPlugin<T> {
+    fn default() -> Self {
+        FlowFieldPlugin {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Component> Plugin for FlowFieldPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system_to_stage(StartupStage::PostStartup, initialize_flow_field::<T>)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_flow_field::<T>
+                    .after(update_passable_filter)
+                    .after(super::update_movement_costs),
+            );
+    }
+}
+
+/// Create the [`FlowField`] resource
+fn initialize_flow_field<T: Component>(mut commands: Commands, map_positions: Res<MapPositions>) {
+    commands.insert_resource(FlowField::<T>::new(&map_positions));
+}
+
+/// Update the [`FlowField`] resource to match the current state of the map
+fn update_flow_field<T: Component>(
+    goals: Query<&TilePos, With<T>>,
+    map_positions: Res<MapPositions>,
+    passability: Res<PassabilityCache>,
+    movement_costs: Res<MovementCostCache>,
+    mut flow_field: ResMut<FlowField<T>>,
+) {
+    // The map has been replaced, for example by loading a saved game
+    if !std::ptr::eq(flow_field.distances.index(), map_positions.index().as_ref()) {
+        *flow_field = FlowField::new(&map_positions);
+    }
+
+    let grid = PathfindingGrid::new(&map_positions, &passability, &movement_costs);
+    flow_field.update(&grid, goals.iter().copied());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::geometry::hex_distance;
+    use crate::simulation::map::{MapGeometry, MapShape};
+    use crate::simulation::pathfinding::costs::TerrainCosts;
+    use crate::terrain::TerrainType;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Marks goal tiles in tests
+    #[derive(Component)]
+    struct Goal;
+
+    /// Computes the distance from every tile to the nearest source from scratch
+    fn reference_distances(
+        map_positions: &MapPositions,
+        passability: &PassabilityCache,
+        costs: &MovementCostCache,
+        sources: &[TilePos],
+    ) -> Vec<Option<u32>> {
+        let mut flow_field = FlowField::<Goal>::new(map_positions);
+        let grid = PathfindingGrid::new(map_positions, passability, costs);
+        flow_field.update(&grid, sources.iter().copied());
+
+        map_positions
+            .iter_positions()
+            .map(|position| flow_field.distance(position))
+            .collect()
+    }
+
+    #[test]
+    fn distances_grow_away_from_sources() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 4 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let passability = PassabilityCache::new(&map_positions);
+        let costs = MovementCostCache::new(&map_positions, &TerrainCosts::default());
+        let center = map_geometry.center();
+
+        let mut flow_field = FlowField::<Goal>::new(&map_positions);
+        let grid = PathfindingGrid::new(&map_positions, &passability, &costs);
+        flow_field.update(&grid, [center].into_iter());
+
+        assert_eq!(
+            flow_field.best_direction(&center),
+            Some(HexPatchLocation::Center)
+        );
+        for position in map_positions.iter_positions() {
+            let steps = hex_distance(&center, position);
+            assert_eq!(
+                flow_field.distance(position),
+                Some(steps * TerrainCosts::PLAIN)
+            );
+
+            if let Some(next_step) = flow_field.next_step(position) {
+                assert_eq!(hex_distance(&center, &next_step), steps - 1);
+                let direction = flow_field.best_direction(position).unwrap();
+                let patch = map_positions.get_patch(position).unwrap();
+                assert_eq!(patch.get(direction), Some(&next_step));
+            }
+        }
+    }
+
+    #[test]
+    fn walls_block_the_field() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 4 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let mut passability = PassabilityCache::new(&map_positions);
+        let costs = MovementCostCache::new(&map_positions, &TerrainCosts::default());
+        let center = map_geometry.center();
+
+        let mut flow_field = FlowField::<Goal>::new(&map_positions);
+        passability.rebuild_from_positions(map_positions.ring(&center, 1));
+        let grid = PathfindingGrid::new(&map_positions, &passability, &costs);
+        flow_field.update(&grid, [center].into_iter());
+
+        let outside = map_positions.ring(&center, 3).next().unwrap();
+        assert_eq!(flow_field.distance(&outside), None);
+        assert_eq!(flow_field.best_direction(&outside), None);
+    }
+
+    #[test]
+    fn incremental_updates_match_full_recomputation() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 6 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let positions: Vec<TilePos> = map_positions.iter_positions().copied().collect();
+        let terrain_types = [TerrainType::Plain, TerrainType::High];
+        let terrain_costs = TerrainCosts::default();
+
+        let mut passability = PassabilityCache::new(&map_positions);
+        let mut costs = MovementCostCache::new(&map_positions, &terrain_costs);
+        let mut flow_field = FlowField::<Goal>::new(&map_positions);
+
+        for _ in 0..30 {
+            let impassable: Vec<TilePos> = positions
+                .iter()
+                .filter(|_| rng.gen_bool(0.2))
+                .copied()
+                .collect();
+            passability.rebuild_from_positions(impassable.into_iter());
+
+            let n_changed = rng.gen_range(0..10);
+            let terrain: Vec<(TilePos, TerrainType)> = (0..n_changed)
+                .map(|_| {
+                    (
+                        positions[rng.gen_range(0..positions.len())],
+                        terrain_types[rng.gen_range(0..2)],
+                    )
+                })
+                .collect();
+            costs.update(terrain.into_iter(), &terrain_costs);
+
+            let n_sources = rng.gen_range(1..4);
+            let sources: Vec<TilePos> = (0..n_sources)
+                .map(|_| positions[rng.gen_range(0..positions.len())])
+                .collect();
+
+            let grid = PathfindingGrid::new(&map_positions, &passability, &costs);
+            flow_field.update(&grid, sources.iter().copied());
+
+            let incremental: Vec<Option<u32>> = map_positions
+                .iter_positions()
+                .map(|position| flow_field.distance(position))
+                .collect();
+            assert_eq!(
+                incremental,
+                reference_distances(&map_positions, &passability, &costs, &sources)
+            );
+        }
+    }
+}
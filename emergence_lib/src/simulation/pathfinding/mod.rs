@@ -0,0 +1,424 @@
+//! Various odds and ends useful for pathfinding
+use crate as emergence_lib;
+use crate::enum_iter::IterableEnum;
+use crate::organisms::sessile::fungi::Fungi;
+use crate::simulation::map::filters::MapFilter;
+use crate::simulation::map::hex_patch::HexPatch;
+use crate::simulation::map::resources::MapResource;
+use crate::simulation::map::MapPositions;
+use crate::simulation::pathfinding::astar::PathfindingGrid;
+use crate::simulation::pathfinding::costs::{MovementCostCache, TerrainCosts};
+use crate::simulation::pathfinding::flow_field::FlowFieldPlugin;
+use crate::simulation::pathfinding::path::{Destination, Path, PathCache};
+use crate::simulation::update_passable_filter;
+use crate::terrain::TerrainType;
+use bevy::app::{App, CoreStage, Plugin, StartupStage};
+use bevy::prelude::{
+    Changed, Commands, Component, Entity, IntoSystemDescriptor, Local, Query, Res, ResMut,
+    Resource, Without,
+};
+use bevy::utils::HashMap;
+use bevy_ecs_tilemap::tiles::TilePos;
+use emergence_macros::IterableEnum;
+
+pub mod astar;
+pub mod costs;
+pub mod flow_field;
+pub mod path;
+
+/// Plans [`Path`]s for units with a [`Destination`], and keeps them up to date as the map changes
+///
+/// Also maintains a [`FlowField`](flow_field::FlowField) leading to the nearest [`Fungi`].
+pub struct PathfindingPlugin;
+
+impl Plugin for PathfindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TerrainCosts>()
+            .init_resource::<PathCache>()
+            .add_plugin(FlowFieldPlugin::<Fungi>::default())
+            .add_startup_system_to_stage(StartupStage::PostStartup, initialize_movement_costs)
+            .add_system_to_stage(CoreStage::PreUpdate, update_movement_costs)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                invalidate_paths.after(update_passable_filter),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                plan_paths
+                    .after(invalidate_paths)
+                    .after(update_movement_costs),
+            );
+    }
+}
+
+/// Create the [`MovementCostCache`] resource
+fn initialize_movement_costs(
+    mut commands: Commands,
+    map_positions: Res<MapPositions>,
+    terrain_costs: Res<TerrainCosts>,
+) {
+    commands.insert_resource(MovementCostCache::new(&map_positions, &terrain_costs));
+}
+
+/// Update the [`MovementCostCache`] resource when terrain, or the cost of terrain, changes
+fn update_movement_costs(
+    changed_terrain: Query<(&TilePos, &TerrainType), Changed<TerrainType>>,
+    all_terrain: Query<(&TilePos, &TerrainType)>,
+    terrain_costs: Res<TerrainCosts>,
+    mut movement_costs: ResMut<MovementCostCache>,
+) {
+    if terrain_costs.is_changed() {
+        movement_costs.update(
+            all_terrain
+                .iter()
+                .map(|(position, terrain_type)| (*position, *terrain_type)),
+            &terrain_costs,
+        );
+    } else {
+        movement_costs.update(
+            changed_terrain
+                .iter()
+                .map(|(position, terrain_type)| (*position, *terrain_type)),
+            &terrain_costs,
+        );
+    }
+}
+
+/// Removes [`Path`]s that pass through tiles which have become impassable, so that they are re-planned
+fn invalidate_paths(
+    mut commands: Commands,
+    paths: Query<(Entity, &Path)>,
+    passability: Res<PassabilityCache>,
+    mut last_version: Local<u64>,
+) {
+    if passability.version() == *last_version {
+        return;
+    }
+    *last_version = passability.version();
+
+    for (entity, path) in paths.iter() {
+        if !path
+            .steps()
+            .all(|step| passability.is_passable(MovementClass::Walking, step))
+        {
+            commands.entity(entity).remove::<Path>();
+        }
+    }
+}
+
+/// Plans a [`Path`] for each entity with a [`Destination`], but no path to get there
+///
+/// Destinations that cannot be reached are removed.
+fn plan_paths(
+    mut commands: Commands,
+    query: Query<(Entity, &TilePos, &Destination), Without<Path>>,
+    map_positions: Res<MapPositions>,
+    passability: Res<PassabilityCache>,
+    movement_costs: Res<MovementCostCache>,
+    mut path_cache: ResMut<PathCache>,
+) {
+    let grid = PathfindingGrid::new(&map_positions, &passability, &movement_costs);
+
+    for (entity, position, destination) in query.iter() {
+        match path_cache.get_or_find(&grid, position, &destination.0) {
+            Some(path) => {
+                commands.entity(entity).insert(path);
+            }
+            None => {
+                commands.entity(entity).remove::<Destination>();
+            }
+        }
+    }
+}
+
+/// The different ways that organisms can move across the map
+///
+/// Each class has its own notion of which tiles are passable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IterableEnum)]
+pub enum MovementClass {
+    /// Moving along the ground, as ants do
+    Walking,
+    /// Moving along the ground, and over rough obstacles
+    Climbing,
+    /// Moving beneath the ground
+    Burrowing,
+    /// Moving through the air, above all obstacles
+    Flying,
+}
+
+/// Specifies that an entity cannot be moved through by some (or all) [`MovementClass`]es
+///
+/// The default value blocks every movement class.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Impassable {
+    /// Whether each movement class is blocked, indexed by [`IterableEnum::index`]
+    blocked: [bool; MovementClass::N_VARIANTS],
+}
+
+impl Impassable {
+    /// Blocks only the given movement classes
+    pub fn only(classes: &[MovementClass]) -> Impassable {
+        let mut blocked = [false; MovementClass::N_VARIANTS];
+        for class in classes {
+            blocked[class.index()] = true;
+        }
+
+        Impassable { blocked }
+    }
+
+    /// Blocks every movement class except for the given ones
+    pub fn except(classes: &[MovementClass]) -> Impassable {
+        let mut blocked = [true; MovementClass::N_VARIANTS];
+        for class in classes {
+            blocked[class.index()] = false;
+        }
+
+        Impassable { blocked }
+    }
+
+    /// Does this block the given movement class?
+    pub fn blocks(&self, class: MovementClass) -> bool {
+        self.blocked[class.index()]
+    }
+
+    /// Iterate over the movement classes that this blocks
+    pub fn blocked_classes(&self) -> impl Iterator<Item = MovementClass> + '_ {
+        MovementClass::variants().filter(|class| self.blocks(*class))
+    }
+}
+
+impl Default for Impassable {
+    fn default() -> Self {
+        Impassable {
+            blocked: [true; MovementClass::N_VARIANTS],
+        }
+    }
+}
+
+/// Caches, for each [`MovementClass`]:
+/// * `bool` indicating whether a given position is passable
+/// * [`HexPatch<bool>`](HexPatch) indicating whether positions
+/// in hex patch are passable for each position
+///
+/// The cache is maintained incrementally, as [`Impassable`] entities are added, moved and removed.
+#[derive(Resource)]
+pub struct PassabilityCache {
+    /// For each movement class, a [`MapFilter`] which caches whether a given position is passable,
+    /// and its corresponding hex patch
+    filters: HashMap<MovementClass, MapFilter>,
+    /// For each movement class, the number of entities blocking it at each position
+    blocker_counts: HashMap<MovementClass, MapResource<u32>>,
+    /// The position of each [`Impassable`] entity, and the classes that it blocks
+    blockers: HashMap<Entity, (TilePos, Impassable)>,
+    /// Incremented every time the passability of any position changes, for any movement class
+    version: u64,
+}
+
+impl PassabilityCache {
+    /// Creates new [`PassabilityCache`], where every position is passable
+    pub fn new(template: &MapPositions) -> PassabilityCache {
+        PassabilityCache {
+            filters: MovementClass::variants()
+                .map(|class| {
+                    (
+                        class,
+                        MapFilter::new_with_default(true, template, [].into_iter()),
+                    )
+                })
+                .collect(),
+            blocker_counts: MovementClass::variants()
+                .map(|class| (class, MapResource::default_from_template(template)))
+                .collect(),
+            blockers: HashMap::new(),
+            version: 0,
+        }
+    }
+
+    /// Is the given position on the map, and passable for the given movement class?
+    pub fn is_passable(&self, class: MovementClass, tile_pos: &TilePos) -> bool {
+        self.filter(class).get(tile_pos).copied().unwrap_or(false)
+    }
+
+    /// The [`MapFilter`] for the given movement class, which is `true` for passable positions
+    pub fn filter(&self, class: MovementClass) -> &MapFilter {
+        &self.filters[&class]
+    }
+
+    /// A counter which changes every time the passability of any position changes
+    ///
+    /// Useful for invalidating data that was derived from this cache.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Get neighbors associated with a given tile position for the given movement class,
+    /// if it exists in the cache
+    pub fn get_patch(&self, class: MovementClass, tile_pos: &TilePos) -> Option<HexPatch<&bool>> {
+        self.filter(class).get_patch(tile_pos)
+    }
+
+    /// Records that `entity` is impassable at `tile_pos`
+    ///
+    /// If the entity was already recorded, it is moved to its new position and blocked classes.
+    pub fn insert_blocker(&mut self, entity: Entity, tile_pos: TilePos, impassable: Impassable) {
+        if self.blockers.get(&entity) == Some(&(tile_pos, impassable)) {
+            return;
+        }
+
+        self.remove_blocker(entity);
+        for class in impassable.blocked_classes() {
+            self.change_blocker_count(class, &tile_pos, true);
+        }
+        self.blockers.insert(entity, (tile_pos, impassable));
+    }
+
+    /// Records that `entity` is no longer impassable
+    ///
+    /// Entities which were never recorded are ignored.
+    pub fn remove_blocker(&mut self, entity: Entity) {
+        if let Some((tile_pos, impassable)) = self.blockers.remove(&entity) {
+            for class in impassable.blocked_classes() {
+                self.change_blocker_count(class, &tile_pos, false);
+            }
+        }
+    }
+
+    /// Replaces every recorded [`Impassable`] entity with the given ones
+    pub fn rebuild(&mut self, blockers: impl Iterator<Item = (Entity, TilePos, Impassable)>) {
+        let previous: Vec<Entity> = self.blockers.keys().copied().collect();
+        for entity in previous {
+            self.remove_blocker(entity);
+        }
+
+        for (entity, tile_pos, impassable) in blockers {
+            self.insert_blocker(entity, tile_pos, impassable);
+        }
+    }
+
+    /// Replaces every recorded [`Impassable`] entity with one blocking every class at each of `positions`
+    #[cfg(test)]
+    pub(crate) fn rebuild_from_positions(&mut self, positions: impl Iterator<Item = TilePos>) {
+        self.rebuild(
+            positions
+                .enumerate()
+                .map(|(i, position)| (Entity::from_raw(i as u32), position, Impassable::default())),
+        );
+    }
+
+    /// Adds or removes one blocker of `class` at `tile_pos`, updating the filter if needed
+    fn change_blocker_count(&mut self, class: MovementClass, tile_pos: &TilePos, added: bool) {
+        let count = match self
+            .blocker_counts
+            .get_mut(&class)
+            .and_then(|counts| counts.get_mut(tile_pos))
+        {
+            Some(count) => count,
+            None => return,
+        };
+
+        let was_passable = *count == 0;
+        if added {
+            *count += 1;
+        } else {
+            *count = count.saturating_sub(1);
+        }
+        let is_passable = *count == 0;
+
+        if was_passable != is_passable {
+            if let Some(filter) = self.filters.get_mut(&class) {
+                filter.update([(*tile_pos, is_passable)].into_iter());
+            }
+            self.version += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::{MapGeometry, MapShape};
+    use crate::simulation::{remove_impassable_from_filter, update_passable_filter};
+
+    #[test]
+    fn blockers_only_affect_their_classes() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 2 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let mut passability = PassabilityCache::new(&map_positions);
+        let center = map_geometry.center();
+
+        let rock = Entity::from_raw(0);
+        passability.insert_blocker(
+            rock,
+            center,
+            Impassable::except(&[MovementClass::Climbing, MovementClass::Flying]),
+        );
+
+        assert!(!passability.is_passable(MovementClass::Walking, &center));
+        assert!(!passability.is_passable(MovementClass::Burrowing, &center));
+        assert!(passability.is_passable(MovementClass::Climbing, &center));
+        assert!(passability.is_passable(MovementClass::Flying, &center));
+
+        passability.remove_blocker(rock);
+        assert!(MovementClass::variants().all(|class| passability.is_passable(class, &center)));
+    }
+
+    #[test]
+    fn overlapping_blockers_are_counted() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 2 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let mut passability = PassabilityCache::new(&map_positions);
+        let center = map_geometry.center();
+        let neighbor = map_positions.ring(&center, 1).next().unwrap();
+
+        let rock = Entity::from_raw(0);
+        let structure = Entity::from_raw(1);
+        passability.insert_blocker(rock, center, Impassable::default());
+        passability.insert_blocker(structure, center, Impassable::default());
+        let version = passability.version();
+
+        // Re-inserting an unchanged blocker does nothing
+        passability.insert_blocker(rock, center, Impassable::default());
+        assert_eq!(passability.version(), version);
+
+        passability.remove_blocker(rock);
+        assert!(!passability.is_passable(MovementClass::Walking, &center));
+        assert_eq!(passability.version(), version);
+
+        // Moving a blocker frees its old position
+        passability.insert_blocker(structure, neighbor, Impassable::default());
+        assert!(passability.is_passable(MovementClass::Walking, &center));
+        assert!(!passability.is_passable(MovementClass::Walking, &neighbor));
+        assert!(passability.version() > version);
+    }
+
+    #[test]
+    fn cache_follows_impassable_entities() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 2 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let center = map_geometry.center();
+        let neighbor = map_positions.ring(&center, 1).next().unwrap();
+
+        let mut app = App::new();
+        app.insert_resource(PassabilityCache::new(&map_positions))
+            .add_system_to_stage(CoreStage::PreUpdate, update_passable_filter)
+            .add_system_to_stage(CoreStage::Last, remove_impassable_from_filter);
+        let is_passable = |app: &App, position: &TilePos| {
+            app.world
+                .resource::<PassabilityCache>()
+                .is_passable(MovementClass::Walking, position)
+        };
+
+        let entity = app.world.spawn((center, Impassable::default())).id();
+        app.update();
+        assert!(!is_passable(&app, &center));
+
+        *app.world.get_mut::<TilePos>(entity).unwrap() = neighbor;
+        app.update();
+        assert!(is_passable(&app, &center));
+        assert!(!is_passable(&app, &neighbor));
+
+        app.world.despawn(entity);
+        app.update();
+        assert!(is_passable(&app, &neighbor));
+    }
+}
@@ -0,0 +1,150 @@
+//! Routes across the map, and where units want them to lead
+
+use crate::simulation::pathfinding::astar::PathfindingGrid;
+use bevy::prelude::{Component, Resource};
+use bevy::utils::HashMap;
+use bevy_ecs_tilemap::tiles::TilePos;
+use std::collections::VecDeque;
+
+/// A route across the map that a unit is following.
+///
+/// The route does not include the tile that the unit started on.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct Path {
+    /// The remaining tiles to move onto, in order
+    steps: VecDeque<TilePos>,
+    /// The total cost of moving along `steps`, when the path was found
+    cost: u32,
+}
+
+impl Path {
+    /// Creates a new [`Path`] from the tiles to move onto, in order, and their total cost.
+    pub fn new(steps: VecDeque<TilePos>, cost: u32) -> Path {
+        Path { steps, cost }
+    }
+
+    /// The next tile to move onto, if any
+    pub fn next_step(&self) -> Option<&TilePos> {
+        self.steps.front()
+    }
+
+    /// Removes and returns the next tile to move onto, if any
+    pub fn advance(&mut self) -> Option<TilePos> {
+        self.steps.pop_front()
+    }
+
+    /// Iterate over the remaining tiles to move onto, in order
+    pub fn steps(&self) -> impl Iterator<Item = &TilePos> {
+        self.steps.iter()
+    }
+
+    /// The final tile of this path, if any steps remain
+    pub fn destination(&self) -> Option<&TilePos> {
+        self.steps.back()
+    }
+
+    /// The number of steps remaining
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Have all of the steps along this path been taken?
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// The total cost of the path, when it was found
+    pub fn cost(&self) -> u32 {
+        self.cost
+    }
+}
+
+/// The tile that a unit is trying to reach.
+///
+/// Units with a [`Destination`] but no [`Path`] will have a path planned for them,
+/// and both components are removed once the destination has been reached.
+/// If the destination cannot be reached, the [`Destination`] is removed.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Destination(pub TilePos);
+
+/// Caches the result of previous path searches, keyed by their start and goal.
+///
+/// The cache is cleared whenever the passability or cost of any tile changes.
+#[derive(Resource, Debug, Default)]
+pub struct PathCache {
+    /// The path found from each start to each goal, or `None` if the goal was unreachable
+    paths: HashMap<(TilePos, TilePos), Option<Path>>,
+    /// The [`PassabilityCache`](super::PassabilityCache) version that the cached paths were found with
+    passability_version: u64,
+    /// The [`MovementCostCache`](super::costs::MovementCostCache) version that the cached paths were found with
+    cost_version: u64,
+}
+
+impl PathCache {
+    /// Returns the cheapest path from `start` to `goal`, searching for it if it is not cached
+    pub fn get_or_find(
+        &mut self,
+        grid: &PathfindingGrid,
+        start: &TilePos,
+        goal: &TilePos,
+    ) -> Option<Path> {
+        let versions = grid.versions();
+        if versions != (self.passability_version, self.cost_version) {
+            self.paths.clear();
+            (self.passability_version, self.cost_version) = versions;
+        }
+
+        self.paths
+            .entry((*start, *goal))
+            .or_insert_with(|| grid.find_path_bidirectional(start, goal))
+            .clone()
+    }
+
+    /// The number of cached searches
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Are there no cached searches?
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Removes all cached searches
+    pub fn clear(&mut self) {
+        self.paths.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::map::{MapGeometry, MapPositions, MapShape};
+    use crate::simulation::pathfinding::costs::{MovementCostCache, TerrainCosts};
+    use crate::simulation::pathfinding::PassabilityCache;
+
+    #[test]
+    fn cache_is_cleared_when_passability_changes() {
+        let map_geometry = MapGeometry::new(MapShape::Hexagon { radius: 3 });
+        let map_positions = MapPositions::new(&map_geometry);
+        let mut passability = PassabilityCache::new(&map_positions);
+        let costs = MovementCostCache::new(&map_positions, &TerrainCosts::default());
+        let mut path_cache = PathCache::default();
+
+        let start = map_geometry.center();
+        let goal = map_positions.ring(&start, 3).next().unwrap();
+
+        let grid = PathfindingGrid::new(&map_positions, &passability, &costs);
+        let path = path_cache.get_or_find(&grid, &start, &goal).unwrap();
+        path_cache.get_or_find(&grid, &goal, &start);
+        assert_eq!(path_cache.len(), 2);
+
+        let blocked = *path.next_step().unwrap();
+        passability.rebuild_from_positions([blocked].into_iter());
+        let grid = PathfindingGrid::new(&map_positions, &passability, &costs);
+        let new_path = path_cache.get_or_find(&grid, &start, &goal).unwrap();
+
+        assert_eq!(path_cache.len(), 1);
+        assert!(!new_path.steps().any(|step| *step == blocked));
+    }
+}
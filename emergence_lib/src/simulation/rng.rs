@@ -0,0 +1,149 @@
+//! Deterministic sources of randomness for the simulation.
+//!
+//! Every random decision made by the simulation should draw from the [`SimulationRng`] resource,
+//! so that the same seed and the same inputs always produce the same world.
+
+use crate as emergence_lib;
+use crate::enum_iter::IterableEnum;
+use bevy::prelude::Resource;
+use emergence_macros::IterableEnum;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+/// The independent random number streams used by the simulation.
+///
+/// Each subsystem draws from its own stream, so that adding (or removing) random draws in one
+/// subsystem does not change the results produced by another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IterableEnum, Serialize, Deserialize)]
+pub enum RngStream {
+    /// Used when generating terrain.
+    Terrain,
+    /// Used when placing the starting organisms.
+    Organisms,
+    /// Used when units make decisions.
+    Units,
+}
+
+/// The seeded random number generator shared by every system in the simulation.
+///
+/// Internally, this stores one [`ChaCha12Rng`] per [`RngStream`], each seeded from the master seed.
+/// This is the algorithm behind `rand`'s `StdRng`, but unlike `StdRng` its position in the stream
+/// can be read and restored, which lets save files continue each stream where it left off.
+#[derive(Resource, Debug, Clone)]
+pub struct SimulationRng {
+    /// The master seed that every stream is derived from.
+    seed: u64,
+    /// One generator per [`RngStream`], indexed by [`IterableEnum::index`].
+    streams: Vec<ChaCha12Rng>,
+}
+
+impl SimulationRng {
+    /// Creates a new [`SimulationRng`], deriving the seed of each [`RngStream`] from `seed`.
+    pub fn new(seed: u64) -> SimulationRng {
+        SimulationRng {
+            seed,
+            streams: RngStream::variants()
+                .map(|stream| ChaCha12Rng::seed_from_u64(stream_seed(seed, stream)))
+                .collect(),
+        }
+    }
+
+    /// The master seed this generator was created from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Get mutable access to the generator for the given `stream`.
+    pub fn stream(&mut self, stream: RngStream) -> &mut ChaCha12Rng {
+        &mut self.streams[stream.index()]
+    }
+
+    /// The number of 32-bit words that have been drawn from the given `stream` so far.
+    pub fn position(&self, stream: RngStream) -> u64 {
+        // Exhausting 64 bits of words would take far longer than any game will run
+        self.streams[stream.index()].get_word_pos() as u64
+    }
+
+    /// Moves the given `stream` to `position`, as returned by [`SimulationRng::position`].
+    ///
+    /// Subsequent draws continue exactly where a generator at that position would have.
+    pub fn set_position(&mut self, stream: RngStream, position: u64) {
+        self.streams[stream.index()].set_word_pos(position as u128);
+    }
+}
+
+/// Derives the seed of a particular [`RngStream`] from the master `seed`.
+///
+/// This uses the `SplitMix64` finalizer, so that neighboring master seeds produce unrelated streams.
+fn stream_seed(seed: u64, stream: RngStream) -> u64 {
+    let mut z = seed.wrapping_add((stream.index() as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    /// Draws a handful of values from the given stream.
+    fn sample(rng: &mut SimulationRng, stream: RngStream) -> Vec<u64> {
+        (0..8).map(|_| rng.stream(stream).gen()).collect()
+    }
+
+    #[test]
+    fn same_seed_produces_same_values() {
+        let mut a = SimulationRng::new(7);
+        let mut b = SimulationRng::new(7);
+
+        for stream in RngStream::variants() {
+            assert_eq!(sample(&mut a, stream), sample(&mut b, stream));
+        }
+    }
+
+    #[test]
+    fn different_seeds_produce_different_values() {
+        let mut a = SimulationRng::new(7);
+        let mut b = SimulationRng::new(8);
+
+        assert_ne!(
+            sample(&mut a, RngStream::Terrain),
+            sample(&mut b, RngStream::Terrain)
+        );
+    }
+
+    #[test]
+    fn restoring_the_position_continues_the_stream() {
+        let mut uninterrupted = SimulationRng::new(7);
+        sample(&mut uninterrupted, RngStream::Units);
+        // Odd-sized draws leave the generator partway through a block
+        let _: u32 = uninterrupted.stream(RngStream::Units).gen();
+
+        let mut restored = SimulationRng::new(7);
+        restored.set_position(RngStream::Units, uninterrupted.position(RngStream::Units));
+
+        assert_eq!(
+            sample(&mut restored, RngStream::Units),
+            sample(&mut uninterrupted, RngStream::Units)
+        );
+    }
+
+    #[test]
+    fn streams_are_independent() {
+        let mut a = SimulationRng::new(7);
+        let mut b = SimulationRng::new(7);
+
+        // Drawing from one stream must not affect the values produced by another
+        sample(&mut a, RngStream::Terrain);
+        assert_eq!(
+            sample(&mut a, RngStream::Units),
+            sample(&mut b, RngStream::Units)
+        );
+        assert_ne!(
+            sample(&mut b, RngStream::Terrain),
+            sample(&mut b, RngStream::Units)
+        );
+    }
+}
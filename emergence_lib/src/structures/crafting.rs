@@ -3,16 +3,24 @@
 use std::time::Duration;
 
 use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use serde::{Deserialize, Serialize};
 
 use crate::items::{inventory::Inventory, recipe::Recipe};
+use crate::organisms::units::behavior::events::WorkThisTurn;
 
 /// The current state in the crafting progress.
-#[derive(Component, Debug, Default, Clone, PartialEq, Eq)]
+#[derive(Component, Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CraftingState {
     /// There are resources missing for the recipe.
     #[default]
     WaitingForInput,
 
+    /// The resource cost has been paid, but a unit must work at the structure before crafting starts.
+    ///
+    /// Only recipes that [need work](Recipe::needs_work) enter this state.
+    WaitingForWork,
+
     /// The resource cost has been paid and the recipe is being crafted.
     InProgress,
 
@@ -22,7 +30,7 @@ pub enum CraftingState {
 
 /// The input inventory for a structure.
 #[derive(Component, Debug, Default)]
-pub struct InputInventory(Inventory);
+pub struct InputInventory(pub(crate) Inventory);
 
 impl InputInventory {
     /// The inventory holding the items to be crafted.
@@ -33,7 +41,7 @@ impl InputInventory {
 
 /// The output inventory for a structure.
 #[derive(Component, Debug, Default)]
-pub struct OutputInventory(Inventory);
+pub struct OutputInventory(pub(crate) Inventory);
 
 impl OutputInventory {
     /// The inventory for the crafting output.
@@ -44,7 +52,7 @@ impl OutputInventory {
 
 /// The recipe that is currently being crafted, if any.
 #[derive(Component, Debug, Default)]
-pub struct ActiveRecipe(Option<Recipe>);
+pub struct ActiveRecipe(pub(crate) Option<Recipe>);
 
 impl ActiveRecipe {
     /// The currently active recipe, if one has been selected.
@@ -55,7 +63,7 @@ impl ActiveRecipe {
 
 /// The time remaining until the recipe has been crafted.
 #[derive(Component, Debug, Default)]
-pub struct CraftTimer(Timer);
+pub struct CraftTimer(pub(crate) Timer);
 
 impl CraftTimer {
     /// The timer indicating how much longer the crafting process will take.
@@ -158,19 +166,97 @@ fn start_and_finish_crafting(
                 craft_timer.0.set_duration(*recipe.craft_time());
                 craft_timer.0.reset();
 
-                // Start crafting
+                // Start crafting, once any work that the recipe needs has been done
+                *craft_state = if recipe.needs_work() {
+                    CraftingState::WaitingForWork
+                } else {
+                    CraftingState::InProgress
+                };
+            }
+        }
+    }
+}
+
+/// Starts crafting at structures that were waiting for work, once a unit works at them.
+fn work_at_structures(
+    mut work_events: EventReader<WorkThisTurn>,
+    mut query: Query<(&TilePos, &mut CraftingState)>,
+) {
+    for work_event in work_events.iter() {
+        for (tile_pos, mut craft_state) in query.iter_mut() {
+            if *tile_pos == work_event.working_at && *craft_state == CraftingState::WaitingForWork {
                 *craft_state = CraftingState::InProgress;
             }
         }
     }
 }
 
+/// System labels for crafting
+#[derive(SystemLabel)]
+pub enum CraftingSystem {
+    /// Advance the timers of recipes in progress
+    Progress,
+    /// Consume inputs to start recipes, and produce outputs once they are done
+    StartAndFinish,
+}
+
 /// Add crafting capabilities to structures.
 pub struct CraftingPlugin;
 
 impl Plugin for CraftingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(progress_crafting)
-            .add_system(start_and_finish_crafting.after(progress_crafting));
+        app.add_event::<WorkThisTurn>()
+            .add_system(work_at_structures.before(CraftingSystem::Progress))
+            .add_system(progress_crafting.label(CraftingSystem::Progress))
+            .add_system(
+                start_and_finish_crafting
+                    .label(CraftingSystem::StartAndFinish)
+                    .after(CraftingSystem::Progress),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::{count::ItemCount, ItemId};
+
+    #[test]
+    fn recipes_that_need_work_wait_for_it() {
+        let mut app = App::new();
+        app.add_plugin(CraftingPlugin);
+        app.insert_resource(Time::default());
+
+        let recipe = Recipe::new(
+            Vec::new(),
+            vec![ItemCount::one(ItemId::acacia_leaf())],
+            Duration::from_secs(1),
+        )
+        .with_work();
+        let position = TilePos { x: 1, y: 1 };
+        let structure = app
+            .world
+            .spawn((CraftingBundle::new_with_recipe(recipe), position))
+            .id();
+
+        // With no inputs to pay, the structure immediately waits for work
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(
+            app.world.get::<CraftingState>(structure),
+            Some(&CraftingState::WaitingForWork)
+        );
+
+        let unit = app.world.spawn_empty().id();
+        app.world.send_event(WorkThisTurn {
+            unit,
+            working_at: position,
+        });
+        app.update();
+        assert_eq!(
+            app.world.get::<CraftingState>(structure),
+            Some(&CraftingState::InProgress)
+        );
     }
 }
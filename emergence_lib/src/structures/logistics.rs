@@ -0,0 +1,195 @@
+//! Signals that tell units which items structures have to offer, which items they need,
+//! and whether they need work done.
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use crate::signals::configs::SignalConfigs;
+use crate::signals::diffusion::SignalTimestep;
+use crate::signals::emitters::Emitter;
+use crate::signals::field::SignalField;
+use crate::signals::SignalSystem;
+
+use super::crafting::{
+    ActiveRecipe, CraftingState, CraftingSystem, InputInventory, OutputInventory,
+};
+
+/// Marks a structure that cannot make progress until a unit performs work at it.
+///
+/// Kept in sync with [`CraftingState::WaitingForWork`] by [`LogisticsPlugin`].
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct NeedsWork;
+
+/// The amount of each logistic signal emitted per [tick](crate::signals::diffusion::SignalTimestep).
+pub const LOGISTIC_EMISSION_RATE: f32 = 0.01;
+
+/// The logistic signals emitted by a structure, and the amount of each emitted this tick.
+///
+/// - [`Emitter::Push`]: the output inventory is full, so its items should be picked up.
+/// - [`Emitter::Pull`]: the structure is waiting for an input that it does not have enough of.
+/// - [`Emitter::Work`]: the structure [needs work](NeedsWork).
+pub fn logistic_signals(
+    active_recipe: &ActiveRecipe,
+    craft_state: &CraftingState,
+    input: &InputInventory,
+    output: &OutputInventory,
+    needs_work: bool,
+) -> Vec<(Emitter, f32)> {
+    let mut signals = Vec::new();
+
+    if let Some(recipe) = active_recipe.maybe_recipe() {
+        if output.inventory().is_full() {
+            for item_count in recipe.outputs() {
+                if output.inventory().item_count(item_count.item_id()) > 0 {
+                    signals.push((Emitter::Push(*item_count.item_id()), LOGISTIC_EMISSION_RATE));
+                }
+            }
+        }
+
+        if *craft_state == CraftingState::WaitingForInput {
+            for item_count in recipe.inputs() {
+                if !input.inventory().has_count_of_item(item_count) {
+                    signals.push((Emitter::Pull(*item_count.item_id()), LOGISTIC_EMISSION_RATE));
+                }
+            }
+        }
+    }
+
+    if needs_work {
+        signals.push((Emitter::Work, LOGISTIC_EMISSION_RATE));
+    }
+
+    signals
+}
+
+/// Marks the structures that are [waiting for work](CraftingState::WaitingForWork) with
+/// [`NeedsWork`], and unmarks them once work has been done.
+fn update_needs_work(
+    mut commands: Commands,
+    structure_query: Query<(Entity, &CraftingState, Option<&NeedsWork>), Changed<CraftingState>>,
+) {
+    for (entity, craft_state, needs_work) in structure_query.iter() {
+        match (
+            *craft_state == CraftingState::WaitingForWork,
+            needs_work.is_some(),
+        ) {
+            (true, false) => {
+                commands.entity(entity).insert(NeedsWork);
+            }
+            (false, true) => {
+                commands.entity(entity).remove::<NeedsWork>();
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Emits the [`logistic_signals`] of every crafting structure, for each tick simulated this frame.
+///
+/// Like [emitted signals](crate::signals::emitters::EmissionRate), these are deposited straight
+/// into the [`SignalField`], limited by the [saturation](crate::signals::configs::Saturation)
+/// of each signal, so that they are always simulated in the frame they were emitted.
+fn emit_logistic_signals(
+    structure_query: Query<(
+        &TilePos,
+        &ActiveRecipe,
+        &CraftingState,
+        &InputInventory,
+        &OutputInventory,
+        Option<&NeedsWork>,
+    )>,
+    signal_configs: Res<SignalConfigs>,
+    timestep: Res<SignalTimestep>,
+    mut signal_field: ResMut<SignalField>,
+) {
+    let ticks = timestep.ticks_this_frame() as f32;
+    for (tile_pos, active_recipe, craft_state, input, output, needs_work) in structure_query.iter()
+    {
+        let signals = logistic_signals(
+            active_recipe,
+            craft_state,
+            input,
+            output,
+            needs_work.is_some(),
+        );
+
+        for (emitter, rate) in signals {
+            let saturation = signal_configs
+                .get(&emitter)
+                .map(|config| config.saturation)
+                .unwrap_or_default();
+            signal_field.deposit(emitter, tile_pos, rate * ticks, &saturation);
+        }
+    }
+}
+
+/// Makes structures advertise their logistic needs through signals.
+pub struct LogisticsPlugin;
+
+impl Plugin for LogisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_needs_work.after(CraftingSystem::StartAndFinish))
+            .add_system(
+                emit_logistic_signals
+                    .after(CraftingSystem::StartAndFinish)
+                    .before(SignalSystem::Simulate),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::items::{count::ItemCount, inventory::Inventory, recipe::Recipe, ItemId};
+
+    #[test]
+    fn signals_follow_crafting_state() {
+        let recipe = Recipe::new(
+            vec![ItemCount::one(ItemId::test())],
+            vec![ItemCount::one(ItemId::acacia_leaf())],
+            Duration::from_secs(1),
+        );
+        let active_recipe = ActiveRecipe(Some(recipe));
+        let mut input = InputInventory(Inventory::new(1, 1));
+        let mut output = OutputInventory(Inventory::new(1, 1));
+
+        // Missing inputs are pulled
+        let signals = logistic_signals(
+            &active_recipe,
+            &CraftingState::WaitingForInput,
+            &input,
+            &output,
+            false,
+        );
+        assert_eq!(
+            signals,
+            vec![(Emitter::Pull(ItemId::test()), LOGISTIC_EMISSION_RATE)]
+        );
+
+        // Full outputs are pushed, and work is requested
+        input
+            .0
+            .add_all_or_nothing_one_item(&ItemCount::one(ItemId::test()))
+            .unwrap();
+        output
+            .0
+            .add_all_or_nothing_one_item(&ItemCount::one(ItemId::acacia_leaf()))
+            .unwrap();
+        let signals = logistic_signals(
+            &active_recipe,
+            &CraftingState::WaitingForInput,
+            &input,
+            &output,
+            true,
+        );
+        assert_eq!(
+            signals,
+            vec![
+                (Emitter::Push(ItemId::acacia_leaf()), LOGISTIC_EMISSION_RATE),
+                (Emitter::Work, LOGISTIC_EMISSION_RATE),
+            ]
+        );
+    }
+}
@@ -7,8 +7,10 @@ use crate::simulation::pathfinding::Impassable;
 use bevy::prelude::*;
 
 use self::crafting::CraftingPlugin;
+use self::logistics::LogisticsPlugin;
 
 pub mod crafting;
+pub mod logistics;
 
 /// The data needed to build a structure
 #[derive(Bundle, Default)]
@@ -37,6 +39,6 @@ pub struct StructuresPlugin;
 
 impl Plugin for StructuresPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(CraftingPlugin);
+        app.add_plugin(CraftingPlugin).add_plugin(LogisticsPlugin);
     }
 }
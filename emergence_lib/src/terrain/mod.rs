@@ -5,7 +5,7 @@ pub mod entity_map;
 
 use crate as emergence_lib;
 
-use crate::simulation::pathfinding::Impassable;
+use crate::simulation::pathfinding::{Impassable, MovementClass};
 use crate::terrain::components::{HighTerrain, PlainTerrain, RockyTerrain};
 use bevy::ecs::component::Component;
 use bevy::ecs::entity::Entity;
@@ -13,9 +13,12 @@ use bevy::ecs::system::Commands;
 
 use bevy_ecs_tilemap::tiles::TilePos;
 use emergence_macros::IterableEnum;
+use serde::{Deserialize, Serialize};
 
 /// Available terrain types.
-#[derive(Component, Clone, Copy, Hash, Eq, PartialEq, IterableEnum)]
+#[derive(
+    Component, Debug, Clone, Copy, Hash, Eq, PartialEq, IterableEnum, Serialize, Deserialize,
+)]
 pub enum TerrainType {
     /// Terrain with no distinguishing characteristics.
     Plain,
@@ -37,7 +40,11 @@ impl TerrainType {
             }
             TerrainType::Rocky => {
                 builder.insert(RockyTerrain);
-                builder.insert(Impassable);
+                // Rocks can be climbed or flown over, but not walked or burrowed through
+                builder.insert(Impassable::except(&[
+                    MovementClass::Climbing,
+                    MovementClass::Flying,
+                ]));
             }
             TerrainType::High => {
                 builder.insert(HighTerrain);
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_ecs_tilemap::tiles::TilePos;
+use emergence_lib::enum_iter::IterableEnum;
+use emergence_lib::organisms::units::Ant;
+use emergence_lib::organisms::Organism;
+use emergence_lib::simulation::generation::GenerationConfig;
+use emergence_lib::terrain::TerrainType;
+use emergence_lib::testing::simulation_app;
+use std::time::Duration;
+
+/// The terrain type at each position, and the position of each organism.
+type WorldSummary = (Vec<(u32, u32, usize)>, Vec<(u32, u32)>);
+
+/// Generates a world from the given seed, returning its terrain and organism positions.
+fn generate_world(seed: u64) -> WorldSummary {
+    let mut app = simulation_app(GenerationConfig {
+        seed,
+        ..Default::default()
+    });
+    app.update();
+
+    let mut terrain: Vec<(u32, u32, usize)> = app
+        .world
+        .query::<(&TilePos, &TerrainType)>()
+        .iter(&app.world)
+        .map(|(position, terrain_type)| (position.x, position.y, terrain_type.index()))
+        .collect();
+    terrain.sort();
+
+    let mut organisms: Vec<(u32, u32)> = app
+        .world
+        .query_filtered::<&TilePos, With<Organism>>()
+        .iter(&app.world)
+        .map(|position| (position.x, position.y))
+        .collect();
+    organisms.sort();
+
+    (terrain, organisms)
+}
+
+/// The amount of time simulated by each frame of [`ant_trajectories`]
+const FRAME_TIME: Duration = Duration::from_millis(100);
+
+/// Simulates the world generated from the given seed for `frames` frames of [`FRAME_TIME`],
+/// returning the positions of every ant after each frame.
+fn ant_trajectories(seed: u64, frames: u32) -> Vec<Vec<(u32, u32)>> {
+    let mut app = simulation_app(GenerationConfig {
+        seed,
+        ..Default::default()
+    });
+    // Advance time by exactly the same amount each frame, rather than following the clock
+    let startup = app.world.resource::<Time>().startup();
+
+    (1..=frames)
+        .map(|frame| {
+            app.insert_resource(TimeUpdateStrategy::ManualInstant(
+                startup + FRAME_TIME * frame,
+            ));
+            app.update();
+
+            let mut ants: Vec<(u32, u32)> = app
+                .world
+                .query_filtered::<&TilePos, With<Ant>>()
+                .iter(&app.world)
+                .map(|position| (position.x, position.y))
+                .collect();
+            ants.sort();
+            ants
+        })
+        .collect()
+}
+
+#[test]
+fn same_seed_generates_same_world() {
+    assert_eq!(generate_world(42), generate_world(42));
+}
+
+#[test]
+fn different_seeds_generate_different_worlds() {
+    assert_ne!(generate_world(42), generate_world(43));
+}
+
+#[test]
+fn same_seed_produces_same_ant_trajectories() {
+    let trajectories = ant_trajectories(42, 50);
+
+    // The ants must actually move for the comparison to mean anything
+    assert_ne!(trajectories.first(), trajectories.last());
+    assert_eq!(trajectories, ant_trajectories(42, 50));
+}
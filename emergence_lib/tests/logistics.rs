@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use bevy_ecs_tilemap::tiles::TilePos;
+use emergence_lib::items::count::ItemCount;
+use emergence_lib::items::recipe::Recipe;
+use emergence_lib::items::ItemId;
+use emergence_lib::signals::diffusion::SignalTimestep;
+use emergence_lib::signals::emitters::Emitter;
+use emergence_lib::signals::field::SignalField;
+use emergence_lib::simulation::generation::GenerationConfig;
+use emergence_lib::simulation::map::MapGeometry;
+use emergence_lib::structures::crafting::{CraftingBundle, CraftingState};
+use emergence_lib::structures::logistics::NeedsWork;
+use emergence_lib::testing::simulation_app;
+
+#[test]
+fn structures_waiting_for_work_emit_work_signals() {
+    let mut app = simulation_app(GenerationConfig::default());
+    app.update();
+    // Simulate as many signal ticks as possible each frame
+    app.world.resource_mut::<SignalTimestep>().tick = Duration::from_micros(1);
+
+    let position: TilePos = app.world.resource::<MapGeometry>().center();
+    let recipe = Recipe::new(
+        Vec::new(),
+        vec![ItemCount::one(ItemId::acacia_leaf())],
+        Duration::from_secs(1),
+    )
+    .with_work();
+    let structure = app
+        .world
+        .spawn((CraftingBundle::new_with_recipe(recipe), position))
+        .id();
+
+    for _ in 0..5 {
+        app.update();
+    }
+
+    assert_eq!(
+        app.world.get::<CraftingState>(structure),
+        Some(&CraftingState::WaitingForWork)
+    );
+    assert!(app.world.get::<NeedsWork>(structure).is_some());
+    assert!(
+        app.world
+            .resource::<SignalField>()
+            .get(&Emitter::Work, &position)
+            > 0.0
+    );
+}
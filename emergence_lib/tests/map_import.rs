@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use emergence_lib::organisms::sessile::fungi::Fungi;
+use emergence_lib::organisms::units::Ant;
+use emergence_lib::simulation::generation::import::ImportedMap;
+use emergence_lib::simulation::generation::GenerationConfig;
+use emergence_lib::simulation::map::MapPositions;
+use emergence_lib::terrain::TerrainType;
+use emergence_lib::testing::simulation_app;
+
+/// A small scenario, with a rocky wall between two ants and a fungus
+const SCENARIO: &str = "
+    5 3
+    . . # . .
+    a . # . f
+    . . # . a
+";
+
+#[test]
+fn imported_maps_replace_generation() {
+    let imported = ImportedMap::from_text(SCENARIO).unwrap();
+    let mut app = simulation_app(GenerationConfig {
+        map_import: Some(imported.clone()),
+        ..Default::default()
+    });
+    app.update();
+
+    assert_eq!(app.world.resource::<MapPositions>().n_positions(), 15);
+
+    let mut terrain: Vec<(TilePos, TerrainType)> = app
+        .world
+        .query::<(&TilePos, &TerrainType)>()
+        .iter(&app.world)
+        .map(|(position, terrain_type)| (*position, *terrain_type))
+        .collect();
+    terrain.sort_by_key(|(position, _)| (position.y, position.x));
+    let mut expected_terrain: Vec<(TilePos, TerrainType)> = imported.terrain().collect();
+    expected_terrain.sort_by_key(|(position, _)| (position.y, position.x));
+    assert_eq!(terrain, expected_terrain);
+
+    let n_fungi = app
+        .world
+        .query_filtered::<&TilePos, With<Fungi>>()
+        .iter(&app.world)
+        .count();
+    let n_ants = app
+        .world
+        .query_filtered::<&TilePos, With<Ant>>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(n_fungi, 1);
+    assert_eq!(n_ants, 2);
+}
@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::tiles::TilePos;
+use emergence_lib::enum_iter::IterableEnum;
+use emergence_lib::organisms::sessile::plants::Acacia;
+use emergence_lib::organisms::units::Ant;
+use emergence_lib::save::format::{SaveFile, SAVE_FORMAT_VERSION};
+use emergence_lib::save::{load_world, save_world, SaveError};
+use emergence_lib::signals::configs::SignalConfigs;
+use emergence_lib::signals::emitters::{Emitter, StockEmitter};
+use emergence_lib::signals::field::SignalField;
+use emergence_lib::signals::registry::CustomEmitter;
+use emergence_lib::simulation::generation::GenerationConfig;
+use emergence_lib::simulation::map::MapPositions;
+use emergence_lib::simulation::rng::{RngStream, SimulationRng};
+use emergence_lib::structures::crafting::CraftingState;
+use emergence_lib::structures::logistics::NeedsWork;
+use emergence_lib::terrain::TerrainType;
+use emergence_lib::testing::simulation_app;
+use rand::Rng;
+
+/// Generates a world, and adds a signal to it.
+fn populated_app(seed: u64) -> App {
+    let mut app = simulation_app(GenerationConfig {
+        seed,
+        ..Default::default()
+    });
+    app.update();
+
+    let center = *app
+        .world
+        .resource::<MapPositions>()
+        .iter_positions()
+        .next()
+        .unwrap();
+    app.world.resource_mut::<SignalField>().increment(
+        Emitter::Stock(StockEmitter::Ant),
+        &center,
+        0.5,
+    );
+
+    app
+}
+
+/// The terrain type of each tile, and the position of each ant.
+type WorldSummary = (Vec<(u32, u32, usize)>, Vec<(u32, u32)>);
+
+/// Summarizes the entities in the world of `app`.
+fn summarize(app: &mut App) -> WorldSummary {
+    let mut terrain: Vec<(u32, u32, usize)> = app
+        .world
+        .query::<(&TilePos, &TerrainType)>()
+        .iter(&app.world)
+        .map(|(position, terrain_type)| (position.x, position.y, terrain_type.index()))
+        .collect();
+    terrain.sort();
+
+    let mut ants: Vec<(u32, u32)> = app
+        .world
+        .query_filtered::<&TilePos, With<Ant>>()
+        .iter(&app.world)
+        .map(|position| (position.x, position.y))
+        .collect();
+    ants.sort();
+
+    (terrain, ants)
+}
+
+#[test]
+fn save_file_round_trips_through_ron() {
+    let mut app = populated_app(1);
+    let save_file = save_world(&mut app.world).unwrap();
+
+    let contents = save_file.to_ron_string().unwrap();
+    let parsed = SaveFile::from_ron_str(&contents).unwrap();
+
+    assert_eq!(parsed.version, SAVE_FORMAT_VERSION);
+    assert_eq!(parsed.tiles.len(), save_file.tiles.len());
+    assert_eq!(parsed.organisms.len(), save_file.organisms.len());
+}
+
+#[test]
+fn loading_restores_saved_world() {
+    let mut original = populated_app(1);
+    let save_file = save_world(&mut original.world).unwrap();
+
+    // Load into a world generated from a different seed
+    let mut loaded = populated_app(2);
+    load_world(&mut loaded.world, save_file.clone()).unwrap();
+
+    // Saving again before any time has passed must produce an identical file
+    let resaved = save_world(&mut loaded.world).unwrap();
+    assert_eq!(
+        resaved.to_ron_string().unwrap(),
+        save_file.to_ron_string().unwrap()
+    );
+    assert_eq!(summarize(&mut loaded), summarize(&mut original));
+}
+
+#[test]
+fn unknown_versions_are_rejected() {
+    let mut app = populated_app(1);
+    let mut save_file = save_world(&mut app.world).unwrap();
+    save_file.version = SAVE_FORMAT_VERSION + 1;
+
+    let contents = save_file.to_ron_string().unwrap();
+    assert!(matches!(
+        SaveFile::from_ron_str(&contents),
+        Err(SaveError::UnsupportedVersion { .. })
+    ));
+}
+
+#[test]
+fn custom_emitter_names_are_restored() {
+    let mut original = populated_app(1);
+    let mut signal_configs = original.world.resource_mut::<SignalConfigs>();
+    let config = *signal_configs
+        .get(&Emitter::Stock(StockEmitter::Ant))
+        .unwrap();
+    let mut trail = CustomEmitter::new("trail", config);
+    trail.display_name = "Foraging trail".to_string();
+    let trail = signal_configs.register_custom_emitter(trail).unwrap();
+    let save_file = save_world(&mut original.world).unwrap();
+
+    // Register a different emitter first, so that "trail" would be given another id
+    let mut loaded = populated_app(2);
+    loaded
+        .world
+        .resource_mut::<SignalConfigs>()
+        .register_custom_emitter(CustomEmitter::new("alarm", config))
+        .unwrap();
+    load_world(&mut loaded.world, save_file).unwrap();
+
+    let signal_configs = loaded.world.resource::<SignalConfigs>();
+    assert_eq!(signal_configs.custom_emitter("trail"), Some(trail));
+    assert_eq!(signal_configs.custom_emitter("alarm"), None);
+    assert_eq!(signal_configs.label(&trail), "Foraging trail");
+    assert_eq!(signal_configs.get(&trail), Some(&config));
+}
+
+#[test]
+fn structures_keep_waiting_for_work() {
+    let mut original = populated_app(1);
+    let mut acacias = original
+        .world
+        .query_filtered::<(&TilePos, &mut CraftingState), With<Acacia>>();
+    let (position, mut craft_state) = acacias.iter_mut(&mut original.world).next().unwrap();
+    let position = *position;
+    *craft_state = CraftingState::WaitingForWork;
+    let save_file = save_world(&mut original.world).unwrap();
+
+    let mut loaded = populated_app(2);
+    load_world(&mut loaded.world, save_file).unwrap();
+
+    let mut structures = loaded
+        .world
+        .query::<(&TilePos, &CraftingState, Option<&NeedsWork>)>();
+    let (_, craft_state, needs_work) = structures
+        .iter(&loaded.world)
+        .find(|(tile_pos, _, _)| **tile_pos == position)
+        .unwrap();
+    assert_eq!(*craft_state, CraftingState::WaitingForWork);
+    assert!(needs_work.is_some());
+}
+
+#[test]
+fn random_draws_continue_after_loading() {
+    let mut original = populated_app(1);
+    for _ in 0..5 {
+        original.update();
+    }
+    let save_file = save_world(&mut original.world).unwrap();
+
+    let mut loaded = populated_app(2);
+    load_world(&mut loaded.world, save_file).unwrap();
+
+    for stream in RngStream::variants() {
+        let uninterrupted: u64 = original
+            .world
+            .resource_mut::<SimulationRng>()
+            .stream(stream)
+            .gen();
+        let reloaded: u64 = loaded
+            .world
+            .resource_mut::<SimulationRng>()
+            .stream(stream)
+            .gen();
+        assert_eq!(uninterrupted, reloaded);
+    }
+}
+
+#[test]
+fn wind_is_restored() {
+    let mut original = populated_app(1);
+    original
+        .world
+        .resource_mut::<SignalField>()
+        .set_uniform_wind(Vec2::new(0.5, -0.25));
+    let save_file = save_world(&mut original.world).unwrap();
+
+    let mut loaded = populated_app(2);
+    load_world(&mut loaded.world, save_file).unwrap();
+
+    let positions: Vec<TilePos> = loaded
+        .world
+        .resource::<MapPositions>()
+        .iter_positions()
+        .copied()
+        .collect();
+    let signal_field = loaded.world.resource::<SignalField>();
+    for position in positions {
+        assert_eq!(signal_field.wind(&position), Some(Vec2::new(0.5, -0.25)));
+    }
+}
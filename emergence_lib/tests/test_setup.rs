@@ -1,6 +1,7 @@
 // use common::{bevy_app, interaction_app, minimal_app, simulation_app};
 
-use emergence_lib::simulation::generation::GenerationConfig;
+use emergence_lib::simulation::generation::{GenerationConfig, GenerationError, GenerationFailed};
+use emergence_lib::simulation::map::MapShape;
 use emergence_lib::testing::{interaction_app, minimal_app, simulation_app};
 
 #[test]
@@ -17,6 +18,20 @@ fn simulation_app_can_update() {
     app.update()
 }
 
+#[test]
+fn impossible_generation_is_reported() {
+    let mut app = simulation_app(GenerationConfig {
+        map_shape: MapShape::Hexagon { radius: 1 },
+        n_ant: 100,
+        ..Default::default()
+    });
+
+    app.update();
+
+    let GenerationFailed(error) = app.world.resource::<GenerationFailed>();
+    assert!(matches!(error, GenerationError::NotEnoughTiles { .. }));
+}
+
 #[test]
 #[ignore = "Cannot test interaction without a virtual window."]
 // Blocked on https://github.com/bevyengine/bevy/pull/6256